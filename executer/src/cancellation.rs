@@ -0,0 +1,162 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use futures::Future;
+use tokio::time::{Duration, Instant};
+use tonic::metadata::MetadataMap;
+use tonic::{Result, Status};
+
+use crate::ErrorCollection;
+
+// Tonic starts its own timer for the `grpc-timeout` header when the request arrives, and answers
+// `cancelled` when it fires; the query has to give up a bit earlier to answer `deadline_exceeded`.
+const DEADLINE_MARGIN: Duration = Duration::from_millis(20);
+
+// Stops the query between its rounds, once its deadline passes or it is cancelled. The default one
+// never does.
+#[derive(Debug, Clone, Default)]
+pub struct Cancellation {
+    deadline: Option<Instant>,
+    cancelled: Arc<AtomicBool>,
+}
+
+// Cancels the query when dropped, e.g. together with the future of a request whose client went away
+pub struct CancelOnDrop(Cancellation);
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        self.0.cancelled.store(true, Ordering::Relaxed);
+    }
+}
+
+impl Cancellation {
+    // With the deadline set by the client in the `grpc-timeout` header, e.g. "100m" for 100 ms
+    pub fn from_metadata(metadata: &MetadataMap) -> Self {
+        let timeout = metadata
+            .get("grpc-timeout")
+            .and_then(|value| value.to_str().ok())
+            .and_then(parse_timeout);
+
+        if let Some(timeout) = timeout {
+            debug!("query deadline in {timeout:?}");
+        }
+
+        Cancellation {
+            deadline: timeout
+                .map(|timeout| Instant::now() + timeout.saturating_sub(DEADLINE_MARGIN)),
+            cancelled: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    pub fn guard(&self) -> CancelOnDrop {
+        CancelOnDrop(self.clone())
+    }
+
+    pub fn check(&self) -> Result<()> {
+        if self.cancelled.load(Ordering::Relaxed) {
+            return Err(ErrorCollection::query_cancelled());
+        }
+
+        if matches!(self.deadline, Some(deadline) if deadline <= Instant::now()) {
+            return Err(ErrorCollection::deadline_exceeded());
+        }
+
+        Ok(())
+    }
+
+    // Gives up waiting for the query at the deadline, even in the middle of a round; the query
+    // stops by itself before its next round.
+    pub async fn until_deadline<T>(&self, query: impl Future<Output = Result<T>>) -> Result<T> {
+        match self.deadline {
+            Some(deadline) => tokio::time::timeout_at(deadline, query)
+                .await
+                .map_err(|_| ErrorCollection::deadline_exceeded())?,
+            None => query.await,
+        }
+    }
+}
+
+fn parse_timeout(timeout: &str) -> Option<Duration> {
+    let (value, unit) = timeout.split_at(timeout.len().checked_sub(1)?);
+
+    // At most 8 digits, so that the multiplications below do not overflow
+    if value.len() > 8 {
+        return None;
+    }
+
+    let value: u64 = value.parse().ok()?;
+
+    let timeout = match unit {
+        "H" => Duration::from_secs(value * 60 * 60),
+        "M" => Duration::from_secs(value * 60),
+        "S" => Duration::from_secs(value),
+        "m" => Duration::from_millis(value),
+        "u" => Duration::from_micros(value),
+        "n" => Duration::from_nanos(value),
+        _ => return None,
+    };
+
+    Some(timeout)
+}
+
+impl ErrorCollection {
+    fn query_cancelled() -> Status {
+        Status::cancelled("Query was cancelled by the client")
+    }
+
+    fn deadline_exceeded() -> Status {
+        Status::deadline_exceeded("Query did not finish before its deadline")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_all_units() {
+        assert_eq!(parse_timeout("2H"), Some(Duration::from_secs(2 * 60 * 60)));
+        assert_eq!(parse_timeout("3M"), Some(Duration::from_secs(3 * 60)));
+        assert_eq!(parse_timeout("4S"), Some(Duration::from_secs(4)));
+        assert_eq!(parse_timeout("100m"), Some(Duration::from_millis(100)));
+        assert_eq!(parse_timeout("250u"), Some(Duration::from_micros(250)));
+        assert_eq!(parse_timeout("999n"), Some(Duration::from_nanos(999)));
+        assert_eq!(parse_timeout("0m"), Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn parses_eight_digits_without_overflow() {
+        assert_eq!(
+            parse_timeout("99999999H"),
+            Some(Duration::from_secs(99_999_999 * 60 * 60))
+        );
+        assert_eq!(parse_timeout("123456789S"), None);
+    }
+
+    #[test]
+    fn rejects_malformed_timeouts() {
+        assert_eq!(parse_timeout(""), None);
+        assert_eq!(parse_timeout("m"), None);
+        assert_eq!(parse_timeout("100"), None);
+        assert_eq!(parse_timeout("100x"), None);
+        assert_eq!(parse_timeout("-5S"), None);
+        assert_eq!(parse_timeout("1.5S"), None);
+        assert_eq!(parse_timeout("5 S"), None);
+    }
+
+    #[test]
+    fn default_cancellation_never_stops() {
+        assert!(Cancellation::default().check().is_ok());
+    }
+
+    #[test]
+    fn dropped_guard_cancels() {
+        let cancellation = Cancellation::default();
+        drop(cancellation.guard());
+
+        assert_eq!(
+            cancellation.check().unwrap_err().code(),
+            tonic::Code::Cancelled
+        );
+    }
+}
@@ -1,8 +1,12 @@
-use futures::Stream;
+use futures::{Future, Stream};
 use std::pin::Pin;
+use tokio::task::JoinHandle;
 use tonic::{Request, Response, Result, Status};
 
+use crate::cancellation::Cancellation;
 use crate::globals;
+use crate::queries_manager::QueryId;
+use crate::ErrorCollection;
 
 use generated::executer;
 use generated::executer::executer_server::Executer;
@@ -10,22 +14,197 @@ use generated::executer::executer_server::Executer;
 pub struct ExecuterService {}
 
 type NodeStream = Pin<Box<dyn Stream<Item = Result<executer::Node, Status>> + Send + 'static>>;
+type ReachableStream =
+    Pin<Box<dyn Stream<Item = Result<executer::ReachableResponse, Status>> + Send + 'static>>;
 type CoordinatesStream =
     Pin<Box<dyn Stream<Item = Result<executer::CoordinateResponse, Status>> + Send + 'static>>;
 
+// Query run in its own task, so that it gets to tell the workers to forget it even if the client
+// goes away. A query whose results do not reach the client (it went away, or its deadline passed
+// first) is forgotten once it finishes.
+struct SpawnedQuery<T: Send + 'static> {
+    // Taken once the client got the outcome of the query
+    task: Option<JoinHandle<Result<T>>>,
+    // Handles in the results which the client would forget
+    query_ids: fn(&T) -> Vec<QueryId>,
+}
+
+impl<T: Send + 'static> SpawnedQuery<T> {
+    fn spawn(
+        query: impl Future<Output = Result<T>> + Send + 'static,
+        query_ids: fn(&T) -> Vec<QueryId>,
+    ) -> Self {
+        SpawnedQuery {
+            task: Some(tokio::spawn(query)),
+            query_ids,
+        }
+    }
+
+    async fn wait(mut self, cancellation: &Cancellation) -> Result<T> {
+        let mut finished = false;
+        let task = self.task.as_mut().unwrap();
+
+        let result = cancellation
+            .until_deadline(async {
+                let result = task.await;
+                finished = true;
+                result.map_err(ErrorCollection::query_task_failed)?
+            })
+            .await;
+
+        // A failed query already told the workers to forget it
+        if finished {
+            self.task = None;
+        }
+
+        result
+    }
+}
+
+impl<T: Send + 'static> Drop for SpawnedQuery<T> {
+    fn drop(&mut self) {
+        let (task, query_ids) = match self.task.take() {
+            Some(task) => (task, self.query_ids),
+            None => return,
+        };
+
+        tokio::spawn(async move {
+            let query_ids = match task.await {
+                Ok(Ok(results)) => query_ids(&results),
+                _ => Vec::new(),
+            };
+
+            for query_id in query_ids {
+                info!("forgetting query[id: {query_id}], its results did not reach the client");
+
+                let request = executer::QueryId { query_id };
+
+                if let Err(status) = globals::queries_manager().forget_query(request).await {
+                    warn!("Cannot forget query[id: {query_id}]: {status:?}");
+                }
+            }
+        });
+    }
+}
+
 #[tonic::async_trait]
 impl Executer for ExecuterService {
     async fn shortest_path_query(
         &self,
         request: Request<executer::QueryData>,
     ) -> Result<Response<executer::QueryResults>> {
+        let cancellation = Cancellation::from_metadata(request.metadata());
+
+        // This future is dropped if the client goes away
+        let _guard = cancellation.guard();
+
+        let query = SpawnedQuery::spawn(
+            globals::queries_manager()
+                .shortest_path_query(request.into_inner(), cancellation.clone()),
+            |results| results.query_id.into_iter().collect(),
+        );
+
+        let response = query.wait(&cancellation).await?;
+
+        Ok(Response::new(response))
+    }
+
+    async fn one_to_many_query(
+        &self,
+        request: Request<executer::OneToManyQueryData>,
+    ) -> Result<Response<executer::OneToManyResults>> {
+        let cancellation = Cancellation::from_metadata(request.metadata());
+        let _guard = cancellation.guard();
+
+        let query = SpawnedQuery::spawn(
+            globals::queries_manager()
+                .one_to_many_query(request.into_inner(), cancellation.clone()),
+            |response| {
+                response
+                    .results
+                    .iter()
+                    .filter_map(|result| result.query_id)
+                    .collect()
+            },
+        );
+
+        let response = query.wait(&cancellation).await?;
+
+        Ok(Response::new(response))
+    }
+
+    type ReachableQueryStream = ReachableStream;
+
+    async fn reachable_query(
+        &self,
+        request: Request<executer::ReachableQueryData>,
+    ) -> Result<Response<ReachableStream>> {
+        let cancellation = Cancellation::from_metadata(request.metadata());
+        let stream =
+            globals::queries_manager().get_reachable_stream(request.into_inner(), cancellation);
+
+        Ok(Response::new(Box::pin(stream) as ReachableStream))
+    }
+
+    async fn nearest_nodes(
+        &self,
+        request: Request<executer::NearestNodesQuery>,
+    ) -> Result<Response<executer::NearestNodesResults>> {
         let response = globals::queries_manager()
-            .shortest_path_query(request.into_inner())
+            .nearest_nodes(request.into_inner())
             .await?;
 
         Ok(Response::new(response))
     }
 
+    async fn shortest_path_between_coordinates(
+        &self,
+        request: Request<executer::CoordinatesQueryData>,
+    ) -> Result<Response<executer::CoordinatesQueryResults>> {
+        let cancellation = Cancellation::from_metadata(request.metadata());
+        let _guard = cancellation.guard();
+
+        let query = SpawnedQuery::spawn(
+            globals::queries_manager()
+                .shortest_path_between_coordinates(request.into_inner(), cancellation.clone()),
+            |response| {
+                response
+                    .results
+                    .iter()
+                    .filter_map(|results| results.query_id)
+                    .collect()
+            },
+        );
+
+        let response = query.wait(&cancellation).await?;
+
+        Ok(Response::new(response))
+    }
+
+    async fn k_shortest_paths_query(
+        &self,
+        request: Request<executer::KShortestPathsQueryData>,
+    ) -> Result<Response<executer::KShortestPathsResults>> {
+        let cancellation = Cancellation::from_metadata(request.metadata());
+        let _guard = cancellation.guard();
+
+        let query = SpawnedQuery::spawn(
+            globals::queries_manager()
+                .k_shortest_paths_query(request.into_inner(), cancellation.clone()),
+            |response| {
+                response
+                    .paths
+                    .iter()
+                    .filter_map(|path| path.query_id)
+                    .collect()
+            },
+        );
+
+        let response = query.wait(&cancellation).await?;
+
+        Ok(Response::new(response))
+    }
+
     type BacktrackPathForQueryStream = NodeStream;
 
     async fn backtrack_path_for_query(
@@ -56,4 +235,21 @@ impl Executer for ExecuterService {
 
         Ok(Response::new(Box::pin(stream) as CoordinatesStream))
     }
+
+    async fn update_edge_weights(
+        &self,
+        request: Request<executer::EdgeWeightUpdates>,
+    ) -> Result<Response<executer::EdgeWeightUpdatesResult>> {
+        let response = globals::queries_manager()
+            .update_edge_weights(request.into_inner())
+            .await?;
+
+        Ok(Response::new(response))
+    }
+}
+
+impl ErrorCollection {
+    fn query_task_failed(error: tokio::task::JoinError) -> Status {
+        Status::internal(format!("Query task failed: {error}"))
+    }
 }
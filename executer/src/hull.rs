@@ -0,0 +1,105 @@
+// Points are (lat, lon); the hull is computed in the plane of the coordinates, which is accurate
+// enough for regions reachable by a single query.
+
+// Positive if `o -> a -> b` turns counter-clockwise (with longitude as the x axis)
+fn cross(o: (f64, f64), a: (f64, f64), b: (f64, f64)) -> f64 {
+    (a.1 - o.1) * (b.0 - o.0) - (a.0 - o.0) * (b.1 - o.1)
+}
+
+// Convex hull by the monotone chain algorithm, counter-clockwise.
+pub fn convex_hull(mut points: Vec<(f64, f64)>) -> Vec<(f64, f64)> {
+    points.sort_by(|a, b| a.1.total_cmp(&b.1).then(a.0.total_cmp(&b.0)));
+    points.dedup();
+
+    if points.len() < 3 {
+        return points;
+    }
+
+    let mut hull: Vec<(f64, f64)> = Vec::with_capacity(points.len() + 1);
+
+    let push = |hull: &mut Vec<(f64, f64)>, point, chain_start| {
+        while hull.len() >= chain_start + 2
+            && cross(hull[hull.len() - 2], hull[hull.len() - 1], point) <= 0.
+        {
+            hull.pop();
+        }
+
+        hull.push(point);
+    };
+
+    for &point in points.iter() {
+        push(&mut hull, point, 0);
+    }
+
+    // The upper chain starts at the last point of the lower one
+    let upper_start = hull.len() - 1;
+
+    for &point in points.iter().rev().skip(1) {
+        push(&mut hull, point, upper_start);
+    }
+
+    // Same as the first point
+    hull.pop();
+
+    hull
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_counter_clockwise(hull: &[(f64, f64)]) -> bool {
+        (0..hull.len()).all(|i| {
+            let next = (i + 1) % hull.len();
+            let after = (i + 2) % hull.len();
+            cross(hull[i], hull[next], hull[after]) > 0.
+        })
+    }
+
+    #[test]
+    fn hull_skips_inner_points() {
+        let points = vec![(0., 0.), (1., 1.), (0., 2.), (2., 2.), (2., 0.), (1., 0.5)];
+
+        let hull = convex_hull(points);
+
+        assert_eq!(hull.len(), 4);
+        assert!(is_counter_clockwise(&hull));
+
+        for corner in [(0., 0.), (0., 2.), (2., 2.), (2., 0.)] {
+            assert!(hull.contains(&corner));
+        }
+    }
+
+    #[test]
+    fn hull_skips_points_on_edges() {
+        let points = vec![(0., 0.), (0., 1.), (0., 2.), (2., 0.), (1., 1.)];
+
+        let hull = convex_hull(points);
+
+        assert_eq!(hull.len(), 3);
+        assert!(is_counter_clockwise(&hull));
+        assert!(!hull.contains(&(0., 1.)));
+        assert!(!hull.contains(&(1., 1.)));
+    }
+
+    #[test]
+    fn collinear_points_give_segment() {
+        let points = vec![(1., 1.), (0., 0.), (3., 3.), (2., 2.)];
+
+        let mut hull = convex_hull(points);
+        hull.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        assert_eq!(hull, vec![(0., 0.), (3., 3.)]);
+    }
+
+    #[test]
+    fn fewer_than_three_points_are_kept() {
+        assert!(convex_hull(Vec::new()).is_empty());
+        assert_eq!(convex_hull(vec![(1., 2.)]), vec![(1., 2.)]);
+        assert_eq!(
+            convex_hull(vec![(1., 2.), (1., 2.), (1., 2.)]),
+            vec![(1., 2.)]
+        );
+        assert_eq!(convex_hull(vec![(0., 1.), (0., 0.)]).len(), 2);
+    }
+}
@@ -0,0 +1,271 @@
+// Bookkeeping of Yen's algorithm: each next path deviates from one of the previous ones at some
+// (spur) node. The paths from the spur nodes are found by the caller, with the distributed search.
+
+use generated::executer::Node;
+
+use crate::queries_manager::{NodeId, ShortestPathLen};
+
+// Path found by the k-shortest paths query
+pub struct Route {
+    // From the first node to the last one
+    pub nodes: Vec<Node>,
+    // Lengths of the path from the first node to each node
+    pub lengths: Vec<ShortestPathLen>,
+}
+
+// Search for the path from the spur node which avoids the shared prefix and the edges by which the
+// previous routes with the same prefix continue
+pub struct SpurSearch {
+    // Position of the spur node in the last route
+    pub spur: usize,
+    pub from: NodeId,
+    pub excluded_node_ids: Vec<NodeId>,
+    pub excluded_edges: Vec<(NodeId, NodeId)>,
+}
+
+#[derive(Default)]
+pub struct KShortestPaths {
+    routes: Vec<Route>,
+    // Paths which may become the next route, with their lengths
+    candidates: Vec<(ShortestPathLen, Vec<Node>)>,
+}
+
+impl KShortestPaths {
+    pub fn routes(&self) -> &[Route] {
+        &self.routes
+    }
+
+    pub fn into_routes(self) -> Vec<Route> {
+        self.routes
+    }
+
+    // Adds the path unless it is already known
+    pub fn add_candidate(&mut self, len: ShortestPathLen, nodes: Vec<Node>) {
+        let known = self
+            .routes
+            .iter()
+            .map(|route| &route.nodes)
+            .chain(self.candidates.iter().map(|(_, nodes)| nodes))
+            .any(|known| *known == nodes);
+
+        if !known {
+            self.candidates.push((len, nodes));
+        }
+    }
+
+    // Removes the shortest candidate, which is the next route
+    pub fn take_shortest(&mut self) -> Option<Vec<Node>> {
+        let shortest = self
+            .candidates
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, (len, _))| *len)
+            .map(|(position, _)| position)?;
+
+        Some(self.candidates.swap_remove(shortest).1)
+    }
+
+    pub fn push_route(&mut self, route: Route) {
+        self.routes.push(route);
+    }
+
+    // Searches for the paths deviating from the last route, one per its node but the last one
+    pub fn spur_searches(&self) -> Vec<SpurSearch> {
+        let Some(last) = self.routes.last() else {
+            return Vec::new();
+        };
+
+        (0..last.nodes.len().saturating_sub(1))
+            .map(|spur| {
+                let root = &last.nodes[..=spur];
+
+                let excluded_node_ids = root[..spur].iter().map(|node| node.node_id).collect();
+                let excluded_edges = self
+                    .routes
+                    .iter()
+                    .filter(|route| route.nodes.len() > spur + 1 && route.nodes[..=spur] == *root)
+                    .map(|route| (route.nodes[spur].node_id, route.nodes[spur + 1].node_id))
+                    .collect();
+
+                SpurSearch {
+                    spur,
+                    from: root[spur].node_id,
+                    excluded_node_ids,
+                    excluded_edges,
+                }
+            })
+            .collect()
+    }
+
+    // Adds the path found by the spur search from the node at `spur` of the last route
+    pub fn add_spur_path(&mut self, spur: usize, spur_len: ShortestPathLen, spur_nodes: Vec<Node>) {
+        let last = self.routes.last().unwrap();
+
+        let len = last.lengths[spur] + spur_len;
+        let mut nodes = last.nodes[..spur].to_vec();
+        nodes.extend(spur_nodes);
+
+        self.add_candidate(len, nodes);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeMap, HashSet};
+
+    use super::*;
+
+    // Example of Yen's algorithm from Wikipedia, with the nodes C..H numbered 1..6
+    const EDGES: [(NodeId, NodeId, ShortestPathLen); 9] = [
+        (1, 2, 3),
+        (1, 3, 2),
+        (2, 4, 4),
+        (3, 2, 1),
+        (3, 4, 2),
+        (3, 5, 3),
+        (4, 5, 2),
+        (4, 6, 1),
+        (5, 6, 2),
+    ];
+
+    fn node(node_id: NodeId) -> Node {
+        Node {
+            node_id,
+            worker_id: 1,
+        }
+    }
+
+    fn weight(from: NodeId, to: NodeId) -> ShortestPathLen {
+        EDGES
+            .iter()
+            .find(|(edge_from, edge_to, _)| (*edge_from, *edge_to) == (from, to))
+            .unwrap()
+            .2
+    }
+
+    // Dijkstra avoiding the excluded nodes and edges, in place of the distributed search
+    fn find_path(search: &SpurSearch, to: NodeId) -> Option<(ShortestPathLen, Vec<Node>)> {
+        let excluded_nodes: HashSet<NodeId> = search.excluded_node_ids.iter().copied().collect();
+        let mut visits = BTreeMap::from([(search.from, (0, search.from))]);
+        let mut settled = HashSet::new();
+
+        loop {
+            let (&current, &(len, _)) = visits
+                .iter()
+                .filter(|(node_id, _)| !settled.contains(*node_id))
+                .min_by_key(|(_, (len, _))| *len)?;
+
+            if current == to {
+                break;
+            }
+
+            settled.insert(current);
+
+            for &(from, next, weight) in EDGES.iter() {
+                let excluded =
+                    excluded_nodes.contains(&next) || search.excluded_edges.contains(&(from, next));
+
+                if from != current || excluded || settled.contains(&next) {
+                    continue;
+                }
+
+                let visit = visits.entry(next).or_insert((len + weight, current));
+
+                if visit.0 > len + weight {
+                    *visit = (len + weight, current);
+                }
+            }
+        }
+
+        let mut nodes = vec![node(to)];
+
+        while nodes.last().unwrap().node_id != search.from {
+            let parent = visits[&nodes.last().unwrap().node_id].1;
+            nodes.push(node(parent));
+        }
+
+        nodes.reverse();
+        Some((visits[&to].0, nodes))
+    }
+
+    fn route(nodes: Vec<Node>) -> Route {
+        let mut lengths = vec![0];
+
+        for pair in nodes.windows(2) {
+            lengths.push(lengths.last().unwrap() + weight(pair[0].node_id, pair[1].node_id));
+        }
+
+        Route { nodes, lengths }
+    }
+
+    fn k_shortest_paths(from: NodeId, to: NodeId, k: usize) -> Vec<Route> {
+        let mut paths = KShortestPaths::default();
+        let first = SpurSearch {
+            spur: 0,
+            from,
+            excluded_node_ids: Vec::new(),
+            excluded_edges: Vec::new(),
+        };
+
+        if let Some((len, nodes)) = find_path(&first, to) {
+            paths.add_candidate(len, nodes);
+        }
+
+        while paths.routes().len() < k {
+            let Some(nodes) = paths.take_shortest() else {
+                break;
+            };
+
+            paths.push_route(route(nodes));
+
+            for search in paths.spur_searches() {
+                if let Some((len, nodes)) = find_path(&search, to) {
+                    paths.add_spur_path(search.spur, len, nodes);
+                }
+            }
+        }
+
+        paths.into_routes()
+    }
+
+    fn ids(route: &Route) -> Vec<NodeId> {
+        route.nodes.iter().map(|node| node.node_id).collect()
+    }
+
+    #[test]
+    fn paths_come_shortest_first() {
+        let routes = k_shortest_paths(1, 6, 3);
+        let lengths: Vec<_> = routes
+            .iter()
+            .map(|route| *route.lengths.last().unwrap())
+            .collect();
+
+        assert_eq!(lengths, vec![5, 7, 8]);
+        assert_eq!(ids(&routes[0]), vec![1, 3, 4, 6]);
+        assert_eq!(ids(&routes[1]), vec![1, 3, 5, 6]);
+    }
+
+    #[test]
+    fn all_loopless_paths_are_found_once() {
+        let routes = k_shortest_paths(1, 6, 10);
+        let lengths: Vec<_> = routes
+            .iter()
+            .map(|route| *route.lengths.last().unwrap())
+            .collect();
+
+        assert_eq!(lengths, vec![5, 7, 8, 8, 8, 11, 11]);
+
+        let paths: HashSet<Vec<NodeId>> = routes.iter().map(ids).collect();
+        let expected = [
+            vec![1, 3, 4, 6],
+            vec![1, 3, 5, 6],
+            vec![1, 2, 4, 6],
+            vec![1, 3, 2, 4, 6],
+            vec![1, 3, 4, 5, 6],
+            vec![1, 2, 4, 5, 6],
+            vec![1, 3, 2, 4, 5, 6],
+        ];
+
+        assert_eq!(paths, expected.into_iter().collect());
+    }
+}
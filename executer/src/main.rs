@@ -2,8 +2,14 @@ extern crate pretty_env_logger;
 #[macro_use]
 extern crate log;
 
+mod cancellation;
 mod executer_service;
 mod globals;
+mod hull;
+mod k_shortest;
+mod membership;
+mod overlay;
+mod ownership_index;
 mod queries_manager;
 mod query_coordinator;
 mod workers_connection;
@@ -33,12 +39,18 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     info!("connected to manager");
 
     let addresses = workers_connection::get_sorted_workers_addresses(&mut manager).await?;
-    let workers = workers_connection::connect_to_all_workers(addresses).await?;
+    let workers = workers_connection::connect_to_all_workers(addresses)?;
+
+    for worker in workers.iter() {
+        tokio::spawn(workers_connection::probe(worker.clone()));
+    }
 
     globals::QUERIES_MANAGER
         .set(QueriesManager::new(workers))
         .map_err(|_| Status::internal("Cannot set global QUERIES_MANAGER"))?;
 
+    tokio::spawn(globals::queries_manager().watch_workers(manager));
+
     info!("creating the server");
     let service = ExecuterService {};
     let server = ExecuterServer::new(service);
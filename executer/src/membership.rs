@@ -0,0 +1,193 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use tonic::Status;
+
+use generated::worker;
+
+use crate::overlay::Overlay;
+use crate::ownership_index::OwnershipIndex;
+use crate::workers_connection::{self, WorkerAddrList, WorkerList, WorkerState};
+
+// An ownership index which could not be fetched from all workers is fetched again after this
+// interval, doubled after each such fetch up to the maximum.
+const INDEX_RETRY_INTERVAL: Duration = Duration::from_secs(1);
+const MAX_INDEX_RETRY_INTERVAL: Duration = Duration::from_secs(60);
+
+struct IndexCache {
+    // Empty until fetched
+    index: Arc<OwnershipIndex>,
+    // Whether the index was fetched from all workers; otherwise it is fetched again after
+    // `retry_at`
+    complete: bool,
+    retry_at: Option<Instant>,
+    retry_interval: Duration,
+}
+
+impl IndexCache {
+    fn usable(&self, now: Instant) -> bool {
+        !self.index.is_stale()
+            && (self.complete || matches!(self.retry_at, Some(retry_at) if now < retry_at))
+    }
+
+    fn back_off(&mut self, now: Instant) {
+        self.complete = false;
+        self.retry_at = Some(now + self.retry_interval);
+        self.retry_interval = std::cmp::min(self.retry_interval * 2, MAX_INDEX_RETRY_INTERVAL);
+    }
+}
+
+// Workers known to the executer, with what is derived from the set of them. Replaced as a whole
+// when the workers change; queries keep the membership they started with.
+pub struct Membership {
+    // Sorted by ID
+    pub workers: WorkerList,
+    // Fetched by the first overlay query with the given metric
+    overlays: Mutex<HashMap<worker::Metric, Arc<Overlay>>>,
+    // Fetched by the first query which looks for the owners of its nodes; the others wait for it
+    ownership_index: tokio::sync::Mutex<IndexCache>,
+}
+
+impl Membership {
+    pub fn new(workers: WorkerList) -> Self {
+        Membership {
+            workers,
+            overlays: Mutex::new(HashMap::new()),
+            ownership_index: tokio::sync::Mutex::new(IndexCache {
+                index: Arc::default(),
+                complete: false,
+                retry_at: None,
+                retry_interval: INDEX_RETRY_INTERVAL,
+            }),
+        }
+    }
+
+    pub async fn get_overlay(
+        &self,
+        metric: worker::Metric,
+        refresh: bool,
+    ) -> Result<Arc<Overlay>, Status> {
+        if !refresh {
+            if let Some(overlay) = self.overlays.lock().unwrap().get(&metric) {
+                return Ok(overlay.clone());
+            }
+        }
+
+        let overlay = Arc::new(Overlay::fetch(&self.workers, metric).await?);
+        self.overlays
+            .lock()
+            .unwrap()
+            .insert(metric, overlay.clone());
+
+        Ok(overlay)
+    }
+
+    pub fn clear_overlays(&self) {
+        self.overlays.lock().unwrap().clear();
+    }
+
+    // Skips the workers which are down, and falls back to an empty index (so that all workers are
+    // asked) if some worker cannot list its nodes. Such an index is fetched again after a backoff,
+    // as is the one found stale by a query.
+    pub async fn get_ownership_index(&self) -> Arc<OwnershipIndex> {
+        let mut cache = self.ownership_index.lock().await;
+        let now = Instant::now();
+
+        if cache.usable(now) {
+            return cache.index.clone();
+        }
+
+        let available: WorkerList = self
+            .workers
+            .iter()
+            .filter(|worker| worker.health.state() != WorkerState::Down)
+            .cloned()
+            .collect();
+
+        match OwnershipIndex::fetch(&available).await {
+            Ok(index) => {
+                cache.index = Arc::new(index);
+
+                match available.len() == self.workers.len() {
+                    true => {
+                        cache.complete = true;
+                        cache.retry_interval = INDEX_RETRY_INTERVAL;
+                    }
+                    false => {
+                        let down = self.workers.len() - available.len();
+                        info!("ownership index misses the nodes of {down} workers which are down");
+                        cache.back_off(now);
+                    }
+                }
+            }
+            Err(status) => {
+                warn!("Cannot fetch the ownership index: {status:?}");
+
+                if cache.index.is_stale() {
+                    cache.index = Arc::default();
+                }
+
+                cache.back_off(now);
+            }
+        }
+
+        cache.index.clone()
+    }
+
+    // Membership of the workers at `addrs` (sorted by ID), or `None` if they did not change.
+    // Unchanged workers keep their connections and health; the new ones start being probed and
+    // the removed ones stop.
+    pub fn update(&self, addrs: WorkerAddrList) -> Option<Membership> {
+        let unchanged = addrs.len() == self.workers.len()
+            && addrs
+                .iter()
+                .zip(self.workers.iter())
+                .all(|(entry, worker)| {
+                    entry.worker_id == worker.id && entry.address == worker.address
+                });
+
+        if unchanged {
+            return None;
+        }
+
+        let mut workers = WorkerList::with_capacity(addrs.len());
+
+        for entry in addrs {
+            let current = self
+                .workers
+                .iter()
+                .find(|worker| worker.id == entry.worker_id && worker.address == entry.address);
+
+            if let Some(worker) = current {
+                workers.push(worker.clone());
+                continue;
+            }
+
+            let worker_id = entry.worker_id;
+
+            match workers_connection::connect_to_worker(entry) {
+                Ok(worker) => {
+                    info!("worker[id {worker_id}] joined at '{}'", worker.address);
+                    tokio::spawn(workers_connection::probe(worker.clone()));
+                    workers.push(worker);
+                }
+                Err(e) => warn!("Cannot connect to worker[id {worker_id}]: {e:?}"),
+            }
+        }
+
+        let kept: HashSet<_> = workers
+            .iter()
+            .map(|worker| (worker.id, worker.address.as_str()))
+            .collect();
+
+        for worker in self.workers.iter() {
+            if !kept.contains(&(worker.id, worker.address.as_str())) {
+                info!("worker[id {}] at '{}' left", worker.id, worker.address);
+                worker.health.remove();
+            }
+        }
+
+        Some(Membership::new(workers))
+    }
+}
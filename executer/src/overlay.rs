@@ -0,0 +1,191 @@
+use std::cmp::Reverse;
+use std::collections::hash_map::Entry;
+use std::collections::{BinaryHeap, HashMap};
+
+use futures::future::try_join_all;
+use tonic::Status;
+
+use generated::worker;
+use worker::{BoundaryOverlayRequest, NodeDistance};
+
+use crate::queries_manager::{NodeId, ShortestPathLen};
+use crate::workers_connection::{self, Worker, WorkerId};
+
+#[derive(Debug, Clone, Copy)]
+struct OverlayArc {
+    to: NodeId,
+    weight: ShortestPathLen,
+    // Owner of `to`
+    worker_id: WorkerId,
+    // Shortest path within the worker rather than a single boundary edge
+    local: bool,
+}
+
+// Node on the path found in the overlay
+#[derive(Debug, Clone, Copy)]
+pub struct Hop {
+    pub node_id: NodeId,
+    pub worker_id: WorkerId,
+    // Reached from the previous hop by a path within `worker_id`, which has to be unpacked
+    pub local: bool,
+    // Version of the fragment of `worker_id` the hop was found in
+    pub graph_version: u64,
+}
+
+// Boundary overlays of all workers joined into one graph
+#[derive(Debug)]
+pub struct Overlay {
+    arcs: HashMap<NodeId, Vec<OverlayArc>>,
+    // Versions of the worker fragments the overlay was built from
+    versions: HashMap<WorkerId, u64>,
+}
+
+impl Overlay {
+    pub async fn fetch(workers: &[Worker], metric: worker::Metric) -> Result<Self, Status> {
+        info!("fetching {metric:?} boundary overlays of all workers");
+
+        workers_connection::check_all(workers)?;
+
+        let requests = workers.iter().map(|worker| {
+            let mut channel = worker.channel.clone();
+            let request = BoundaryOverlayRequest {
+                metric: metric as i32,
+            };
+
+            async move {
+                let overlay = channel.get_boundary_overlay(request).await?.into_inner();
+                Ok::<_, Status>((worker.id, overlay))
+            }
+        });
+
+        let mut arcs: HashMap<NodeId, Vec<OverlayArc>> = HashMap::new();
+        let mut versions = HashMap::new();
+
+        for (worker_id, overlay) in try_join_all(requests).await? {
+            versions.insert(worker_id, overlay.graph_version);
+
+            for edge in overlay.clique_edges {
+                arcs.entry(edge.node_from_id).or_default().push(OverlayArc {
+                    to: edge.node_to_id,
+                    weight: edge.weight,
+                    worker_id,
+                    local: true,
+                });
+            }
+
+            for edge in overlay.boundary_edges {
+                arcs.entry(edge.node_from_id).or_default().push(OverlayArc {
+                    to: edge.node_to_id,
+                    weight: edge.weight,
+                    worker_id: edge.worker_to_id,
+                    local: false,
+                });
+            }
+        }
+
+        Ok(Overlay { arcs, versions })
+    }
+
+    pub fn version(&self, worker_id: WorkerId) -> Option<u64> {
+        self.versions.get(&worker_id).copied()
+    }
+
+    // Dijkstra from `from` to `to` over the overlay. `source_leg` are the distances from `from`
+    // within its worker, `target_leg` the distances to `to` within its worker.
+    pub fn shortest_path(
+        &self,
+        from: Hop,
+        to: Hop,
+        source_leg: &[NodeDistance],
+        target_leg: &[NodeDistance],
+    ) -> Option<(ShortestPathLen, Vec<Hop>)> {
+        let source_arcs: Vec<OverlayArc> = source_leg
+            .iter()
+            .map(|node| OverlayArc {
+                to: node.node_id,
+                weight: node.distance,
+                worker_id: from.worker_id,
+                local: true,
+            })
+            .collect();
+
+        // Keyed by the source of the arc
+        let target_arcs: HashMap<NodeId, OverlayArc> = target_leg
+            .iter()
+            .map(|node| {
+                let arc = OverlayArc {
+                    to: to.node_id,
+                    weight: node.distance,
+                    worker_id: to.worker_id,
+                    local: true,
+                };
+
+                (node.node_id, arc)
+            })
+            .collect();
+
+        // Node ID -> (distance, the arc by which it was reached and its source)
+        let mut visits: HashMap<NodeId, (ShortestPathLen, Option<(NodeId, OverlayArc)>)> =
+            HashMap::new();
+        let mut queue = BinaryHeap::new();
+
+        visits.insert(from.node_id, (0, None));
+        queue.push(Reverse((0, from.node_id)));
+
+        while let Some(Reverse((shortest, node_id))) = queue.pop() {
+            if visits[&node_id].0 < shortest {
+                continue;
+            }
+
+            if node_id == to.node_id {
+                break;
+            }
+
+            let leg = match node_id == from.node_id {
+                true => source_arcs.as_slice(),
+                false => &[],
+            };
+
+            let arcs = self
+                .arcs
+                .get(&node_id)
+                .map_or(&[][..], |arcs| arcs.as_slice());
+
+            for arc in leg.iter().chain(arcs).chain(target_arcs.get(&node_id)) {
+                let new_shortest = shortest + arc.weight;
+                let reached = Some((node_id, *arc));
+
+                match visits.entry(arc.to) {
+                    Entry::Occupied(mut entry) if entry.get().0 > new_shortest => {
+                        entry.insert((new_shortest, reached));
+                    }
+                    Entry::Occupied(_) => continue,
+                    Entry::Vacant(entry) => {
+                        entry.insert((new_shortest, reached));
+                    }
+                }
+
+                queue.push(Reverse((new_shortest, arc.to)));
+            }
+        }
+
+        let shortest_path_len = visits.get(&to.node_id)?.0;
+        let mut hops = Vec::new();
+        let mut current = to.node_id;
+
+        while let Some((parent, arc)) = visits[&current].1 {
+            hops.push(Hop {
+                node_id: current,
+                worker_id: arc.worker_id,
+                local: arc.local,
+                graph_version: self.version(arc.worker_id).unwrap_or_default(),
+            });
+            current = parent;
+        }
+
+        hops.push(from);
+        hops.reverse();
+
+        Some((shortest_path_len, hops))
+    }
+}
@@ -0,0 +1,153 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use futures::future::try_join_all;
+use futures::TryStreamExt;
+use tonic::Status;
+
+use generated::worker::NodeIdRanges;
+
+use crate::queries_manager::NodeId;
+use crate::workers_connection::{Worker, WorkerId};
+
+#[derive(Debug, Clone, Copy)]
+struct OwnedRange {
+    first: NodeId,
+    last: NodeId,
+    worker_id: WorkerId,
+}
+
+// Owners of the nodes, as ranges of consecutive ids sorted by their first id. It is not refreshed
+// when the workers reload their fragments, which usually keep the partition of the nodes; nodes
+// missing from it are looked up by asking all workers. The owners found in it are checked by the
+// queries, which mark it stale if some owner no longer has its node.
+#[derive(Debug, Default)]
+pub struct OwnershipIndex {
+    ranges: Vec<OwnedRange>,
+    stale: AtomicBool,
+}
+
+impl OwnershipIndex {
+    pub async fn fetch(workers: &[Worker]) -> Result<Self, Status> {
+        info!("fetching node ids of all workers");
+
+        let requests = workers.iter().map(|worker| {
+            let mut channel = worker.channel.clone();
+
+            async move {
+                let batches: Vec<NodeIdRanges> = channel
+                    .list_node_ids(())
+                    .await?
+                    .into_inner()
+                    .try_collect()
+                    .await?;
+
+                let ranges: Vec<_> = batches
+                    .into_iter()
+                    .flat_map(|batch| batch.ranges)
+                    .map(|range| OwnedRange {
+                        first: range.first,
+                        last: range.last,
+                        worker_id: worker.id,
+                    })
+                    .collect();
+
+                Ok::<_, Status>(ranges)
+            }
+        });
+
+        let mut ranges: Vec<OwnedRange> = try_join_all(requests)
+            .await?
+            .into_iter()
+            .flatten()
+            .collect();
+
+        ranges.sort_unstable_by_key(|range| range.first);
+
+        info!("ownership index holds {} ranges of node ids", ranges.len());
+
+        Ok(OwnershipIndex {
+            ranges,
+            stale: AtomicBool::new(false),
+        })
+    }
+
+    pub fn owner(&self, node_id: NodeId) -> Option<WorkerId> {
+        let position = self.ranges.partition_point(|range| range.first <= node_id);
+        let range = self.ranges[..position].last()?;
+
+        (node_id <= range.last).then_some(range.worker_id)
+    }
+
+    pub fn mark_stale(&self) {
+        if !self.stale.swap(true, Ordering::Relaxed) {
+            warn!("ownership index is out of date, it will be fetched again");
+        }
+    }
+
+    pub fn is_stale(&self) -> bool {
+        self.stale.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn index(ranges: &[(NodeId, NodeId, WorkerId)]) -> OwnershipIndex {
+        let ranges = ranges
+            .iter()
+            .map(|&(first, last, worker_id)| OwnedRange {
+                first,
+                last,
+                worker_id,
+            })
+            .collect();
+
+        OwnershipIndex {
+            ranges,
+            stale: AtomicBool::new(false),
+        }
+    }
+
+    #[test]
+    fn owner_of_nodes_in_ranges() {
+        let index = index(&[(1, 1, 7), (3, 5, 2), (6, 9, 4), (20, u64::MAX, 1)]);
+
+        assert_eq!(index.owner(1), Some(7));
+        assert_eq!(index.owner(3), Some(2));
+        assert_eq!(index.owner(5), Some(2));
+        assert_eq!(index.owner(6), Some(4));
+        assert_eq!(index.owner(9), Some(4));
+        assert_eq!(index.owner(20), Some(1));
+        assert_eq!(index.owner(u64::MAX), Some(1));
+    }
+
+    #[test]
+    fn no_owner_of_nodes_between_ranges() {
+        let index = index(&[(1, 1, 7), (3, 5, 2), (20, 30, 1)]);
+
+        assert_eq!(index.owner(0), None);
+        assert_eq!(index.owner(2), None);
+        assert_eq!(index.owner(6), None);
+        assert_eq!(index.owner(19), None);
+        assert_eq!(index.owner(31), None);
+    }
+
+    #[test]
+    fn empty_index_has_no_owners() {
+        let index = OwnershipIndex::default();
+
+        assert_eq!(index.owner(0), None);
+        assert_eq!(index.owner(42), None);
+        assert!(!index.is_stale());
+    }
+
+    #[test]
+    fn marked_index_is_stale() {
+        let index = index(&[(3, 5, 2)]);
+        index.mark_stale();
+
+        assert!(index.is_stale());
+        assert_eq!(index.owner(4), Some(2));
+    }
+}
@@ -1,33 +1,141 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::ops::{Deref, DerefMut};
 use std::sync::atomic::{AtomicU32, Ordering};
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex};
 
-use futures::Stream;
-use tonic::{Result, Status};
+use futures::{Stream, StreamExt, TryStreamExt};
+use tonic::transport::Channel;
+use tonic::{Code, Result, Status};
 
 use generated::executer;
+use generated::manager::manager_service_client::ManagerServiceClient;
 use generated::worker;
 
-use crate::query_coordinator::{QueryCoordinator, WorkerExtended};
-use crate::workers_connection::Worker;
+use crate::cancellation::Cancellation;
+use crate::hull;
+use crate::k_shortest::{KShortestPaths, Route};
+use crate::membership::Membership;
+use crate::query_coordinator::{Direction, Meeting, QueryCoordinator, WorkerExtended};
+use crate::workers_connection::{self, WorkerId, WorkerList, WORKERS_REFRESH_INTERVAL};
 use crate::ErrorCollection;
 
 pub type NodeId = u64;
 pub type ShortestPathLen = u64;
 pub type QueryId = u32;
 
+// Spur searches run at once by one iteration of Yen's algorithm
+const SPUR_SEARCH_CONCURRENCY: usize = 4;
+
+// Limit of `k` in the k-shortest paths query; each path costs up to one search per its node
+const MAX_SHORTEST_PATHS: u32 = 32;
+
+// Window of the parallel mode used by the searches of the k-shortest paths query, which need the
+// exact distances; about a kilometer or a hundred seconds, depending on the metric.
+const PATH_SEARCH_WINDOW: ShortestPathLen = 100_000;
+
+// Coordinator taken out of `QueriesManager::query_coordinators` for a backtrack. It is put back
+// when dropped, so the query can still be forgotten after the backtrack failed or its stream was
+// dropped by the client.
+struct BorrowedCoordinator {
+    manager: &'static QueriesManager,
+    query_id: QueryId,
+    coordinator: Option<QueryCoordinator>,
+}
+
+impl Deref for BorrowedCoordinator {
+    type Target = QueryCoordinator;
+
+    fn deref(&self) -> &QueryCoordinator {
+        self.coordinator.as_ref().unwrap()
+    }
+}
+
+impl DerefMut for BorrowedCoordinator {
+    fn deref_mut(&mut self) -> &mut QueryCoordinator {
+        self.coordinator.as_mut().unwrap()
+    }
+}
+
+impl Drop for BorrowedCoordinator {
+    fn drop(&mut self) {
+        self.manager
+            .query_coordinators
+            .lock()
+            .unwrap()
+            .insert(self.query_id, self.coordinator.take());
+    }
+}
+
 pub struct QueriesManager {
-    workers: Vec<Worker>,
+    // Replaced when the manager lists other workers
+    membership: Mutex<Arc<Membership>>,
     query_id_counter: AtomicU32,
     query_coordinators: Mutex<HashMap<QueryId, Option<QueryCoordinator>>>,
+    // Handles of the targets of one-to-many queries -> (query ID, target)
+    target_handles: Mutex<HashMap<QueryId, (QueryId, NodeId)>>,
+    // Paths found by k-shortest paths queries, from the last node to the first one
+    stored_paths: Mutex<HashMap<QueryId, Vec<executer::Node>>>,
 }
 
 impl QueriesManager {
-    pub fn new(workers: Vec<Worker>) -> Self {
+    pub fn new(workers: WorkerList) -> Self {
         QueriesManager {
-            workers,
+            membership: Mutex::new(Arc::new(Membership::new(workers))),
             query_id_counter: AtomicU32::new(0),
             query_coordinators: Mutex::new(HashMap::new()),
+            target_handles: Mutex::new(HashMap::new()),
+            stored_paths: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn membership(&self) -> Arc<Membership> {
+        self.membership.lock().unwrap().clone()
+    }
+
+    // Fetches the list of the workers from the manager every `WORKERS_REFRESH_INTERVAL`, so that
+    // the new queries use the workers which joined (or moved) since. Queries already running keep
+    // their workers.
+    pub async fn watch_workers(&self, mut manager: ManagerServiceClient<Channel>) {
+        loop {
+            tokio::time::sleep(WORKERS_REFRESH_INTERVAL).await;
+
+            let addrs = match workers_connection::get_sorted_workers_addresses(&mut manager).await {
+                Ok(addrs) => addrs,
+                Err(status) => {
+                    warn!("Cannot refresh the workers list: {status:?}");
+                    continue;
+                }
+            };
+
+            // Most likely the manager restarted and its workers did not register yet
+            if addrs.is_empty() {
+                warn!("Manager lists no workers, keeping the current ones");
+                continue;
+            }
+
+            if let Some(membership) = self.membership().update(addrs) {
+                info!("workers changed, now {} workers", membership.workers.len());
+                *self.membership.lock().unwrap() = Arc::new(membership);
+            }
+        }
+    }
+
+    async fn overlay_query(
+        membership: &Membership,
+        coordinator: &mut QueryCoordinator,
+        metric: worker::Metric,
+    ) -> Result<Option<ShortestPathLen>, Status> {
+        let overlay = membership.get_overlay(metric, false).await?;
+
+        match coordinator.overlay_query(&overlay).await {
+            // Some worker got a new fragment since the overlay was fetched
+            Err(status) if status.code() == Code::Aborted => {
+                info!("{}, refreshing the overlay", status.message());
+
+                let overlay = membership.get_overlay(metric, true).await?;
+                coordinator.overlay_query(&overlay).await
+            }
+            result => result,
         }
     }
 
@@ -54,15 +162,46 @@ impl QueriesManager {
         coordinator.ok_or_else(|| ErrorCollection::query_invalid_or_busy(query_id))
     }
 
+    fn borrow_query_coordinator(
+        &'static self,
+        query_id: QueryId,
+    ) -> Result<BorrowedCoordinator, Status> {
+        Ok(BorrowedCoordinator {
+            manager: self,
+            query_id,
+            coordinator: Some(self.get_query_coordinator(query_id)?),
+        })
+    }
+
+    // Stopped by the `cancellation` between the rounds of the search; the workers then forget the
+    // query like after any other failure.
     pub async fn shortest_path_query(
         &self,
         request: executer::QueryData,
+        cancellation: Cancellation,
     ) -> Result<executer::QueryResults> {
         let executer::QueryData {
             node_id_from,
             node_id_to,
+            a_star,
+            bidirectional,
+            metric,
+            overlay,
+            parallel_window,
         } = request;
 
+        if overlay && (a_star || bidirectional) {
+            return Err(ErrorCollection::overlay_with_other_modes());
+        }
+
+        if parallel_window.is_some() && (a_star || bidirectional || overlay) {
+            return Err(ErrorCollection::parallel_with_other_modes());
+        }
+
+        // `executer::Metric` and `worker::Metric` share the values
+        let metric = worker::Metric::from_i32(metric)
+            .ok_or_else(|| ErrorCollection::unknown_metric(metric))?;
+
         let query_id = self.get_new_query_id();
         info!("`query_id` is: {query_id}");
 
@@ -73,10 +212,41 @@ impl QueriesManager {
                     query_id: None,
                 }
             } else {
-                let mut coordinator =
-                    QueryCoordinator::new(&self.workers, node_id_from, node_id_to, query_id)
-                        .await?;
-                let shortest_path_len = coordinator.shortest_path_query().await?;
+                let membership = self.membership();
+                let index = membership.get_ownership_index().await;
+                let mut coordinator = QueryCoordinator::new(
+                    &membership.workers,
+                    &index,
+                    node_id_from,
+                    node_id_to,
+                    query_id,
+                    a_star,
+                    metric,
+                )
+                .await?;
+
+                if bidirectional {
+                    coordinator.make_bidirectional()?;
+                }
+
+                if let Some(window) = parallel_window {
+                    coordinator.parallelize(window);
+                }
+
+                coordinator.set_cancellation(cancellation);
+                let result = match overlay {
+                    true => Self::overlay_query(&membership, &mut coordinator, metric).await,
+                    false => coordinator.shortest_path_query().await,
+                };
+
+                let shortest_path_len = match result {
+                    Ok(shortest_path_len) => shortest_path_len,
+                    Err(status) => {
+                        // Release what the already involved workers hold for this query
+                        Self::send_forget_query(coordinator).await;
+                        return Err(status);
+                    }
+                };
 
                 self.query_coordinators
                     .lock()
@@ -93,26 +263,602 @@ impl QueriesManager {
         Ok(response)
     }
 
+    // Stopped by the `cancellation` between the rounds of the search, like `shortest_path_query`
+    pub async fn one_to_many_query(
+        &self,
+        request: executer::OneToManyQueryData,
+        cancellation: Cancellation,
+    ) -> Result<executer::OneToManyResults> {
+        let executer::OneToManyQueryData {
+            node_id_from,
+            node_ids_to,
+            metric,
+        } = request;
+
+        let metric = worker::Metric::from_i32(metric)
+            .ok_or_else(|| ErrorCollection::unknown_metric(metric))?;
+
+        let query_id = self.get_new_query_id();
+        info!("`query_id` is: {query_id}");
+
+        let membership = self.membership();
+        let index = membership.get_ownership_index().await;
+        let mut coordinator = QueryCoordinator::new_from_source(
+            &membership.workers,
+            &index,
+            node_id_from,
+            node_ids_to.clone(),
+            query_id,
+            metric,
+        )
+        .await?;
+
+        coordinator.set_cancellation(cancellation);
+
+        let distances = match coordinator.one_to_many_query().await {
+            Ok(distances) => distances,
+            Err(status) => {
+                Self::send_forget_query(coordinator).await;
+                return Err(status);
+            }
+        };
+
+        let results: Vec<_> = {
+            let mut handles = self.target_handles.lock().unwrap();
+
+            node_ids_to
+                .into_iter()
+                .map(|node_id_to| match distances.get(&node_id_to) {
+                    _ if node_id_to == node_id_from => executer::TargetResult {
+                        node_id_to,
+                        query_id: None,
+                        shortest_path_len: Some(0),
+                    },
+                    Some(shortest_path_len) => {
+                        let handle = self.get_new_query_id();
+                        handles.insert(handle, (query_id, node_id_to));
+
+                        executer::TargetResult {
+                            node_id_to,
+                            query_id: Some(handle),
+                            shortest_path_len: Some(*shortest_path_len),
+                        }
+                    }
+                    None => executer::TargetResult {
+                        node_id_to,
+                        query_id: None,
+                        shortest_path_len: None,
+                    },
+                })
+                .collect()
+        };
+
+        // Nothing to backtrack
+        if results.iter().all(|result| result.query_id.is_none()) {
+            Self::send_forget_query(coordinator).await;
+        } else {
+            self.query_coordinators
+                .lock()
+                .unwrap()
+                .insert(query_id, Some(coordinator));
+        }
+
+        Ok(executer::OneToManyResults { results })
+    }
+
+    pub fn get_reachable_stream(
+        &'static self,
+        request: executer::ReachableQueryData,
+        cancellation: Cancellation,
+    ) -> impl Stream<Item = Result<executer::ReachableResponse, Status>> + Send + 'static {
+        use executer::reachable_response::MessageType;
+
+        async_stream::try_stream! {
+            // The stream is dropped if the client goes away
+            let _guard = cancellation.guard();
+
+            let executer::ReachableQueryData {
+                node_id_from,
+                budget,
+                metric,
+                hull,
+            } = request;
+
+            let metric = worker::Metric::from_i32(metric)
+                .ok_or_else(|| ErrorCollection::unknown_metric(metric))?;
+
+            let query_id = self.get_new_query_id();
+            info!("`query_id` is: {query_id}");
+
+            let membership = self.membership();
+            let index = membership.get_ownership_index().await;
+            let mut coordinator = QueryCoordinator::new_from_source(
+                &membership.workers,
+                &index,
+                node_id_from,
+                Vec::new(),
+                query_id,
+                metric,
+            )
+            .await?;
+
+            coordinator.set_cancellation(cancellation.clone());
+
+            // Nothing to backtrack later, so the workers forget the query right away. The search
+            // runs in its own task, so that they are told to even if the client goes away.
+            let search = tokio::spawn(async move {
+                let nodes = coordinator.reachable_query(budget).await;
+                Self::send_forget_query(coordinator).await;
+                nodes
+            });
+
+            let nodes = cancellation
+                .until_deadline(async {
+                    search.await.map_err(ErrorCollection::reachable_task_failed)?
+                })
+                .await?;
+
+            let points: Vec<_> = match hull {
+                true => nodes.iter().map(|node| (node.lat, node.lon)).collect(),
+                false => Vec::new(),
+            };
+
+            for node in nodes {
+                yield executer::ReachableResponse {
+                    message_type: Some(MessageType::Node(node)),
+                };
+            }
+
+            if hull {
+                let points = hull::convex_hull(points)
+                    .into_iter()
+                    .map(|(lat, lon)| executer::CoordinateResponse { lat, lon })
+                    .collect();
+
+                yield executer::ReachableResponse {
+                    message_type: Some(MessageType::Hull(executer::Hull { points })),
+                };
+            }
+        }
+    }
+
+    // Asks every worker for its `k` nearest nodes and keeps the `k` nearest of all.
+    pub async fn nearest_nodes(
+        &self,
+        request: executer::NearestNodesQuery,
+    ) -> Result<executer::NearestNodesResults, Status> {
+        use futures::future::try_join_all;
+
+        if request.k == 0 {
+            return Err(ErrorCollection::no_nearest_nodes_requested());
+        }
+
+        // Also checks the endpoints snapped by `shortest_path_between_coordinates`
+        let (lat, lon) = (request.lat, request.lon);
+
+        if !(lat.is_finite() && lon.is_finite() && lat.abs() <= 90. && lon.abs() <= 180.) {
+            return Err(ErrorCollection::invalid_coordinates(lat, lon));
+        }
+
+        let worker_request = worker::NearestNodesRequest {
+            lat: request.lat,
+            lon: request.lon,
+            k: request.k,
+            max_radius: request.max_radius,
+            routable: request.routable,
+        };
+
+        let membership = self.membership();
+        workers_connection::check_all(&membership.workers)?;
+
+        let results = try_join_all(membership.workers.iter().map(|worker| {
+            let mut channel = worker.channel.clone();
+            let request = worker_request.clone();
+            let worker_id = worker.id;
+
+            async move {
+                let nearest = channel.nearest_nodes(request).await?.into_inner();
+                Ok::<_, Status>((worker_id, nearest.nodes))
+            }
+        }))
+        .await?;
+
+        let mut nodes: Vec<executer::NearNode> = results
+            .into_iter()
+            .flat_map(|(worker_id, nodes)| {
+                nodes.into_iter().map(move |node| {
+                    let coords = node.coords.unwrap_or_default();
+
+                    executer::NearNode {
+                        node_id: node.node_id,
+                        worker_id,
+                        distance: node.distance,
+                        lat: coords.lat,
+                        lon: coords.lon,
+                    }
+                })
+            })
+            .collect();
+
+        nodes.sort_by(|a, b| a.distance.total_cmp(&b.distance));
+        nodes.truncate(request.k as usize);
+
+        Ok(executer::NearestNodesResults { nodes })
+    }
+
+    pub async fn shortest_path_between_coordinates(
+        &self,
+        request: executer::CoordinatesQueryData,
+        cancellation: Cancellation,
+    ) -> Result<executer::CoordinatesQueryResults> {
+        let snap = |lat, lon| async move {
+            let query = executer::NearestNodesQuery {
+                lat,
+                lon,
+                k: 1,
+                max_radius: request.max_snap_distance,
+                routable: true,
+            };
+
+            self.nearest_nodes(query)
+                .await?
+                .nodes
+                .into_iter()
+                .next()
+                .ok_or_else(|| ErrorCollection::no_routable_node_near(lat, lon))
+        };
+
+        let (node_from, node_to) = futures::try_join!(
+            snap(request.lat_from, request.lon_from),
+            snap(request.lat_to, request.lon_to)
+        )?;
+
+        debug!("snapped endpoints to {node_from:?} and {node_to:?}");
+
+        let results = self
+            .shortest_path_query(
+                executer::QueryData {
+                    node_id_from: node_from.node_id,
+                    node_id_to: node_to.node_id,
+                    a_star: request.a_star,
+                    bidirectional: request.bidirectional,
+                    metric: request.metric,
+                    overlay: request.overlay,
+                    parallel_window: request.parallel_window,
+                },
+                cancellation,
+            )
+            .await?;
+
+        Ok(executer::CoordinatesQueryResults {
+            node_from: Some(node_from),
+            node_to: Some(node_to),
+            results: Some(results),
+        })
+    }
+
+    // Shortest path avoiding the excluded nodes and edges, from `from` to `to`. The query is
+    // forgotten once its path is backtracked.
+    async fn find_path(
+        &'static self,
+        from: NodeId,
+        to: NodeId,
+        metric: worker::Metric,
+        excluded_node_ids: Vec<NodeId>,
+        excluded_edges: Vec<(NodeId, NodeId)>,
+        cancellation: Cancellation,
+    ) -> Result<Option<(ShortestPathLen, Vec<executer::Node>)>> {
+        let query_id = self.get_new_query_id();
+        let membership = self.membership();
+        let index = membership.get_ownership_index().await;
+        let mut coordinator = QueryCoordinator::new(
+            &membership.workers,
+            &index,
+            from,
+            to,
+            query_id,
+            false,
+            metric,
+        )
+        .await?;
+
+        coordinator.exclude(excluded_node_ids, excluded_edges);
+        coordinator.parallelize(PATH_SEARCH_WINDOW);
+        coordinator.set_cancellation(cancellation);
+
+        let shortest_path_len = match coordinator.shortest_path_query().await {
+            Ok(Some(shortest_path_len)) => shortest_path_len,
+            result => {
+                Self::send_forget_query(coordinator).await;
+                return result.map(|_| None);
+            }
+        };
+
+        self.query_coordinators
+            .lock()
+            .unwrap()
+            .insert(query_id, Some(coordinator));
+
+        let nodes: Result<Vec<_>> = self.get_backtrack_stream(query_id).try_collect().await;
+        let forgotten = self.forget_query(executer::QueryId { query_id }).await;
+
+        let mut nodes = nodes?;
+        forgotten?;
+        nodes.reverse();
+
+        Ok(Some((shortest_path_len, nodes)))
+    }
+
+    // Lengths of the path from its first node to each node, with the current weights of its edges
+    async fn path_lengths(
+        &self,
+        nodes: &[executer::Node],
+        metric: worker::Metric,
+    ) -> Result<Vec<ShortestPathLen>> {
+        use futures::future::try_join_all;
+
+        // Edges are asked from the workers owning their sources
+        let mut edges: HashMap<WorkerId, Vec<(usize, worker::NodePair)>> = HashMap::new();
+
+        for (position, pair) in nodes.windows(2).enumerate() {
+            edges.entry(pair[0].worker_id).or_default().push((
+                position,
+                worker::NodePair {
+                    node_from_id: pair[0].node_id,
+                    node_to_id: pair[1].node_id,
+                },
+            ));
+        }
+
+        let membership = self.membership();
+        let requests = edges
+            .into_iter()
+            .map(|(worker_id, edges)| {
+                let worker = membership
+                    .workers
+                    .iter()
+                    .find(|worker| worker.id == worker_id)
+                    .ok_or_else(|| ErrorCollection::unknown_worker(worker_id))?;
+                worker.health.check(worker_id)?;
+
+                let mut channel = worker.channel.clone();
+                let (positions, edges): (Vec<usize>, Vec<worker::NodePair>) =
+                    edges.into_iter().unzip();
+                let request = worker::EdgeWeightsRequest {
+                    metric: metric as i32,
+                    edges,
+                };
+
+                Ok(async move {
+                    let weights = channel
+                        .get_edge_weights(request)
+                        .await?
+                        .into_inner()
+                        .weights;
+                    Ok::<_, Status>(positions.into_iter().zip(weights))
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut weights = vec![0; nodes.len().saturating_sub(1)];
+
+        for (position, weight) in try_join_all(requests).await?.into_iter().flatten() {
+            weights[position] = weight;
+        }
+
+        let mut lengths = vec![0];
+
+        for weight in weights {
+            lengths.push(lengths.last().unwrap() + weight);
+        }
+
+        Ok(lengths)
+    }
+
+    // Yen's algorithm (see `KShortestPaths`), with the paths from the spur nodes found by the
+    // distributed search in the parallel mode, as it gives the exact distances. Stopped by the
+    // `cancellation` between the rounds of the searches.
+    pub async fn k_shortest_paths_query(
+        &'static self,
+        request: executer::KShortestPathsQueryData,
+        cancellation: Cancellation,
+    ) -> Result<executer::KShortestPathsResults> {
+        let executer::KShortestPathsQueryData {
+            node_id_from: from,
+            node_id_to: to,
+            k,
+            metric,
+        } = request;
+
+        let metric = worker::Metric::from_i32(metric)
+            .ok_or_else(|| ErrorCollection::unknown_metric(metric))?;
+
+        if k > MAX_SHORTEST_PATHS {
+            return Err(ErrorCollection::too_many_paths_requested(k));
+        }
+
+        if from == to {
+            let path = executer::QueryResults {
+                shortest_path_len: Some(0),
+                query_id: None,
+            };
+
+            return Ok(executer::KShortestPathsResults { paths: vec![path] });
+        }
+
+        let mut paths = KShortestPaths::default();
+
+        let first = self
+            .find_path(
+                from,
+                to,
+                metric,
+                Vec::new(),
+                Vec::new(),
+                cancellation.clone(),
+            )
+            .await?;
+
+        if let Some((len, nodes)) = first {
+            paths.add_candidate(len, nodes);
+        }
+
+        while paths.routes().len() < k as usize {
+            cancellation.check()?;
+
+            let Some(nodes) = paths.take_shortest() else {
+                break;
+            };
+
+            let lengths = self.path_lengths(&nodes, metric).await?;
+            paths.push_route(Route { nodes, lengths });
+
+            if paths.routes().len() == k as usize {
+                break;
+            }
+
+            let spur_searches = paths.spur_searches().into_iter().map(|search| {
+                let cancellation = cancellation.clone();
+
+                async move {
+                    let path = self
+                        .find_path(
+                            search.from,
+                            to,
+                            metric,
+                            search.excluded_node_ids,
+                            search.excluded_edges,
+                            cancellation,
+                        )
+                        .await?;
+
+                    Ok::<_, Status>((search.spur, path))
+                }
+            });
+
+            let spur_paths: Vec<_> = futures::stream::iter(spur_searches)
+                .buffer_unordered(SPUR_SEARCH_CONCURRENCY)
+                .try_collect()
+                .await?;
+
+            for (spur, path) in spur_paths {
+                if let Some((spur_len, spur_nodes)) = path {
+                    paths.add_spur_path(spur, spur_len, spur_nodes);
+                }
+            }
+        }
+
+        let routes = paths.into_routes();
+        debug!("found {} of {k} shortest paths", routes.len());
+
+        let mut stored = self.stored_paths.lock().unwrap();
+
+        let paths = routes
+            .into_iter()
+            .map(|route| {
+                let query_id = self.get_new_query_id();
+                let shortest_path_len = route.lengths.last().copied();
+
+                let mut nodes = route.nodes;
+                nodes.reverse();
+                stored.insert(query_id, nodes);
+
+                executer::QueryResults {
+                    query_id: Some(query_id),
+                    shortest_path_len,
+                }
+            })
+            .collect();
+
+        Ok(executer::KShortestPathsResults { paths })
+    }
+
+    // Query behind the handle and the target of the path, for the handles of one-to-many queries
+    fn resolve_handle(&self, handle: QueryId) -> (QueryId, Option<NodeId>) {
+        match self.target_handles.lock().unwrap().get(&handle) {
+            Some((query_id, target)) => (*query_id, Some(*target)),
+            None => (handle, None),
+        }
+    }
+
     pub fn get_backtrack_stream(
         &'static self,
-        query_id: QueryId,
+        handle: QueryId,
     ) -> impl Stream<Item = Result<executer::Node, Status>> + Send + 'static {
+        let (query_id, target) = self.resolve_handle(handle);
+
         async_stream::try_stream! {
-            let mut coordinator = self.get_query_coordinator(query_id)?;
+            let stored = self.stored_paths.lock().unwrap().get(&handle).cloned();
+
+            if let Some(nodes) = stored {
+                for node in nodes {
+                    yield node;
+                }
+
+                return;
+            }
+
+            let mut coordinator = self.borrow_query_coordinator(query_id)?;
+
+            if let Some(hops) = coordinator.overlay_path.clone() {
+                let nodes = coordinator.unpack_overlay_path(&hops).await?;
+
+                for node in nodes.into_iter().rev() {
+                    yield node;
+                }
+
+                return;
+            }
+
+            let start_point = match coordinator.meeting {
+                None => {
+                    let (node_id_to, owner_idx, reached_idx) = match target {
+                        Some(target) => (
+                            target,
+                            coordinator.target_owners[&target],
+                            coordinator.reached_targets[&target].0,
+                        ),
+                        None => (
+                            coordinator.node_id_to,
+                            coordinator.last_worker_idx,
+                            coordinator.last_reached_worker_idx.unwrap(),
+                        ),
+                    };
+
+                    yield executer::Node {
+                        node_id: node_id_to,
+                        worker_id: coordinator.get_worker_id(owner_idx),
+                    };
+
+                    (reached_idx, node_id_to)
+                }
+                Some(meeting) => {
+                    // The path from the meeting point to `to` is found by the backward search
+                    // and comes in the reversed order.
+                    let backward_half = Self::collect_backward_half(&mut coordinator, meeting).await?;
+
+                    for node in backward_half.into_iter().rev() {
+                        yield node;
+                    }
+
+                    yield executer::Node {
+                        node_id: meeting.node_id,
+                        worker_id: coordinator.get_worker_id(meeting.worker_idx),
+                    };
 
-            yield executer::Node {
-                node_id: coordinator.node_id_to,
-                worker_id: coordinator.get_worker_id(coordinator.last_worker_idx),
+                    (meeting.worker_idx, meeting.node_id)
+                }
             };
 
-            let last_worker_idx = coordinator.last_reached_worker_idx.unwrap();
-            let last_worker_id = coordinator.get_worker_id(last_worker_idx);
+            let (start_worker_idx, start_node) = start_point;
+            let start_worker_id = coordinator.get_worker_id(start_worker_idx);
 
-            let mut next_point = Some((last_worker_idx, last_worker_id, coordinator.node_id_to));
+            let mut next_point = Some((start_worker_idx, start_worker_id, start_node));
 
             while let Some((cur_worker_idx, cur_worker_id, current_node)) = next_point {
                 let mut inbound = coordinator
-                    .send_backtrack_request_to_worker(cur_worker_idx, current_node).await?;
+                    .send_backtrack_request_to_worker(cur_worker_idx, current_node, Direction::Forward)
+                    .await?;
 
                 next_point = None;
 
@@ -134,20 +880,56 @@ impl QueriesManager {
                     }
                 }
             }
+        }
+    }
 
-            self.query_coordinators
-                .lock()
-                .unwrap()
-                .insert(query_id, Some(coordinator));
+    // Nodes on the path from the meeting point (exclusive) to the `to` node (inclusive)
+    async fn collect_backward_half(
+        coordinator: &mut QueryCoordinator,
+        meeting: Meeting,
+    ) -> Result<Vec<executer::Node>, Status> {
+        let mut nodes = Vec::new();
+        let mut next_point = Some((meeting.worker_idx, meeting.node_id));
+
+        while let Some((cur_worker_idx, current_node)) = next_point {
+            let cur_worker_id = coordinator.get_worker_id(cur_worker_idx);
+            let mut inbound = coordinator
+                .send_backtrack_request_to_worker(cur_worker_idx, current_node, Direction::Backward)
+                .await?;
+
+            next_point = None;
+
+            while let Some(node) = inbound.message().await? {
+                let worker_id = match node.worker_id {
+                    Some(worker_id) => {
+                        let worker_idx =
+                            QueryCoordinator::find_worker_by_id(&coordinator.workers, worker_id)?;
+                        next_point = Some((worker_idx, node.node_id));
+                        worker_id
+                    }
+                    None => cur_worker_id,
+                };
+
+                nodes.push(executer::Node {
+                    node_id: node.node_id,
+                    worker_id,
+                });
+            }
         }
+
+        Ok(nodes)
     }
 
     pub fn get_coordinates_stream(
         &'static self,
         mut inbound: tonic::Streaming<executer::Node>,
     ) -> impl Stream<Item = Result<executer::CoordinateResponse, Status>> + Send + 'static {
-        let mut workers: Vec<WorkerExtended> =
-            self.workers.iter().map(WorkerExtended::from).collect();
+        let mut workers: Vec<WorkerExtended> = self
+            .membership()
+            .workers
+            .iter()
+            .map(WorkerExtended::from)
+            .collect();
 
         async_stream::try_stream! {
             while let Some(node) = inbound.message().await? {
@@ -169,8 +951,25 @@ impl QueriesManager {
         }
     }
 
+    // Handles of a one-to-many query are forgotten one by one, the query itself with the last one.
     pub async fn forget_query(&self, request: executer::QueryId) -> Result<(), Status> {
-        let executer::QueryId { query_id } = request;
+        let executer::QueryId { query_id: handle } = request;
+
+        if self.stored_paths.lock().unwrap().remove(&handle).is_some() {
+            return Ok(());
+        }
+
+        let (query_id, target) = self.resolve_handle(handle);
+
+        if target.is_some() {
+            let mut handles = self.target_handles.lock().unwrap();
+            handles.remove(&handle);
+
+            if handles.values().any(|(other, _)| *other == query_id) {
+                return Ok(());
+            }
+        }
+
         let coordinator = self.get_query_coordinator(query_id)?;
 
         Self::send_forget_query(coordinator).await;
@@ -178,6 +977,109 @@ impl QueriesManager {
 
         Ok(())
     }
+
+    // Sends every update to the workers owning one of its endpoints, the others skip it anyway.
+    pub async fn update_edge_weights(
+        &self,
+        request: executer::EdgeWeightUpdates,
+    ) -> Result<executer::EdgeWeightUpdatesResult, Status> {
+        use executer::edge_weight_update::Change;
+        use futures::future::try_join_all;
+        use worker::edge_weight_update::Change as WorkerChange;
+
+        let updates: Vec<worker::EdgeWeightUpdate> = request
+            .updates
+            .into_iter()
+            .map(|update| worker::EdgeWeightUpdate {
+                node_from_id: update.node_from_id,
+                node_to_id: update.node_to_id,
+                metric: update.metric,
+                change: update.change.map(|change| match change {
+                    Change::NewWeight(weight) => WorkerChange::NewWeight(weight),
+                    Change::Closed(closed) => WorkerChange::Closed(closed),
+                }),
+            })
+            .collect();
+
+        let node_ids: Vec<NodeId> = updates
+            .iter()
+            .flat_map(|update| [update.node_from_id, update.node_to_id])
+            .collect();
+
+        let membership = self.membership();
+        workers_connection::check_all(&membership.workers)?;
+
+        let owned_nodes = try_join_all(membership.workers.iter().map(|worker| {
+            let mut channel = worker.channel.clone();
+            let request = worker::NodeIdList {
+                node_ids: node_ids.clone(),
+            };
+
+            async move { channel.filter_owned_nodes(request).await }
+        }))
+        .await?;
+
+        let send_updates = membership
+            .workers
+            .iter()
+            .zip(owned_nodes)
+            .map(|(worker, owned)| {
+                let owned: HashSet<NodeId> = owned.into_inner().node_ids.into_iter().collect();
+
+                // Positions of the sent updates in the whole request
+                let (positions, updates): (Vec<u32>, Vec<worker::EdgeWeightUpdate>) = updates
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, update)| {
+                        owned.contains(&update.node_from_id) || owned.contains(&update.node_to_id)
+                    })
+                    .map(|(position, update)| (position as u32, update.clone()))
+                    .unzip();
+
+                let mut channel = worker.channel.clone();
+                let worker_id = worker.id;
+
+                async move {
+                    if updates.is_empty() {
+                        return Ok(Vec::new());
+                    }
+
+                    let result = channel
+                        .update_edge_weights(worker::EdgeWeightUpdates { updates })
+                        .await?
+                        .into_inner();
+
+                    debug!(
+                        "worker[id: {worker_id}] matched {} updates, graph version is {}",
+                        result.matched.len(),
+                        result.graph_version
+                    );
+
+                    let matched = result
+                        .matched
+                        .into_iter()
+                        .map(|idx| positions[idx as usize])
+                        .collect::<Vec<_>>();
+
+                    Ok::<_, Status>(matched)
+                }
+            });
+
+        let mut matched = vec![false; updates.len()];
+
+        for position in try_join_all(send_updates).await?.into_iter().flatten() {
+            matched[position as usize] = true;
+        }
+
+        // The workers compute their overlays with the new weights when they are fetched again
+        membership.clear_overlays();
+
+        let unmatched = (0..updates.len() as u32)
+            .filter(|position| !matched[*position as usize])
+            .collect();
+
+        Ok(executer::EdgeWeightUpdatesResult { unmatched })
+    }
 }
 
 impl ErrorCollection {
@@ -187,6 +1089,49 @@ impl ErrorCollection {
         ))
     }
 
+    fn unknown_metric(metric: i32) -> Status {
+        Status::invalid_argument(format!("Unknown metric {metric}"))
+    }
+
+    fn unknown_worker(worker_id: WorkerId) -> Status {
+        Status::internal(format!("Path goes through unknown worker[id: {worker_id}]"))
+    }
+
+    fn no_routable_node_near(lat: f64, lon: f64) -> Status {
+        Status::not_found(format!("No routable node near ({lat}, {lon})"))
+    }
+
+    fn too_many_paths_requested(k: u32) -> Status {
+        Status::invalid_argument(format!(
+            "Requested {k} shortest paths, at most {MAX_SHORTEST_PATHS} are allowed"
+        ))
+    }
+
+    fn reachable_task_failed(error: tokio::task::JoinError) -> Status {
+        Status::internal(format!("Reachable query task failed: {error}"))
+    }
+
+    fn no_nearest_nodes_requested() -> Status {
+        Status::invalid_argument("At least one nearest node must be requested")
+    }
+
+    fn invalid_coordinates(lat: f64, lon: f64) -> Status {
+        Status::invalid_argument(format!(
+            "Invalid coordinates ({lat}, {lon}); latitude must be within [-90, 90] and \
+            longitude within [-180, 180]"
+        ))
+    }
+
+    fn overlay_with_other_modes() -> Status {
+        Status::invalid_argument("Overlay search cannot be combined with A* nor bidirectional")
+    }
+
+    fn parallel_with_other_modes() -> Status {
+        Status::invalid_argument(
+            "Parallel rounds cannot be combined with A*, bidirectional nor overlay",
+        )
+    }
+
     fn wrong_first_message() -> Status {
         Status::invalid_argument("First message in CoordinateRequest must be query_id")
     }
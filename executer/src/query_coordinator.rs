@@ -1,5 +1,8 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
 use async_stream::AsyncStream;
-use futures::future::try_join_all;
+use futures::future::{join_all, try_join_all};
 use futures::stream::FuturesUnordered;
 use futures::Future;
 use futures::TryFutureExt;
@@ -7,18 +10,22 @@ use futures::TryStreamExt;
 use tonic::transport::Channel;
 use tonic::Request;
 use tonic::Result;
-use tonic::Status;
+use tonic::Streaming;
+use tonic::{Code, Status};
 
 use generated::executer;
 use generated::worker;
 
 use worker::worker_client::WorkerClient;
 use worker::{request_djikstra, response_djikstra};
-use worker::{ForgetQueryMessage, RequestDjikstra};
+use worker::{ForgetQueryMessage, RequestDjikstra, ResponseDjikstra};
 
+use crate::cancellation::Cancellation;
+use crate::overlay::{Hop, Overlay};
+use crate::ownership_index::OwnershipIndex;
 use crate::queries_manager::{NodeId, ShortestPathLen};
-use crate::workers_connection::Worker;
 use crate::workers_connection::WorkerId;
+use crate::workers_connection::{Health, Worker};
 use crate::ErrorCollection;
 
 use request_djikstra::NewDomesticNode;
@@ -26,12 +33,25 @@ use response_djikstra::MessageType;
 
 type WorkerIdx = usize;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Forward = 0,
+    Backward = 1, // From the `to` node, over reversed edges
+}
+
+// Nodes of the worker waiting to be explored in one direction of the search
+#[derive(Default)]
+struct Frontier {
+    minimal: Option<ShortestPathLen>,
+    new_nodes: Vec<NewDomesticNode>,
+}
+
 pub struct WorkerExtended {
     id: WorkerId,
     pub channel: WorkerClient<Channel>,
-    minimal: Option<ShortestPathLen>,
-    new_nodes: Vec<NewDomesticNode>,
-    is_involved: bool, // Was the worker involved in the current query?
+    health: Arc<Health>,
+    frontiers: [Frontier; 2], // Indexed by `Direction`
+    is_involved: bool,        // Was the worker involved in the current query?
 }
 
 impl WorkerExtended {
@@ -39,45 +59,94 @@ impl WorkerExtended {
         WorkerExtended {
             id: worker.id,
             channel: worker.channel.clone(), // Cloning `Channel` is cheap (and unavoidable I guess)
-            minimal: None,
-            new_nodes: Vec::new(),
+            health: worker.health.clone(),
+            frontiers: Default::default(),
             is_involved: false,
         }
     }
 
+    // Fails with `unavailable` if the worker is down
+    pub fn check_health(&self) -> Result<()> {
+        self.health.check(self.id)
+    }
+
+    fn minimal(&self, direction: Direction) -> Option<ShortestPathLen> {
+        self.frontiers[direction as usize].minimal
+    }
+
+    // `estimated_path_len` is the key by which the node is ordered; in A* mode it is a lower bound
+    // of the whole path through the node, otherwise it is equal to `shortest_path_len`.
     fn push_new_domestic(
         &mut self,
+        direction: Direction,
         node_id: NodeId,
         shortest_path_len: ShortestPathLen,
+        estimated_path_len: ShortestPathLen,
         parent_node: Option<(NodeId, WorkerId)>,
     ) {
         let parent_node =
             parent_node.map(|(node_id, worker_id)| worker::NodePointer { worker_id, node_id });
 
-        self.new_nodes.push(NewDomesticNode {
+        let frontier = &mut self.frontiers[direction as usize];
+
+        frontier.new_nodes.push(NewDomesticNode {
             node_id,
             shortest_path_len,
             parent_node,
         });
 
-        let update = self.minimal.is_none() || self.minimal.unwrap() > shortest_path_len;
+        let update = frontier.minimal.is_none() || frontier.minimal.unwrap() > estimated_path_len;
 
         if update {
-            self.minimal = Some(shortest_path_len);
+            frontier.minimal = Some(estimated_path_len);
         };
     }
 
-    fn extract_new_domestic(&mut self) -> std::vec::Vec<NewDomesticNode> {
-        std::mem::take(&mut self.new_nodes)
+    fn extract_new_domestic(&mut self, direction: Direction) -> std::vec::Vec<NewDomesticNode> {
+        std::mem::take(&mut self.frontiers[direction as usize].new_nodes)
     }
 }
 
+// Node in which the forward and the backward searches met
+#[derive(Debug, Clone, Copy)]
+pub struct Meeting {
+    pub worker_idx: WorkerIdx,
+    pub node_id: NodeId,
+    pub path_len: ShortestPathLen,
+}
+
 pub struct QueryCoordinator {
     pub workers: Vec<WorkerExtended>,
     query_id: u32,
 
     pub node_id_from: NodeId,
     pub node_id_to: NodeId,
+    // Present in A* mode
+    node_to_coords: Option<worker::Coordinates>,
+    bidirectional: bool,
+    metric: worker::Metric,
+    // Best meeting point found so far by the bidirectional search
+    pub meeting: Option<Meeting>,
+    // Path found in the overlay mode
+    pub overlay_path: Option<Vec<Hop>>,
+    // Targets of the one-to-many query not finished yet
+    targets: Vec<NodeId>,
+    // Shortest distances to the targets of the one-to-many query reported so far, with the workers
+    // which reported them
+    pub reached_targets: HashMap<NodeId, (WorkerIdx, ShortestPathLen)>,
+    // Owners of the targets of the one-to-many query
+    pub target_owners: HashMap<NodeId, WorkerIdx>,
+    // Whether the query looks for the nodes within a budget rather than for a path
+    reachable_set: bool,
+    // Nodes and edges the path must avoid
+    excluded_node_ids: Vec<NodeId>,
+    excluded_edges: Vec<worker::NodePair>,
+    // Present in the parallel mode, see `parallel_query`
+    parallel_window: Option<ShortestPathLen>,
+    // Shortest path to the final node reported so far in the parallel mode
+    final_reached: Option<ShortestPathLen>,
+    // Checked before each round of the path searches
+    cancellation: Cancellation,
 
     pub first_worker_idx: WorkerIdx,
     pub last_worker_idx: WorkerIdx,
@@ -85,25 +154,69 @@ pub struct QueryCoordinator {
 }
 
 impl QueryCoordinator {
-    fn find_shortest_foreign(&self, current: WorkerIdx) -> Option<ShortestPathLen> {
+    fn find_shortest_foreign(
+        &self,
+        current: WorkerIdx,
+        direction: Direction,
+    ) -> Option<ShortestPathLen> {
         self.workers
             .iter()
             .enumerate()
             .filter(|(idx, _)| *idx != current)
-            .filter_map(|(_, w)| w.minimal)
+            .filter_map(|(_, w)| w.minimal(direction))
             .min()
     }
 
+    // Worker with the smallest node waiting to be explored in the given direction
+    fn find_next_worker(&self, direction: Direction) -> Option<(WorkerIdx, ShortestPathLen)> {
+        self.workers
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, w)| w.minimal(direction).map(|minimal| (idx, minimal)))
+            .min_by_key(|(_, minimal)| *minimal)
+    }
+
+    // Grows the search from both ends at once
+    pub fn make_bidirectional(&mut self) -> Result<()> {
+        if self.node_to_coords.is_some() {
+            return Err(ErrorCollection::a_star_with_bidirectional());
+        }
+
+        self.bidirectional = true;
+
+        Ok(())
+    }
+
+    pub fn set_cancellation(&mut self, cancellation: Cancellation) {
+        self.cancellation = cancellation;
+    }
+
+    pub fn parallelize(&mut self, window: ShortestPathLen) {
+        self.parallel_window = Some(window);
+    }
+
+    pub fn exclude(&mut self, node_ids: Vec<NodeId>, edges: Vec<(NodeId, NodeId)>) {
+        self.excluded_node_ids = node_ids;
+        self.excluded_edges = edges
+            .into_iter()
+            .map(|(node_from_id, node_to_id)| worker::NodePair {
+                node_from_id,
+                node_to_id,
+            })
+            .collect();
+    }
+
     pub fn get_worker_id(&self, idx: WorkerIdx) -> WorkerId {
         self.workers[idx].id
     }
 
+    // Workers which are down are skipped; they drop the query once it expires.
     pub async fn send_forget_to_workers(&mut self) -> Result<(), Status> {
         let query_id = self.query_id;
         let futures = self
             .workers
             .iter_mut()
-            .filter(|worker| worker.is_involved)
+            .filter(|worker| worker.is_involved && worker.check_health().is_ok())
             .map(|worker| {
                 info!(" -> sending forget request to worker[id: {}]", worker.id);
                 worker.channel.forget_query(ForgetQueryMessage { query_id })
@@ -119,12 +232,16 @@ impl QueryCoordinator {
         &mut self,
         worker_idx: WorkerIdx,
         node_id: NodeId,
+        direction: Direction,
     ) -> Result<tonic::Streaming<worker::ResponseBacktrack>, Status> {
+        self.workers[worker_idx].check_health()?;
+
         let stream = self.workers[worker_idx]
             .channel
             .get_backtrack(worker::RequestBacktrack {
                 query_id: self.query_id,
                 from_node: node_id,
+                backward: direction == Direction::Backward,
             })
             .await?
             .into_inner();
@@ -132,24 +249,191 @@ impl QueryCoordinator {
         Ok(stream)
     }
 
-    pub async fn new(workers: &[Worker], from: NodeId, to: NodeId, query_id: u32) -> Result<Self> {
+    pub async fn new(
+        workers: &[Worker],
+        index: &OwnershipIndex,
+        from: NodeId,
+        to: NodeId,
+        query_id: u32,
+        a_star: bool,
+        metric: worker::Metric,
+    ) -> Result<Self> {
+        // The heuristic estimates distance, it is not a lower bound of other metrics
+        if a_star && metric != worker::Metric::Distance {
+            return Err(ErrorCollection::a_star_with_metric(metric));
+        }
+
         let mut workers_extended: Vec<_> = workers.iter().map(WorkerExtended::from).collect();
 
-        let (worker_from, worker_to) = Self::find_workers(&mut workers_extended, from, to).await?;
+        let (worker_from, worker_to) =
+            Self::find_workers(&mut workers_extended, index, from, to).await?;
+
+        workers_extended[worker_from].check_health()?;
+        workers_extended[worker_to].check_health()?;
+
+        let node_to_coords = match a_star {
+            true => Some(
+                workers_extended[worker_to]
+                    .channel
+                    .get_node_coordinates(worker::RequestCoordinates { node_id: to })
+                    .await?
+                    .into_inner(),
+            ),
+            false => None,
+        };
 
         Ok(QueryCoordinator {
             workers: workers_extended,
             query_id,
             node_id_from: from,
             node_id_to: to,
+            node_to_coords,
+            bidirectional: false,
+            metric,
+            meeting: None,
+            overlay_path: None,
+            targets: Vec::new(),
+            reached_targets: HashMap::new(),
+            target_owners: HashMap::new(),
+            reachable_set: false,
+            excluded_node_ids: Vec::new(),
+            excluded_edges: Vec::new(),
+            parallel_window: None,
+            final_reached: None,
+            cancellation: Cancellation::default(),
             first_worker_idx: worker_from,
             last_worker_idx: worker_to,
             last_reached_worker_idx: None,
         })
     }
 
+    // For the queries without a single final node: one-to-many (with `targets`) and reachable-set
+    // ones. Targets not present in any worker are left out, as unreachable.
+    pub async fn new_from_source(
+        workers: &[Worker],
+        index: &OwnershipIndex,
+        from: NodeId,
+        targets: Vec<NodeId>,
+        query_id: u32,
+        metric: worker::Metric,
+    ) -> Result<Self> {
+        let workers_extended: Vec<_> = workers.iter().map(WorkerExtended::from).collect();
+
+        let mut owners = HashMap::new();
+        // Nodes not in the index (or no longer owned by the worker it names), all workers are
+        // asked about them
+        let mut not_indexed: Vec<NodeId> = Vec::new();
+        let mut indexed: HashMap<WorkerIdx, Vec<NodeId>> = HashMap::new();
+
+        for node_id in std::iter::once(from).chain(targets) {
+            match index.owner(node_id) {
+                Some(worker_id) => {
+                    let worker_idx = Self::find_worker_by_id(&workers_extended, worker_id)?;
+                    indexed.entry(worker_idx).or_default().push(node_id);
+                }
+                None => not_indexed.push(node_id),
+            }
+        }
+
+        // The owners which are down cannot confirm their nodes; the search fails if it needs them
+        indexed.retain(
+            |&worker_idx, node_ids| match workers_extended[worker_idx].check_health() {
+                Ok(()) => true,
+                Err(_) => {
+                    owners.extend(node_ids.iter().map(|node_id| (*node_id, worker_idx)));
+                    false
+                }
+            },
+        );
+
+        let confirmations = try_join_all(indexed.into_iter().map(|(worker_idx, node_ids)| {
+            let mut channel = workers_extended[worker_idx].channel.clone();
+
+            async move {
+                let request = worker::NodeIdList {
+                    node_ids: node_ids.clone(),
+                };
+                let owned = channel.filter_owned_nodes(request).await?.into_inner();
+                let owned: HashSet<NodeId> = owned.node_ids.into_iter().collect();
+
+                Ok::<_, Status>((worker_idx, node_ids, owned))
+            }
+        }))
+        .await?;
+
+        for (worker_idx, node_ids, owned) in confirmations {
+            for node_id in node_ids {
+                match owned.contains(&node_id) {
+                    true => {
+                        owners.insert(node_id, worker_idx);
+                    }
+                    false => {
+                        index.mark_stale();
+                        not_indexed.push(node_id);
+                    }
+                }
+            }
+        }
+
+        if !not_indexed.is_empty() {
+            for worker in workers_extended.iter() {
+                worker.check_health()?;
+            }
+
+            let owned_nodes = try_join_all(workers_extended.iter().map(|worker| {
+                let mut channel = worker.channel.clone();
+                let request = worker::NodeIdList {
+                    node_ids: not_indexed.clone(),
+                };
+
+                async move { channel.filter_owned_nodes(request).await }
+            }))
+            .await?;
+
+            for (worker_idx, owned) in owned_nodes.into_iter().enumerate() {
+                for node_id in owned.into_inner().node_ids {
+                    owners.insert(node_id, worker_idx);
+                }
+            }
+        }
+
+        let worker_from = owners
+            .remove(&from)
+            .ok_or_else(|| Status::not_found("requested `from` node not found"))?;
+
+        debug!("node `from` found in worker[id {worker_from}]");
+
+        workers_extended[worker_from].check_health()?;
+
+        Ok(QueryCoordinator {
+            workers: workers_extended,
+            query_id,
+            node_id_from: from,
+            // Not used, the workers look for `targets` instead
+            node_id_to: from,
+            node_to_coords: None,
+            bidirectional: false,
+            metric,
+            meeting: None,
+            overlay_path: None,
+            targets: owners.keys().copied().collect(),
+            reached_targets: HashMap::new(),
+            target_owners: owners,
+            reachable_set: false,
+            excluded_node_ids: Vec::new(),
+            excluded_edges: Vec::new(),
+            parallel_window: None,
+            final_reached: None,
+            cancellation: Cancellation::default(),
+            first_worker_idx: worker_from,
+            last_worker_idx: worker_from,
+            last_reached_worker_idx: None,
+        })
+    }
+
     async fn find_workers(
         workers: &mut [WorkerExtended],
+        index: &OwnershipIndex,
         from: NodeId,
         to: NodeId,
     ) -> Result<(WorkerIdx, WorkerIdx), Status> {
@@ -158,6 +442,26 @@ impl QueryCoordinator {
             node_to_id: to,
         };
 
+        if let (Some(from_id), Some(to_id)) = (index.owner(from), index.owner(to)) {
+            debug!("nodes found in the ownership index: worker[id {from_id}], worker[id {to_id}]");
+
+            let owners = (
+                Self::find_worker_by_id(workers, from_id)?,
+                Self::find_worker_by_id(workers, to_id)?,
+            );
+
+            if Self::owners_confirm(workers, &message, owners).await? {
+                return Ok(owners);
+            }
+
+            index.mark_stale();
+        }
+
+        // All workers are asked, the owner might be the one which is down
+        for worker in workers.iter() {
+            worker.check_health()?;
+        }
+
         let mut futs = workers
             .iter_mut()
             .enumerate()
@@ -205,20 +509,69 @@ impl QueryCoordinator {
         Ok((from, to))
     }
 
+    // Asks the owners of `from` and `to` found in the ownership index whether they still have them
+    async fn owners_confirm(
+        workers: &[WorkerExtended],
+        message: &generated::worker::NodeIds,
+        (worker_from, worker_to): (WorkerIdx, WorkerIdx),
+    ) -> Result<bool, Status> {
+        workers[worker_from].check_health()?;
+        workers[worker_to].check_health()?;
+
+        let ask = |worker_idx: WorkerIdx| {
+            let mut channel = workers[worker_idx].channel.clone();
+            let message = message.clone();
+
+            async move {
+                let present = channel.are_nodes_present(message).await?.into_inner();
+                Ok::<_, Status>(present)
+            }
+        };
+
+        let (from_present, to_present) = match worker_from == worker_to {
+            true => {
+                let present = ask(worker_from).await?;
+                (present.node_from_present, present.node_to_present)
+            }
+            false => {
+                let (from, to) = futures::try_join!(ask(worker_from), ask(worker_to))?;
+                (from.node_from_present, to.node_to_present)
+            }
+        };
+
+        Ok(from_present && to_present)
+    }
+
     fn prepare_outbound_stream(
         &mut self,
         current: WorkerIdx,
+        direction: Direction,
+        round_limit: Option<ShortestPathLen>,
     ) -> AsyncStream<RequestDjikstra, impl Future<Output = ()>> {
         let query_data = request_djikstra::QueryData {
             query_id: self.query_id,
             final_node_id: self.node_id_to,
-            smallest_foreign_node: self.find_shortest_foreign(current),
+            // In the parallel mode workers stop only at the round limit
+            smallest_foreign_node: match self.parallel_window {
+                Some(_) => None,
+                None => self.find_shortest_foreign(current, direction),
+            },
+            final_node_coords: self.node_to_coords.clone(),
+            bidirectional: self.bidirectional,
+            backward: direction == Direction::Backward,
+            round_limit,
+            metric: self.metric as i32,
+            target_node_ids: self.targets.clone(),
+            reachable_set: self.reachable_set,
+            excluded_node_ids: self.excluded_node_ids.clone(),
+            excluded_edges: self.excluded_edges.clone(),
+            parallel: self.parallel_window.is_some(),
         };
 
         debug!("sending `update_dijkstra` request to worker[idx {current}]");
         debug!(" -> query_data: {query_data:?}");
 
-        let new_nodes = self.workers[current].extract_new_domestic();
+        let new_nodes = self.workers[current].extract_new_domestic(direction);
 
         async_stream::stream! {
             yield proto_helpers::pack_query_data(query_data);
@@ -236,87 +589,200 @@ impl QueryCoordinator {
             .map_err(|_| ErrorCollection::worker_not_found(wid))
     }
 
-    pub async fn shortest_path_query(&mut self) -> Result<Option<ShortestPathLen>, Status> {
-        // Push initial node
-        self.workers[self.first_worker_idx].push_new_domestic(self.node_id_from, 0, None);
+    // Takes the nodes waiting in `current` worker and starts its Djikstra step on them. The
+    // returned future does not borrow the coordinator, so rounds of many workers may run at once.
+    fn start_round(
+        &mut self,
+        current: WorkerIdx,
+        direction: Direction,
+        round_limit: Option<ShortestPathLen>,
+    ) -> impl Future<Output = Result<Streaming<ResponseDjikstra>, Status>> {
+        debug!("current worker: {current}, direction: {direction:?}");
 
-        let mut next_worker = Some(self.first_worker_idx);
+        let health = self.workers[current].check_health();
+        let outbound = self.prepare_outbound_stream(current, direction, round_limit);
 
-        while let Some(current) = next_worker {
-            debug!("current worker: {}", current);
+        let worker = &mut self.workers[current];
+        worker.frontiers[direction as usize].minimal = None;
+        worker.is_involved = true;
 
-            let outbound = self.prepare_outbound_stream(current);
+        let worker_id = worker.id;
+        let mut channel = worker.channel.clone();
 
-            let mut inbound = self.workers[current]
-                .channel
+        async move {
+            health?;
+
+            let inbound = channel
                 .update_djikstra(outbound)
-                .await?
+                .await
+                .map_err(|status| match status.code() {
+                    Code::ResourceExhausted => {
+                        ErrorCollection::worker_overloaded(worker_id, status)
+                    }
+                    _ => status,
+                })?
                 .into_inner();
 
-            debug!("parsing `update_dijkstra` response from worker[idx {current}]:");
+            Ok(inbound)
+        }
+    }
+
+    // Sends the nodes waiting in `current` worker to it and collects the results of its Djikstra
+    // step. Returns the path length if the worker reached the final node.
+    async fn run_round(
+        &mut self,
+        current: WorkerIdx,
+        direction: Direction,
+        round_limit: Option<ShortestPathLen>,
+    ) -> Result<Option<ShortestPathLen>, Status> {
+        let mut inbound = self.start_round(current, direction, round_limit).await?;
 
-            self.workers[current].minimal = None;
-            self.workers[current].is_involved = true;
-            let current_worker_id = self.workers[current].id;
+        debug!("parsing `update_dijkstra` response from worker[idx {current}]:");
 
-            while let Some(response) = inbound.message().await? {
-                let message = match response.message_type {
-                    Some(msg) => msg,
-                    None => {
-                        warn!(" -> empty `ResponseDjikstra` in the stream!");
-                        continue;
-                    }
+        while let Some(response) = inbound.message().await? {
+            if let Some(path_len) = self.handle_response(current, direction, response)? {
+                return Ok(Some(path_len));
+            }
+        }
+
+        debug!("finished parsing `update_djikstra` response from worker");
+
+        Ok(None)
+    }
+
+    // Applies a message from the round of `current` worker. Returns the path length on success.
+    fn handle_response(
+        &mut self,
+        current: WorkerIdx,
+        direction: Direction,
+        response: ResponseDjikstra,
+    ) -> Result<Option<ShortestPathLen>, Status> {
+        let current_worker_id = self.workers[current].id;
+
+        let message = match response.message_type {
+            Some(msg) => msg,
+            None => {
+                warn!(" -> empty `ResponseDjikstra` in the stream!");
+                return Ok(None);
+            }
+        };
+
+        match message {
+            MessageType::Success(s) => {
+                self.last_reached_worker_idx = Some(current);
+                return Ok(Some(s.shortest_path_len));
+            }
+
+            MessageType::NewForeignNode(node) => {
+                debug!(" -> received foreign node {node:?}");
+
+                let this_node = node.this_node.ok_or_else(|| {
+                    Status::invalid_argument("Empty `this_node` in `NewForeignNode` message")
+                })?;
+
+                let worker_idx = Self::find_worker_by_id(&self.workers, this_node.worker_id)?;
+
+                debug!(
+                    " -> node[id {}] belongs to worker[idx {}]",
+                    this_node.node_id, worker_idx
+                );
+
+                self.workers[worker_idx].push_new_domestic(
+                    direction,
+                    this_node.node_id,
+                    node.shortest_path_len,
+                    node.estimated_path_len,
+                    Some((node.parent_node_id, current_worker_id)),
+                );
+            }
+
+            MessageType::SmallestDomesticNode(node) => {
+                debug!(
+                    " -> smallest domestic node has len (or estimate in A*): {}",
+                    node.shortest_path_len
+                );
+
+                // Other workers may have sent it nodes during its round in the parallel mode
+                let frontier = &mut self.workers[current].frontiers[direction as usize];
+                frontier.minimal = match frontier.minimal {
+                    Some(minimal) => Some(std::cmp::min(minimal, node.shortest_path_len)),
+                    None => Some(node.shortest_path_len),
                 };
+            }
 
-                match message {
-                    MessageType::Success(s) => {
-                        self.last_reached_worker_idx = Some(current);
-                        return Ok(Some(s.shortest_path_len));
-                    }
+            MessageType::FinalNodeReached(reached) => {
+                debug!(" -> final node reached {reached:?}");
 
-                    MessageType::NewForeignNode(node) => {
-                        debug!(" -> received foreign node {node:?}");
+                if !matches!(self.final_reached, Some(best) if best <= reached.shortest_path_len) {
+                    self.final_reached = Some(reached.shortest_path_len);
+                    self.last_reached_worker_idx = Some(current);
+                }
+            }
 
-                        let this_node = node.this_node.ok_or_else(|| {
-                            Status::invalid_argument(
-                                "Empty `this_node` in `NewForeignNode` message",
-                            )
-                        })?;
+            MessageType::TargetReached(target) => {
+                debug!(" -> received reached target {target:?}");
 
-                        let worker_idx =
-                            Self::find_worker_by_id(&self.workers, this_node.worker_id)?;
+                // Finished once no frontier is below the shortest distance, see `finish_targets`
+                let best = self.reached_targets.get(&target.node_id);
 
-                        debug!(
-                            " -> node[id {}] belongs to worker[idx {}]",
-                            this_node.node_id, worker_idx
-                        );
+                if self.targets.contains(&target.node_id)
+                    && !matches!(best, Some((_, best)) if *best <= target.shortest_path_len)
+                {
+                    self.reached_targets
+                        .insert(target.node_id, (current, target.shortest_path_len));
+                }
+            }
 
-                        self.workers[worker_idx].push_new_domestic(
-                            this_node.node_id,
-                            node.shortest_path_len,
-                            Some((node.parent_node_id, current_worker_id)),
-                        );
-                    }
+            MessageType::MeetingPoint(point) => {
+                debug!(" -> received meeting point {point:?}");
 
-                    MessageType::SmallestDomesticNode(node) => {
-                        debug!(
-                            " -> smallest domestic node has len: {}",
-                            node.shortest_path_len
-                        );
+                let better = match self.meeting {
+                    Some(meeting) => point.path_len < meeting.path_len,
+                    None => true,
+                };
 
-                        self.workers[current].minimal = Some(node.shortest_path_len);
-                    }
+                if better {
+                    self.meeting = Some(Meeting {
+                        worker_idx: current,
+                        node_id: point.node_id,
+                        path_len: point.path_len,
+                    });
                 }
             }
+        }
 
-            debug!("finished parsing `update_djikstra` response from worker");
+        Ok(None)
+    }
+
+    pub async fn shortest_path_query(&mut self) -> Result<Option<ShortestPathLen>, Status> {
+        if self.bidirectional {
+            return self.bidirectional_query().await;
+        }
+
+        if let Some(window) = self.parallel_window {
+            return self.parallel_query(window).await;
+        }
+
+        // Push initial node
+        self.workers[self.first_worker_idx].push_new_domestic(
+            Direction::Forward,
+            self.node_id_from,
+            0,
+            0,
+            None,
+        );
+
+        let mut next_worker = Some(self.first_worker_idx);
+
+        while let Some(current) = next_worker {
+            self.cancellation.check()?;
+
+            if let Some(path_len) = self.run_round(current, Direction::Forward, None).await? {
+                return Ok(Some(path_len));
+            }
 
             next_worker = self
-                .workers
-                .iter()
-                .enumerate()
-                .filter(|(_, w)| w.minimal.is_some())
-                .min_by_key(|(_, w)| w.minimal)
+                .find_next_worker(Direction::Forward)
                 .map(|(idx, _)| idx);
         }
 
@@ -325,12 +791,462 @@ impl QueryCoordinator {
         // Path not found
         return Ok(None);
     }
+
+    // Delta-stepping over the workers: all workers with frontiers within `window` of the smallest
+    // one run their rounds at once, up to that limit. A node explored before a shorter path to it
+    // was found is explored again, so the search ends only when no frontier is below the shortest
+    // path to the final node reported so far.
+    async fn parallel_query(
+        &mut self,
+        window: ShortestPathLen,
+    ) -> Result<Option<ShortestPathLen>, Status> {
+        use Direction::Forward;
+
+        self.workers[self.first_worker_idx].push_new_domestic(
+            Forward,
+            self.node_id_from,
+            0,
+            0,
+            None,
+        );
+
+        while let Some((_, smallest)) = self.find_next_worker(Forward) {
+            if matches!(self.final_reached, Some(best) if best <= smallest) {
+                break;
+            }
+
+            self.cancellation.check()?;
+
+            // Nodes further than the path already found are never needed
+            let round_limit = match self.final_reached {
+                Some(best) => std::cmp::min(best, smallest.saturating_add(window)),
+                None => smallest.saturating_add(window),
+            };
+
+            let current: Vec<WorkerIdx> = (0..self.workers.len())
+                .filter(|idx| matches!(self.workers[*idx].minimal(Forward), Some(m) if m <= round_limit))
+                .collect();
+
+            debug!("running rounds of workers {current:?} up to {round_limit}");
+
+            let rounds: Vec<_> = current
+                .into_iter()
+                .map(|idx| {
+                    let round = self.start_round(idx, Forward, Some(round_limit));
+
+                    async move {
+                        let responses: Vec<ResponseDjikstra> = round.await?.try_collect().await?;
+                        Ok::<_, Status>((idx, responses))
+                    }
+                })
+                .collect();
+
+            // Other rounds are not dropped halfway when one fails, as the query is forgotten then
+            // and the workers must not be running it anymore.
+            let rounds: Vec<_> = join_all(rounds)
+                .await
+                .into_iter()
+                .collect::<Result<_, _>>()?;
+
+            for (idx, responses) in rounds {
+                for response in responses {
+                    self.handle_response(idx, Forward, response)?;
+                }
+            }
+        }
+
+        debug!(
+            "parallel search finished, path length: {:?}",
+            self.final_reached
+        );
+
+        Ok(self.final_reached)
+    }
+
+    // Removes the reached targets whose distances no frontier is below anymore, so that they are
+    // not searched for by the next rounds.
+    fn finish_targets(&mut self) {
+        let smallest = self
+            .find_next_worker(Direction::Forward)
+            .map(|(_, smallest)| smallest);
+        let reached = &self.reached_targets;
+
+        self.targets.retain(|node_id| match reached.get(node_id) {
+            Some((_, shortest_path_len)) => matches!(smallest, Some(s) if s < *shortest_path_len),
+            None => true,
+        });
+    }
+
+    // Runs the search from `from` until all targets are reached or no worker has nodes left.
+    // Returns the distances to the reached targets.
+    pub async fn one_to_many_query(&mut self) -> Result<HashMap<NodeId, ShortestPathLen>, Status> {
+        self.workers[self.first_worker_idx].push_new_domestic(
+            Direction::Forward,
+            self.node_id_from,
+            0,
+            0,
+            None,
+        );
+
+        let mut next_worker = Some(self.first_worker_idx);
+
+        while let Some(current) = next_worker {
+            if self.targets.is_empty() {
+                break;
+            }
+
+            self.cancellation.check()?;
+            self.run_round(current, Direction::Forward, None).await?;
+            self.finish_targets();
+
+            next_worker = self
+                .find_next_worker(Direction::Forward)
+                .map(|(idx, _)| idx);
+        }
+
+        debug!(
+            "one-to-many search finished, {} targets not reached",
+            self.targets.len()
+        );
+
+        let distances = self
+            .reached_targets
+            .iter()
+            .map(|(node_id, (_, shortest_path_len))| (*node_id, *shortest_path_len))
+            .collect();
+
+        Ok(distances)
+    }
+
+    // Runs the search from `from` until all workers' frontiers exceed the budget, then collects the
+    // reached nodes from the involved workers.
+    pub async fn reachable_query(
+        &mut self,
+        budget: ShortestPathLen,
+    ) -> Result<Vec<executer::ReachableNode>, Status> {
+        self.reachable_set = true;
+        self.workers[self.first_worker_idx].push_new_domestic(
+            Direction::Forward,
+            self.node_id_from,
+            0,
+            0,
+            None,
+        );
+
+        let mut next_worker = Some(self.first_worker_idx);
+
+        while let Some(current) = next_worker {
+            self.cancellation.check()?;
+
+            self.run_round(current, Direction::Forward, Some(budget))
+                .await?;
+
+            next_worker = match self.find_next_worker(Direction::Forward) {
+                Some((idx, minimal)) if minimal <= budget => Some(idx),
+                _ => None,
+            };
+        }
+
+        let requests = self
+            .workers
+            .iter()
+            .filter(|worker| worker.is_involved)
+            .map(|worker| {
+                let mut channel = worker.channel.clone();
+                let request = worker::RequestReachable {
+                    query_id: self.query_id,
+                    budget,
+                };
+                let worker_id = worker.id;
+
+                async move {
+                    let nodes: Vec<_> = channel
+                        .get_reachable(request)
+                        .await?
+                        .into_inner()
+                        .try_collect()
+                        .await?;
+
+                    Ok::<_, Status>((worker_id, nodes))
+                }
+            });
+
+        let mut nodes = Vec::new();
+
+        // As the rounds of the parallel mode, all requests finish before the query is forgotten
+        let reachable: Vec<_> = join_all(requests)
+            .await
+            .into_iter()
+            .collect::<Result<_, _>>()?;
+
+        for (worker_id, reachable) in reachable {
+            nodes.extend(reachable.into_iter().map(|node| {
+                let coords = node.coords.unwrap_or_default();
+
+                executer::ReachableNode {
+                    node_id: node.node_id,
+                    worker_id,
+                    shortest_path_len: node.shortest_path_len,
+                    lat: coords.lat,
+                    lon: coords.lon,
+                }
+            }));
+        }
+
+        debug!("{} nodes reachable within {budget}", nodes.len());
+
+        Ok(nodes)
+    }
+
+    // Runs the forward search from `from` and the backward search from `to`, always advancing the
+    // one with the smaller frontier. Finishes when the frontiers together exceed the best path
+    // through a meeting point.
+    async fn bidirectional_query(&mut self) -> Result<Option<ShortestPathLen>, Status> {
+        use Direction::{Backward, Forward};
+
+        let (from, to) = (self.node_id_from, self.node_id_to);
+        self.workers[self.first_worker_idx].push_new_domestic(Forward, from, 0, 0, None);
+        self.workers[self.last_worker_idx].push_new_domestic(Backward, to, 0, 0, None);
+
+        // One of the searches ran out of nodes? There is no better meeting point.
+        while let (Some(forward), Some(backward)) = (
+            self.find_next_worker(Forward),
+            self.find_next_worker(Backward),
+        ) {
+            self.cancellation.check()?;
+
+            let best = self.meeting.map(|meeting| meeting.path_len);
+
+            if matches!(best, Some(best) if forward.1 + backward.1 >= best) {
+                break;
+            }
+
+            let ((current, _), direction, other_top) = match forward.1 <= backward.1 {
+                true => (forward, Forward, backward.1),
+                false => (backward, Backward, forward.1),
+            };
+
+            // Nodes further than the other frontier are left for the next rounds, so both
+            // searches grow evenly; nodes further than `best - other_top` are never needed.
+            let round_limit = match best {
+                Some(best) => std::cmp::min(other_top, best - other_top),
+                None => other_top,
+            };
+
+            self.run_round(current, direction, Some(round_limit))
+                .await?;
+        }
+
+        let shortest_path_len = self.meeting.map(|meeting| meeting.path_len);
+
+        debug!("bidirectional search finished, path length: {shortest_path_len:?}");
+
+        Ok(shortest_path_len)
+    }
+
+    // Distances from `from` (or to `to` if `backward`) within its worker
+    async fn local_search(
+        &mut self,
+        worker_idx: WorkerIdx,
+        node_id: NodeId,
+        backward: bool,
+        targets: Vec<NodeId>,
+    ) -> Result<worker::LocalSearchResult, Status> {
+        self.workers[worker_idx].check_health()?;
+
+        let request = worker::LocalSearchRequest {
+            node_id,
+            metric: self.metric as i32,
+            backward,
+            targets,
+        };
+
+        let result = self.workers[worker_idx]
+            .channel
+            .local_search(request)
+            .await?
+            .into_inner();
+
+        Ok(result)
+    }
+
+    // Runs the local legs in the workers of `from` and `to`, and the rest of the search on the
+    // overlay. Fails with `aborted` if the overlay is older than the fragments of those workers,
+    // or of the workers whose overlay edges are on the path.
+    pub async fn overlay_query(
+        &mut self,
+        overlay: &Overlay,
+    ) -> Result<Option<ShortestPathLen>, Status> {
+        let (worker_from, worker_to) = (self.first_worker_idx, self.last_worker_idx);
+
+        let targets = match worker_from == worker_to {
+            true => vec![self.node_id_to],
+            false => Vec::new(),
+        };
+
+        let source_leg = self
+            .local_search(worker_from, self.node_id_from, false, targets)
+            .await?;
+        let target_leg = self
+            .local_search(worker_to, self.node_id_to, true, Vec::new())
+            .await?;
+
+        for (worker_idx, version) in [
+            (worker_from, source_leg.graph_version),
+            (worker_to, target_leg.graph_version),
+        ] {
+            let worker_id = self.workers[worker_idx].id;
+
+            if overlay.version(worker_id) != Some(version) {
+                return Err(ErrorCollection::stale_overlay(worker_id));
+            }
+        }
+
+        let from = Hop {
+            node_id: self.node_id_from,
+            worker_id: self.workers[worker_from].id,
+            local: false,
+            graph_version: source_leg.graph_version,
+        };
+        let to = Hop {
+            node_id: self.node_id_to,
+            worker_id: self.workers[worker_to].id,
+            local: true,
+            graph_version: target_leg.graph_version,
+        };
+
+        let path = overlay.shortest_path(from, to, &source_leg.distances, &target_leg.distances);
+
+        if let Some((_, hops)) = &path {
+            let checked = [from.worker_id, to.worker_id];
+            self.check_overlay_versions(overlay, hops, &checked).await?;
+        }
+
+        let shortest_path_len = path.map(|(shortest_path_len, hops)| {
+            debug!("overlay path has {} hops", hops.len());
+            self.overlay_path = Some(hops);
+            shortest_path_len
+        });
+
+        debug!("overlay search finished, path length: {shortest_path_len:?}");
+
+        Ok(shortest_path_len)
+    }
+
+    // Compares the versions of the fragments of the workers on `hops` (besides the `checked` ones)
+    // with the versions the overlay was built from. A boundary edge comes from the worker of the
+    // previous hop, so the workers of the hops cover all overlay edges used.
+    async fn check_overlay_versions(
+        &self,
+        overlay: &Overlay,
+        hops: &[Hop],
+        checked: &[WorkerId],
+    ) -> Result<()> {
+        let mut worker_ids: Vec<WorkerId> = hops
+            .iter()
+            .map(|hop| hop.worker_id)
+            .filter(|worker_id| !checked.contains(worker_id))
+            .collect();
+
+        worker_ids.sort_unstable();
+        worker_ids.dedup();
+
+        let requests = worker_ids.into_iter().map(|worker_id| async move {
+            let worker_idx = Self::find_worker_by_id(&self.workers, worker_id)?;
+            let mut channel = self.workers[worker_idx].channel.clone();
+            let version = channel.ping(()).await?.into_inner().version;
+
+            match overlay.version(worker_id) == Some(version) {
+                true => Ok(()),
+                false => Err(ErrorCollection::stale_overlay(worker_id)),
+            }
+        });
+
+        try_join_all(requests).await?;
+
+        Ok(())
+    }
+
+    // Whole path of the overlay query, from `from` to `to`; local hops are unpacked by their
+    // workers.
+    pub async fn unpack_overlay_path(&mut self, hops: &[Hop]) -> Result<Vec<executer::Node>> {
+        let mut nodes = vec![executer::Node {
+            node_id: hops[0].node_id,
+            worker_id: hops[0].worker_id,
+        }];
+
+        for pair in hops.windows(2) {
+            let (previous, hop) = (pair[0], pair[1]);
+
+            if !hop.local {
+                nodes.push(executer::Node {
+                    node_id: hop.node_id,
+                    worker_id: hop.worker_id,
+                });
+                continue;
+            }
+
+            let worker_idx = Self::find_worker_by_id(&self.workers, hop.worker_id)?;
+            let request = worker::LocalPathRequest {
+                node_from_id: previous.node_id,
+                node_to_id: hop.node_id,
+                metric: self.metric as i32,
+                graph_version: hop.graph_version,
+            };
+
+            let path = self.workers[worker_idx]
+                .channel
+                .get_local_path(request)
+                .await?
+                .into_inner()
+                .node_ids;
+
+            if path.is_empty() {
+                return Err(ErrorCollection::cannot_unpack(
+                    previous.node_id,
+                    hop.node_id,
+                ));
+            }
+
+            nodes.extend(path.into_iter().skip(1).map(|node_id| executer::Node {
+                node_id,
+                worker_id: hop.worker_id,
+            }));
+        }
+
+        Ok(nodes)
+    }
 }
 
 impl ErrorCollection {
+    fn stale_overlay(id: WorkerId) -> Status {
+        Status::aborted(format!(
+            "fragment of worker[id: {id}] changed since the overlay was built"
+        ))
+    }
+
+    fn cannot_unpack(from: NodeId, to: NodeId) -> Status {
+        Status::internal(format!("cannot unpack overlay path[{from} -> {to}]"))
+    }
+
     fn worker_not_found(id: WorkerId) -> Status {
         Status::out_of_range(format!("worker[id: {id}] does not exist"))
     }
+
+    fn worker_overloaded(id: WorkerId, status: Status) -> Status {
+        Status::resource_exhausted(format!(
+            "worker[id: {id}] cannot take more queries now, retry later: {}",
+            status.message()
+        ))
+    }
+
+    fn a_star_with_metric(metric: worker::Metric) -> Status {
+        Status::invalid_argument(format!("A* does not support the {metric:?} metric"))
+    }
+
+    fn a_star_with_bidirectional() -> Status {
+        Status::invalid_argument("A* cannot be combined with the bidirectional search")
+    }
 }
 
 mod proto_helpers {
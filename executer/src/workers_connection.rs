@@ -1,20 +1,103 @@
-use futures::Future;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::Arc;
+
 use log::Level::Debug;
-use tonic::transport::{Channel, Error};
+use tokio::time::Duration;
+use tonic::transport::{Channel, Endpoint, Error};
 use tonic::Status;
 
 use generated::manager::manager_service_client::ManagerServiceClient;
+use generated::manager::workers_list::WorkerEntry;
 use generated::worker::worker_client::WorkerClient;
 
+use crate::ErrorCollection;
+
 pub type WorkerId = u32;
 
+// Requests to a worker which cannot be reached fail after this long instead of hanging
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(1);
+
+const PROBE_INTERVAL: Duration = Duration::from_secs(2);
+const PROBE_TIMEOUT: Duration = Duration::from_secs(1);
+// Failing workers are probed less and less often, down to this interval. Only a probe brings a
+// worker back up, so it is short enough for a restarted worker to be used again within seconds.
+const MAX_PROBE_INTERVAL: Duration = Duration::from_secs(8);
+
+// Consecutive failed probes after which a worker is considered down
+const DOWN_AFTER_FAILURES: u32 = 3;
+
+// How often the list of the workers is fetched again from the manager
+pub const WORKERS_REFRESH_INTERVAL: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WorkerState {
+    Up,
+    // Failed the last probes, but not enough of them to be down
+    Degraded,
+    // Queries which need the worker fail right away
+    Down,
+}
+
+// Health of a worker, shared by all copies of its `Worker`
+#[derive(Debug, Default)]
+pub struct Health {
+    // Consecutive failed probes
+    failures: AtomicU32,
+    // The worker left the list of the manager, so it is no longer probed
+    removed: AtomicBool,
+}
+
+impl Health {
+    pub fn state(&self) -> WorkerState {
+        match self.failures.load(Ordering::Relaxed) {
+            0 => WorkerState::Up,
+            failures if failures < DOWN_AFTER_FAILURES => WorkerState::Degraded,
+            _ => WorkerState::Down,
+        }
+    }
+
+    // Fails with `unavailable` if the worker is down
+    pub fn check(&self, worker_id: WorkerId) -> Result<(), Status> {
+        match self.state() {
+            WorkerState::Down => Err(ErrorCollection::worker_down(worker_id)),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn remove(&self) {
+        self.removed.store(true, Ordering::Relaxed);
+    }
+
+    fn record_probe(&self, worker_id: WorkerId, healthy: bool) {
+        let before = self.state();
+
+        match healthy {
+            true => self.failures.store(0, Ordering::Relaxed),
+            false => {
+                self.failures.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        let after = self.state();
+
+        if before != after {
+            match after {
+                WorkerState::Up => info!("worker[id {worker_id}] is up"),
+                state => warn!("worker[id {worker_id}] is {state:?}"),
+            }
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct Worker {
     pub id: WorkerId,
+    pub address: String,
     pub channel: WorkerClient<Channel>,
+    pub health: Arc<Health>,
 }
 
-pub type WorkerAddrList = Vec<generated::manager::workers_list::WorkerEntry>;
+pub type WorkerAddrList = Vec<WorkerEntry>;
 pub type WorkerList = Vec<Worker>;
 
 // Get workers addresses sorted by ID
@@ -44,25 +127,65 @@ pub async fn get_sorted_workers_addresses(
     Ok(workers)
 }
 
-// Return connection to workers; returned list keeps the order of the input list
-pub fn connect_to_all_workers(
-    addrs: WorkerAddrList,
-) -> impl Future<Output = Result<WorkerList, Error>> {
+// Connection is made by the first request, and made again by the next one after it breaks, so
+// the worker may start (or restart) after the executer.
+pub fn connect_to_worker(entry: WorkerEntry) -> Result<Worker, Error> {
+    debug!(
+        " -> connecting to worker[id {}] at address '{}'",
+        entry.worker_id, entry.address
+    );
+
+    let channel = Endpoint::from_shared(entry.address.clone())?
+        .connect_timeout(CONNECT_TIMEOUT)
+        .connect_lazy();
+
+    Ok(Worker {
+        id: entry.worker_id,
+        address: entry.address,
+        channel: WorkerClient::new(channel),
+        health: Arc::default(),
+    })
+}
+
+// Return connections to workers; returned list keeps the order of the input list.
+pub fn connect_to_all_workers(addrs: WorkerAddrList) -> Result<WorkerList, Error> {
     info!("connecting to workers");
 
-    use futures::TryFutureExt;
+    addrs.into_iter().map(connect_to_worker).collect()
+}
+
+// Fails with `unavailable` if any of the workers is down; for the requests sent to all of them
+pub fn check_all(workers: &[Worker]) -> Result<(), Status> {
+    workers
+        .iter()
+        .try_for_each(|worker| worker.health.check(worker.id))
+}
 
-    let workers_connect = addrs.into_iter().map(|w| {
-        debug!(
-            " -> connecting to worker[id {}] at address '{}'",
-            w.worker_id, w.address
+// Probes the worker until it is removed, backing off while it fails. The probes also reconnect to
+// the worker.
+pub async fn probe(worker: Worker) {
+    let mut interval = PROBE_INTERVAL;
+
+    while !worker.health.removed.load(Ordering::Relaxed) {
+        let mut channel = worker.channel.clone();
+        let healthy = matches!(
+            tokio::time::timeout(PROBE_TIMEOUT, channel.ping(())).await,
+            Ok(Ok(_))
         );
 
-        WorkerClient::connect(w.address).map_ok(move |channel| Worker {
-            id: w.worker_id,
-            channel,
-        })
-    });
+        worker.health.record_probe(worker.id, healthy);
+
+        interval = match healthy {
+            true => PROBE_INTERVAL,
+            false => std::cmp::min(interval * 2, MAX_PROBE_INTERVAL),
+        };
 
-    futures::future::try_join_all(workers_connect)
+        tokio::time::sleep(interval).await;
+    }
+}
+
+impl ErrorCollection {
+    fn worker_down(worker_id: WorkerId) -> Status {
+        Status::unavailable(format!("worker[id: {worker_id}] is down"))
+    }
 }
@@ -1,2 +1,4 @@
 kubectl delete services partitioner executor worker
-kubectl delete deployments partitioner executor worker
+kubectl delete deployments partitioner executor
+kubectl delete statefulsets worker
+kubectl delete pvc -l app=worker
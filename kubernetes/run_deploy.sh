@@ -14,8 +14,8 @@ sleep 10 # and a safety margin
 
 kubectl apply -f deployments/worker.yaml
 kubectl apply -f services/worker.yaml
-kubectl scale deployment worker --replicas=$replicas
-kubectl rollout status deployment/worker # wait for deployment to finish
+kubectl scale statefulset worker --replicas=$replicas
+kubectl rollout status statefulset/worker # wait for deployment to finish
 sleep 10 # and a safety margin
 
 kubectl apply -f deployments/executor.yaml
@@ -1,22 +1,35 @@
 from google.protobuf import empty_pb2 as _empty_pb2
 from google.protobuf.internal import containers as _containers
+from google.protobuf.internal import enum_type_wrapper as _enum_type_wrapper
 from google.protobuf import descriptor as _descriptor
 from google.protobuf import message as _message
 from typing import ClassVar as _ClassVar, Iterable as _Iterable, Mapping as _Mapping, Optional as _Optional, Union as _Union
 
 DESCRIPTOR: _descriptor.FileDescriptor
+DISTANCE: Metric
+TRAVEL_TIME: Metric
 
 class Edge(_message.Message):
-    __slots__ = ["node_from_id", "node_to_id", "node_to_worker_id", "weight"]
+    __slots__ = ["metrics", "node_from_id", "node_from_worker_id", "node_to_id", "node_to_worker_id"]
+    METRICS_FIELD_NUMBER: _ClassVar[int]
     NODE_FROM_ID_FIELD_NUMBER: _ClassVar[int]
+    NODE_FROM_WORKER_ID_FIELD_NUMBER: _ClassVar[int]
     NODE_TO_ID_FIELD_NUMBER: _ClassVar[int]
     NODE_TO_WORKER_ID_FIELD_NUMBER: _ClassVar[int]
-    WEIGHT_FIELD_NUMBER: _ClassVar[int]
+    metrics: _containers.RepeatedCompositeFieldContainer[EdgeMetric]
     node_from_id: int
+    node_from_worker_id: int
     node_to_id: int
     node_to_worker_id: int
-    weight: int
-    def __init__(self, node_from_id: _Optional[int] = ..., node_to_id: _Optional[int] = ..., weight: _Optional[int] = ..., node_to_worker_id: _Optional[int] = ...) -> None: ...
+    def __init__(self, node_from_id: _Optional[int] = ..., node_to_id: _Optional[int] = ..., metrics: _Optional[_Iterable[_Union[EdgeMetric, _Mapping]]] = ..., node_to_worker_id: _Optional[int] = ..., node_from_worker_id: _Optional[int] = ...) -> None: ...
+
+class EdgeMetric(_message.Message):
+    __slots__ = ["metric", "value"]
+    METRIC_FIELD_NUMBER: _ClassVar[int]
+    VALUE_FIELD_NUMBER: _ClassVar[int]
+    metric: Metric
+    value: int
+    def __init__(self, metric: _Optional[Metric] = ..., value: _Optional[int] = ...) -> None: ...
 
 class GraphPiece(_message.Message):
     __slots__ = ["edges", "nodes"]
@@ -37,16 +50,20 @@ class Node(_message.Message):
     def __init__(self, node_id: _Optional[int] = ..., lat: _Optional[float] = ..., lon: _Optional[float] = ...) -> None: ...
 
 class WorkerMetadata(_message.Message):
-    __slots__ = ["worker_id"]
+    __slots__ = ["worker_id", "workers_count"]
     WORKER_ID_FIELD_NUMBER: _ClassVar[int]
+    WORKERS_COUNT_FIELD_NUMBER: _ClassVar[int]
     worker_id: int
-    def __init__(self, worker_id: _Optional[int] = ...) -> None: ...
+    workers_count: int
+    def __init__(self, worker_id: _Optional[int] = ..., workers_count: _Optional[int] = ...) -> None: ...
 
 class WorkerProperties(_message.Message):
-    __slots__ = ["listening_address"]
+    __slots__ = ["listening_address", "worker_id"]
     LISTENING_ADDRESS_FIELD_NUMBER: _ClassVar[int]
+    WORKER_ID_FIELD_NUMBER: _ClassVar[int]
     listening_address: str
-    def __init__(self, listening_address: _Optional[str] = ...) -> None: ...
+    worker_id: int
+    def __init__(self, listening_address: _Optional[str] = ..., worker_id: _Optional[int] = ...) -> None: ...
 
 class WorkersList(_message.Message):
     __slots__ = ["workers"]
@@ -60,3 +77,6 @@ class WorkersList(_message.Message):
     WORKERS_FIELD_NUMBER: _ClassVar[int]
     workers: _containers.RepeatedCompositeFieldContainer[WorkersList.WorkerEntry]
     def __init__(self, workers: _Optional[_Iterable[_Union[WorkersList.WorkerEntry, _Mapping]]] = ...) -> None: ...
+
+class Metric(int, metaclass=_enum_type_wrapper.EnumTypeWrapper):
+    __slots__ = []
@@ -1,4 +1,5 @@
 import grpc
+import math
 import re
 import threading
 import traceback
@@ -10,6 +11,45 @@ import rpc_servers.manager_pb2 as manager__pb2
 import rpc_servers.manager_pb2_grpc as manager_pb2_grpc
 
 
+# Assumed speed (km/h) on roads of each `highway` type, used when the way has no `maxspeed` tag
+HIGHWAY_SPEEDS = {
+    'motorway': 110,
+    'trunk': 90,
+    'primary': 70,
+    'secondary': 60,
+    'tertiary': 50,
+    'residential': 30,
+    'living_street': 10,
+    'service': 20,
+}
+DEFAULT_SPEED = 30
+EARTH_RADIUS_METERS = 6371000
+
+
+def get_way_speed(way_tags):
+    maxspeed = re.match(r'\d+', way_tags.get('maxspeed', ''))
+    if maxspeed:
+        return int(maxspeed.group(0))
+    return HIGHWAY_SPEEDS.get(way_tags.get('highway'), DEFAULT_SPEED)
+
+
+def get_edge_metrics(lat1, lon1, lat2, lon2, way_tags):
+    distance = ((lat2 - lat1) ** 2 + (lon2 - lon1) ** 2) ** 0.5
+    distance = int(1e7 * distance)
+
+    # Haversine distance in meters
+    phi1, phi2 = math.radians(lat1), math.radians(lat2)
+    a = math.sin((phi2 - phi1) / 2) ** 2 + \
+        math.cos(phi1) * math.cos(phi2) * math.sin(math.radians(lon2 - lon1) / 2) ** 2
+    meters = 2 * EARTH_RADIUS_METERS * math.asin(math.sqrt(a))
+    travel_time = int(1000 * meters / (get_way_speed(way_tags) / 3.6))
+
+    return [
+        manager__pb2.EdgeMetric(metric=manager__pb2.DISTANCE, value=distance),
+        manager__pb2.EdgeMetric(metric=manager__pb2.TRAVEL_TIME, value=travel_time),
+    ]
+
+
 def get_node_partition(node, partitions, parser):
     for _, line in parser.get_lines():
         if parser.is_node_line(line):
@@ -21,7 +61,7 @@ def get_node_partition(node, partitions, parser):
     return None
 
 
-def get_edge_info(node1, node2, node_cache, parser, partitions, partition_ix):
+def get_edge_info(node1, node2, node_cache, parser, partitions, partition_ix, way_tags):
     if node1 not in node_cache or node_cache[node1][2] != partition_ix:
         return
     lat1, lon1, partition1 = node_cache.get(node1)
@@ -33,20 +73,38 @@ def get_edge_info(node1, node2, node_cache, parser, partitions, partition_ix):
         partition2 = partition2[0]
         node_cache[node2] = (lat2, lon2, partition2)
 
-    edge_weight = ((lat2 - lat1) ** 2 + (lon2 - lon1) ** 2) ** 0.5
-    edge_weight = int(1e7 * edge_weight)
+    edge_metrics = get_edge_metrics(lat1, lon1, lat2, lon2, way_tags)
     if partition2 != partition1:
         return manager__pb2.Edge(
             node_from_id=node1,
             node_to_id=node2,
-            weight=edge_weight,
+            metrics=edge_metrics,
             node_to_worker_id=partition2 + 1
         )
 
     return manager__pb2.Edge(
         node_from_id=node1,
         node_to_id=node2,
-        weight=edge_weight,
+        metrics=edge_metrics,
+    )
+
+
+def get_incoming_edge_info(node1, node2, node_cache, parser, partitions, way_tags):
+    lat2, lon2, _ = node_cache.get(node2)
+
+    if node1 in node_cache:
+        lat1, lon1, partition1 = node_cache.get(node1)
+    else:
+        lat1, lon1, partition1 = get_node_partition(node1, partitions, parser)
+        partition1 = partition1[0]
+        node_cache[node1] = (lat1, lon1, partition1)
+
+    edge_metrics = get_edge_metrics(lat1, lon1, lat2, lon2, way_tags)
+    return manager__pb2.Edge(
+        node_from_id=node1,
+        node_to_id=node2,
+        metrics=edge_metrics,
+        node_from_worker_id=partition1 + 1
     )
 
 
@@ -76,12 +134,20 @@ def send_partition_edges(node_cache, partitions, parser):
             # TODO: Should we consider it a loop??
             for node1, node2 in zip(way_nodes, way_nodes[1:]):
                 if node1 in node_cache and node_cache[node1][2] == partition_ix:
-                    edge_info = get_edge_info(node1, node2, node_cache, parser, partitions, partition_ix)
+                    edge_info = get_edge_info(node1, node2, node_cache, parser, partitions, partition_ix, way_tags)
                     print(f'Sending edge: {edge_info}')
 
                     yield manager__pb2.GraphPiece(
                         edges=edge_info
                     )
+                elif node2 in node_cache and node_cache[node2][2] == partition_ix:
+                    # Edge coming from other partition, needed for the backward search
+                    edge_info = get_incoming_edge_info(node1, node2, node_cache, parser, partitions, way_tags)
+                    print(f'Sending incoming edge: {edge_info}')
+
+                    yield manager__pb2.GraphPiece(
+                        edges=edge_info
+                    )
 
 
 class ManagerServiceServicer(manager_pb2_grpc.ManagerServiceServicer):
@@ -100,9 +166,14 @@ class ManagerServiceServicer(manager_pb2_grpc.ManagerServiceServicer):
         """
         with self.workers_lock:
             worker_addr = request.listening_address
-            worker_id = self.workers.get(worker_addr, len(self.workers) + 1)
+            if request.HasField('worker_id'):
+                # Restarted worker with the fragment kept in its snapshot
+                worker_id = request.worker_id
+            else:
+                known = [wid for wid, addr in self.workers.items() if addr == worker_addr]
+                worker_id = known[0] if known else len(self.workers) + 1
             self.workers[worker_id] = worker_addr
-        return manager__pb2.WorkerMetadata(worker_id=worker_id)
+        return manager__pb2.WorkerMetadata(worker_id=worker_id, workers_count=self.n_partitions)
 
     def GetGraphFragment(self, request, context):
         """Missing associated documentation comment in .proto file."""
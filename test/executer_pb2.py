@@ -14,25 +14,61 @@ _sym_db = _symbol_database.Default()
 from google.protobuf import empty_pb2 as google_dot_protobuf_dot_empty__pb2
 
 
-DESCRIPTOR = _descriptor_pool.Default().AddSerializedFile(b'\n\x0e\x65xecuter.proto\x12\x08\x65xecuter\x1a\x1bgoogle/protobuf/empty.proto\"G\n\x0fNodeCoordinates\x12\x0f\n\x07node_id\x18\x01 \x01(\x04\x12\x11\n\tworker_id\x18\x02 \x01(\r\x12\x10\n\x08query_id\x18\x03 \x01(\r\"5\n\tQueryData\x12\x14\n\x0cnode_id_from\x18\x01 \x01(\x04\x12\x12\n\nnode_id_to\x18\x02 \x01(\x04\"h\n\x0cQueryResults\x12\x15\n\x08query_id\x18\x01 \x01(\rH\x00\x88\x01\x01\x12\x1e\n\x11shortest_path_len\x18\x02 \x01(\x04H\x01\x88\x01\x01\x42\x0b\n\t_query_idB\x14\n\x12_shortest_path_len\"\x1b\n\x07QueryId\x12\x10\n\x08query_id\x18\x01 \x01(\r\"*\n\x04Node\x12\x0f\n\x07node_id\x18\x01 \x01(\x04\x12\x11\n\tworker_id\x18\x02 \x01(\r\".\n\x12\x43oordinateResponse\x12\x0b\n\x03lat\x18\x01 \x01(\x01\x12\x0b\n\x03lon\x18\x02 \x01(\x01\x32\x88\x02\n\x08\x45xecuter\x12@\n\x11ShortestPathQuery\x12\x13.executer.QueryData\x1a\x16.executer.QueryResults\x12<\n\x15\x42\x61\x63ktrackPathForQuery\x12\x11.executer.QueryId\x1a\x0e.executer.Node0\x01\x12\x38\n\x0b\x46orgetQuery\x12\x11.executer.QueryId\x1a\x16.google.protobuf.Empty\x12\x42\n\x0eGetCoordinates\x12\x0e.executer.Node\x1a\x1c.executer.CoordinateResponse(\x01\x30\x01\x62\x06proto3')
+DESCRIPTOR = _descriptor_pool.Default().AddSerializedFile(b'\n\x0e\x65xecuter.proto\x12\x08\x65xecuter\x1a\x1bgoogle/protobuf/empty.proto\"G\n\x0fNodeCoordinates\x12\x0f\n\x07node_id\x18\x01 \x01(\x04\x12\x11\n\tworker_id\x18\x02 \x01(\r\x12\x10\n\x08query_id\x18\x03 \x01(\r\"\xc1\x01\n\tQueryData\x12\x14\n\x0cnode_id_from\x18\x01 \x01(\x04\x12\x12\n\nnode_id_to\x18\x02 \x01(\x04\x12\x0e\n\x06\x61_star\x18\x03 \x01(\x08\x12\x15\n\rbidirectional\x18\x04 \x01(\x08\x12 \n\x06metric\x18\x05 \x01(\x0e\x32\x10.executer.Metric\x12\x0f\n\x07overlay\x18\x06 \x01(\x08\x12\x1c\n\x0fparallel_window\x18\x07 \x01(\x04H\x00\x88\x01\x01\x42\x12\n\x10_parallel_window\"h\n\x0cQueryResults\x12\x15\n\x08query_id\x18\x01 \x01(\rH\x00\x88\x01\x01\x12\x1e\n\x11shortest_path_len\x18\x02 \x01(\x04H\x01\x88\x01\x01\x42\x0b\n\t_query_idB\x14\n\x12_shortest_path_len\"a\n\x12OneToManyQueryData\x12\x14\n\x0cnode_id_from\x18\x01 \x01(\x04\x12\x13\n\x0bnode_ids_to\x18\x02 \x03(\x04\x12 \n\x06metric\x18\x03 \x01(\x0e\x32\x10.executer.Metric\"|\n\x0cTargetResult\x12\x12\n\nnode_id_to\x18\x01 \x01(\x04\x12\x15\n\x08query_id\x18\x02 \x01(\rH\x00\x88\x01\x01\x12\x1e\n\x11shortest_path_len\x18\x03 \x01(\x04H\x01\x88\x01\x01\x42\x0b\n\t_query_idB\x14\n\x12_shortest_path_len\";\n\x10OneToManyResults\x12\'\n\x07results\x18\x01 \x03(\x0b\x32\x16.executer.TargetResult\"j\n\x12ReachableQueryData\x12\x14\n\x0cnode_id_from\x18\x01 \x01(\x04\x12\x0e\n\x06\x62udget\x18\x02 \x01(\x04\x12 \n\x06metric\x18\x03 \x01(\x0e\x32\x10.executer.Metric\x12\x0c\n\x04hull\x18\x04 \x01(\x08\"h\n\rReachableNode\x12\x0f\n\x07node_id\x18\x01 \x01(\x04\x12\x11\n\tworker_id\x18\x02 \x01(\r\x12\x19\n\x11shortest_path_len\x18\x03 \x01(\x04\x12\x0b\n\x03lat\x18\x04 \x01(\x01\x12\x0b\n\x03lon\x18\x05 \x01(\x01\"4\n\x04Hull\x12,\n\x06points\x18\x01 \x03(\x0b\x32\x1c.executer.CoordinateResponse\"k\n\x11ReachableResponse\x12\'\n\x04node\x18\x01 \x01(\x0b\x32\x17.executer.ReachableNodeH\x00\x12\x1e\n\x04hull\x18\x02 \x01(\x0b\x32\x0e.executer.HullH\x00\x42\r\n\x0bMessageType\"r\n\x11NearestNodesQuery\x12\x0b\n\x03lat\x18\x01 \x01(\x01\x12\x0b\n\x03lon\x18\x02 \x01(\x01\x12\t\n\x01k\x18\x03 \x01(\r\x12\x17\n\nmax_radius\x18\x04 \x01(\x01H\x00\x88\x01\x01\x12\x10\n\x08routable\x18\x05 \x01(\x08\x42\r\n\x0b_max_radius\"Z\n\x08NearNode\x12\x0f\n\x07node_id\x18\x01 \x01(\x04\x12\x11\n\tworker_id\x18\x02 \x01(\r\x12\x10\n\x08\x64istance\x18\x03 \x01(\x01\x12\x0b\n\x03lat\x18\x04 \x01(\x01\x12\x0b\n\x03lon\x18\x05 \x01(\x01\"8\n\x13NearestNodesResults\x12!\n\x05nodes\x18\x01 \x03(\x0b\x32\x12.executer.NearNode\"\x9c\x02\n\x14\x43oordinatesQueryData\x12\x10\n\x08lat_from\x18\x01 \x01(\x01\x12\x10\n\x08lon_from\x18\x02 \x01(\x01\x12\x0e\n\x06lat_to\x18\x03 \x01(\x01\x12\x0e\n\x06lon_to\x18\x04 \x01(\x01\x12\x0e\n\x06\x61_star\x18\x05 \x01(\x08\x12\x15\n\rbidirectional\x18\x06 \x01(\x08\x12 \n\x06metric\x18\x07 \x01(\x0e\x32\x10.executer.Metric\x12\x0f\n\x07overlay\x18\x08 \x01(\x08\x12\x1e\n\x11max_snap_distance\x18\t \x01(\x01H\x00\x88\x01\x01\x12\x1c\n\x0fparallel_window\x18\n \x01(\x04H\x01\x88\x01\x01\x42\x14\n\x12_max_snap_distanceB\x12\n\x10_parallel_window\"\x8e\x01\n\x17\x43oordinatesQueryResults\x12%\n\tnode_from\x18\x01 \x01(\x0b\x32\x12.executer.NearNode\x12#\n\x07node_to\x18\x02 \x01(\x0b\x32\x12.executer.NearNode\x12\'\n\x07results\x18\x03 \x01(\x0b\x32\x16.executer.QueryResults\"p\n\x17KShortestPathsQueryData\x12\x14\n\x0cnode_id_from\x18\x01 \x01(\x04\x12\x12\n\nnode_id_to\x18\x02 \x01(\x04\x12\t\n\x01k\x18\x03 \x01(\r\x12 \n\x06metric\x18\x04 \x01(\x0e\x32\x10.executer.Metric\">\n\x15KShortestPathsResults\x12%\n\x05paths\x18\x01 \x03(\x0b\x32\x16.executer.QueryResults\"\x1b\n\x07QueryId\x12\x10\n\x08query_id\x18\x01 \x01(\r\"*\n\x04Node\x12\x0f\n\x07node_id\x18\x01 \x01(\x04\x12\x11\n\tworker_id\x18\x02 \x01(\r\".\n\x12\x43oordinateResponse\x12\x0b\n\x03lat\x18\x01 \x01(\x01\x12\x0b\n\x03lon\x18\x02 \x01(\x01\"\x90\x01\n\x10\x45\x64geWeightUpdate\x12\x14\n\x0cnode_from_id\x18\x01 \x01(\x04\x12\x12\n\nnode_to_id\x18\x02 \x01(\x04\x12 \n\x06metric\x18\x03 \x01(\x0e\x32\x10.executer.Metric\x12\x14\n\nnew_weight\x18\x04 \x01(\x04H\x00\x12\x10\n\x06\x63losed\x18\x05 \x01(\x08H\x00\x42\x08\n\x06\x43hange\"@\n\x11\x45\x64geWeightUpdates\x12+\n\x07updates\x18\x01 \x03(\x0b\x32\x1a.executer.EdgeWeightUpdate\",\n\x17\x45\x64geWeightUpdatesResult\x12\x11\n\tunmatched\x18\x01 \x03(\r*\'\n\x06Metric\x12\x0c\n\x08\x44ISTANCE\x10\x00\x12\x0f\n\x0bTRAVEL_TIME\x10\x01\x32\x84\x06\n\x08\x45xecuter\x12@\n\x11ShortestPathQuery\x12\x13.executer.QueryData\x1a\x16.executer.QueryResults\x12<\n\x15\x42\x61\x63ktrackPathForQuery\x12\x11.executer.QueryId\x1a\x0e.executer.Node0\x01\x12\x38\n\x0b\x46orgetQuery\x12\x11.executer.QueryId\x1a\x16.google.protobuf.Empty\x12\x42\n\x0eGetCoordinates\x12\x0e.executer.Node\x1a\x1c.executer.CoordinateResponse(\x01\x30\x01\x12J\n\x0eOneToManyQuery\x12\x1c.executer.OneToManyQueryData\x1a\x1a.executer.OneToManyResults\x12M\n\x0eReachableQuery\x12\x1c.executer.ReachableQueryData\x1a\x1b.executer.ReachableResponse0\x01\x12J\n\x0cNearestNodes\x12\x1b.executer.NearestNodesQuery\x1a\x1d.executer.NearestNodesResults\x12\x63\n\x1eShortestPathBetweenCoordinates\x12\x1e.executer.CoordinatesQueryData\x1a!.executer.CoordinatesQueryResults\x12Y\n\x13KShortestPathsQuery\x12!.executer.KShortestPathsQueryData\x1a\x1f.executer.KShortestPathsResults\x12S\n\x11UpdateEdgeWeights\x12\x1b.executer.EdgeWeightUpdates\x1a!.executer.EdgeWeightUpdatesResultb\x06proto3')
 
 _builder.BuildMessageAndEnumDescriptors(DESCRIPTOR, globals())
 _builder.BuildTopDescriptorsAndMessages(DESCRIPTOR, 'executer_pb2', globals())
 if _descriptor._USE_C_DESCRIPTORS == False:
 
   DESCRIPTOR._options = None
+  _METRIC._serialized_start=2351
+  _METRIC._serialized_end=2390
   _NODECOORDINATES._serialized_start=57
   _NODECOORDINATES._serialized_end=128
-  _QUERYDATA._serialized_start=130
-  _QUERYDATA._serialized_end=183
-  _QUERYRESULTS._serialized_start=185
-  _QUERYRESULTS._serialized_end=289
-  _QUERYID._serialized_start=291
-  _QUERYID._serialized_end=318
-  _NODE._serialized_start=320
-  _NODE._serialized_end=362
-  _COORDINATERESPONSE._serialized_start=364
-  _COORDINATERESPONSE._serialized_end=410
-  _EXECUTER._serialized_start=413
-  _EXECUTER._serialized_end=677
+  _QUERYDATA._serialized_start=131
+  _QUERYDATA._serialized_end=324
+  _QUERYRESULTS._serialized_start=326
+  _QUERYRESULTS._serialized_end=430
+  _ONETOMANYQUERYDATA._serialized_start=432
+  _ONETOMANYQUERYDATA._serialized_end=529
+  _TARGETRESULT._serialized_start=531
+  _TARGETRESULT._serialized_end=655
+  _ONETOMANYRESULTS._serialized_start=657
+  _ONETOMANYRESULTS._serialized_end=716
+  _REACHABLEQUERYDATA._serialized_start=718
+  _REACHABLEQUERYDATA._serialized_end=824
+  _REACHABLENODE._serialized_start=826
+  _REACHABLENODE._serialized_end=930
+  _HULL._serialized_start=932
+  _HULL._serialized_end=984
+  _REACHABLERESPONSE._serialized_start=986
+  _REACHABLERESPONSE._serialized_end=1093
+  _NEARESTNODESQUERY._serialized_start=1095
+  _NEARESTNODESQUERY._serialized_end=1209
+  _NEARNODE._serialized_start=1211
+  _NEARNODE._serialized_end=1301
+  _NEARESTNODESRESULTS._serialized_start=1303
+  _NEARESTNODESRESULTS._serialized_end=1359
+  _COORDINATESQUERYDATA._serialized_start=1362
+  _COORDINATESQUERYDATA._serialized_end=1646
+  _COORDINATESQUERYRESULTS._serialized_start=1649
+  _COORDINATESQUERYRESULTS._serialized_end=1791
+  _KSHORTESTPATHSQUERYDATA._serialized_start=1793
+  _KSHORTESTPATHSQUERYDATA._serialized_end=1905
+  _KSHORTESTPATHSRESULTS._serialized_start=1907
+  _KSHORTESTPATHSRESULTS._serialized_end=1969
+  _QUERYID._serialized_start=1971
+  _QUERYID._serialized_end=1998
+  _NODE._serialized_start=2000
+  _NODE._serialized_end=2042
+  _COORDINATERESPONSE._serialized_start=2044
+  _COORDINATERESPONSE._serialized_end=2090
+  _EDGEWEIGHTUPDATE._serialized_start=2093
+  _EDGEWEIGHTUPDATE._serialized_end=2237
+  _EDGEWEIGHTUPDATES._serialized_start=2239
+  _EDGEWEIGHTUPDATES._serialized_end=2303
+  _EDGEWEIGHTUPDATESRESULT._serialized_start=2305
+  _EDGEWEIGHTUPDATESRESULT._serialized_end=2349
+  _EXECUTER._serialized_start=2393
+  _EXECUTER._serialized_end=3165
 # @@protoc_insertion_point(module_scope)
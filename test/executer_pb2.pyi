@@ -1,9 +1,13 @@
 from google.protobuf import empty_pb2 as _empty_pb2
+from google.protobuf.internal import containers as _containers
+from google.protobuf.internal import enum_type_wrapper as _enum_type_wrapper
 from google.protobuf import descriptor as _descriptor
 from google.protobuf import message as _message
-from typing import ClassVar as _ClassVar, Optional as _Optional
+from typing import ClassVar as _ClassVar, Iterable as _Iterable, Mapping as _Mapping, Optional as _Optional, Union as _Union
 
 DESCRIPTOR: _descriptor.FileDescriptor
+DISTANCE: Metric
+TRAVEL_TIME: Metric
 
 class CoordinateResponse(_message.Message):
     __slots__ = ["lat", "lon"]
@@ -13,6 +17,124 @@ class CoordinateResponse(_message.Message):
     lon: float
     def __init__(self, lat: _Optional[float] = ..., lon: _Optional[float] = ...) -> None: ...
 
+class CoordinatesQueryData(_message.Message):
+    __slots__ = ["a_star", "bidirectional", "lat_from", "lat_to", "lon_from", "lon_to", "max_snap_distance", "metric", "overlay", "parallel_window"]
+    A_STAR_FIELD_NUMBER: _ClassVar[int]
+    BIDIRECTIONAL_FIELD_NUMBER: _ClassVar[int]
+    LAT_FROM_FIELD_NUMBER: _ClassVar[int]
+    LAT_TO_FIELD_NUMBER: _ClassVar[int]
+    LON_FROM_FIELD_NUMBER: _ClassVar[int]
+    LON_TO_FIELD_NUMBER: _ClassVar[int]
+    MAX_SNAP_DISTANCE_FIELD_NUMBER: _ClassVar[int]
+    METRIC_FIELD_NUMBER: _ClassVar[int]
+    OVERLAY_FIELD_NUMBER: _ClassVar[int]
+    PARALLEL_WINDOW_FIELD_NUMBER: _ClassVar[int]
+    a_star: bool
+    bidirectional: bool
+    lat_from: float
+    lat_to: float
+    lon_from: float
+    lon_to: float
+    max_snap_distance: float
+    metric: Metric
+    overlay: bool
+    parallel_window: int
+    def __init__(self, lat_from: _Optional[float] = ..., lon_from: _Optional[float] = ..., lat_to: _Optional[float] = ..., lon_to: _Optional[float] = ..., a_star: _Optional[bool] = ..., bidirectional: _Optional[bool] = ..., metric: _Optional[Metric] = ..., overlay: _Optional[bool] = ..., max_snap_distance: _Optional[float] = ..., parallel_window: _Optional[int] = ...) -> None: ...
+
+class CoordinatesQueryResults(_message.Message):
+    __slots__ = ["node_from", "node_to", "results"]
+    NODE_FROM_FIELD_NUMBER: _ClassVar[int]
+    NODE_TO_FIELD_NUMBER: _ClassVar[int]
+    RESULTS_FIELD_NUMBER: _ClassVar[int]
+    node_from: NearNode
+    node_to: NearNode
+    results: QueryResults
+    def __init__(self, node_from: _Optional[_Union[NearNode, _Mapping]] = ..., node_to: _Optional[_Union[NearNode, _Mapping]] = ..., results: _Optional[_Union[QueryResults, _Mapping]] = ...) -> None: ...
+
+class EdgeWeightUpdate(_message.Message):
+    __slots__ = ["closed", "metric", "new_weight", "node_from_id", "node_to_id"]
+    CLOSED_FIELD_NUMBER: _ClassVar[int]
+    METRIC_FIELD_NUMBER: _ClassVar[int]
+    NEW_WEIGHT_FIELD_NUMBER: _ClassVar[int]
+    NODE_FROM_ID_FIELD_NUMBER: _ClassVar[int]
+    NODE_TO_ID_FIELD_NUMBER: _ClassVar[int]
+    closed: bool
+    metric: Metric
+    new_weight: int
+    node_from_id: int
+    node_to_id: int
+    def __init__(self, node_from_id: _Optional[int] = ..., node_to_id: _Optional[int] = ..., metric: _Optional[Metric] = ..., new_weight: _Optional[int] = ..., closed: _Optional[bool] = ...) -> None: ...
+
+class EdgeWeightUpdates(_message.Message):
+    __slots__ = ["updates"]
+    UPDATES_FIELD_NUMBER: _ClassVar[int]
+    updates: _containers.RepeatedCompositeFieldContainer[EdgeWeightUpdate]
+    def __init__(self, updates: _Optional[_Iterable[_Union[EdgeWeightUpdate, _Mapping]]] = ...) -> None: ...
+
+class EdgeWeightUpdatesResult(_message.Message):
+    __slots__ = ["unmatched"]
+    UNMATCHED_FIELD_NUMBER: _ClassVar[int]
+    unmatched: _containers.RepeatedScalarFieldContainer[int]
+    def __init__(self, unmatched: _Optional[_Iterable[int]] = ...) -> None: ...
+
+class Hull(_message.Message):
+    __slots__ = ["points"]
+    POINTS_FIELD_NUMBER: _ClassVar[int]
+    points: _containers.RepeatedCompositeFieldContainer[CoordinateResponse]
+    def __init__(self, points: _Optional[_Iterable[_Union[CoordinateResponse, _Mapping]]] = ...) -> None: ...
+
+class KShortestPathsQueryData(_message.Message):
+    __slots__ = ["k", "metric", "node_id_from", "node_id_to"]
+    K_FIELD_NUMBER: _ClassVar[int]
+    METRIC_FIELD_NUMBER: _ClassVar[int]
+    NODE_ID_FROM_FIELD_NUMBER: _ClassVar[int]
+    NODE_ID_TO_FIELD_NUMBER: _ClassVar[int]
+    k: int
+    metric: Metric
+    node_id_from: int
+    node_id_to: int
+    def __init__(self, node_id_from: _Optional[int] = ..., node_id_to: _Optional[int] = ..., k: _Optional[int] = ..., metric: _Optional[Metric] = ...) -> None: ...
+
+class KShortestPathsResults(_message.Message):
+    __slots__ = ["paths"]
+    PATHS_FIELD_NUMBER: _ClassVar[int]
+    paths: _containers.RepeatedCompositeFieldContainer[QueryResults]
+    def __init__(self, paths: _Optional[_Iterable[_Union[QueryResults, _Mapping]]] = ...) -> None: ...
+
+class NearNode(_message.Message):
+    __slots__ = ["distance", "lat", "lon", "node_id", "worker_id"]
+    DISTANCE_FIELD_NUMBER: _ClassVar[int]
+    LAT_FIELD_NUMBER: _ClassVar[int]
+    LON_FIELD_NUMBER: _ClassVar[int]
+    NODE_ID_FIELD_NUMBER: _ClassVar[int]
+    WORKER_ID_FIELD_NUMBER: _ClassVar[int]
+    distance: float
+    lat: float
+    lon: float
+    node_id: int
+    worker_id: int
+    def __init__(self, node_id: _Optional[int] = ..., worker_id: _Optional[int] = ..., distance: _Optional[float] = ..., lat: _Optional[float] = ..., lon: _Optional[float] = ...) -> None: ...
+
+class NearestNodesQuery(_message.Message):
+    __slots__ = ["k", "lat", "lon", "max_radius", "routable"]
+    K_FIELD_NUMBER: _ClassVar[int]
+    LAT_FIELD_NUMBER: _ClassVar[int]
+    LON_FIELD_NUMBER: _ClassVar[int]
+    MAX_RADIUS_FIELD_NUMBER: _ClassVar[int]
+    ROUTABLE_FIELD_NUMBER: _ClassVar[int]
+    k: int
+    lat: float
+    lon: float
+    max_radius: float
+    routable: bool
+    def __init__(self, lat: _Optional[float] = ..., lon: _Optional[float] = ..., k: _Optional[int] = ..., max_radius: _Optional[float] = ..., routable: _Optional[bool] = ...) -> None: ...
+
+class NearestNodesResults(_message.Message):
+    __slots__ = ["nodes"]
+    NODES_FIELD_NUMBER: _ClassVar[int]
+    nodes: _containers.RepeatedCompositeFieldContainer[NearNode]
+    def __init__(self, nodes: _Optional[_Iterable[_Union[NearNode, _Mapping]]] = ...) -> None: ...
+
 class Node(_message.Message):
     __slots__ = ["node_id", "worker_id"]
     NODE_ID_FIELD_NUMBER: _ClassVar[int]
@@ -31,13 +153,39 @@ class NodeCoordinates(_message.Message):
     worker_id: int
     def __init__(self, node_id: _Optional[int] = ..., worker_id: _Optional[int] = ..., query_id: _Optional[int] = ...) -> None: ...
 
+class OneToManyQueryData(_message.Message):
+    __slots__ = ["metric", "node_id_from", "node_ids_to"]
+    METRIC_FIELD_NUMBER: _ClassVar[int]
+    NODE_ID_FROM_FIELD_NUMBER: _ClassVar[int]
+    NODE_IDS_TO_FIELD_NUMBER: _ClassVar[int]
+    metric: Metric
+    node_id_from: int
+    node_ids_to: _containers.RepeatedScalarFieldContainer[int]
+    def __init__(self, node_id_from: _Optional[int] = ..., node_ids_to: _Optional[_Iterable[int]] = ..., metric: _Optional[Metric] = ...) -> None: ...
+
+class OneToManyResults(_message.Message):
+    __slots__ = ["results"]
+    RESULTS_FIELD_NUMBER: _ClassVar[int]
+    results: _containers.RepeatedCompositeFieldContainer[TargetResult]
+    def __init__(self, results: _Optional[_Iterable[_Union[TargetResult, _Mapping]]] = ...) -> None: ...
+
 class QueryData(_message.Message):
-    __slots__ = ["node_id_from", "node_id_to"]
+    __slots__ = ["a_star", "bidirectional", "metric", "node_id_from", "node_id_to", "overlay", "parallel_window"]
+    A_STAR_FIELD_NUMBER: _ClassVar[int]
+    BIDIRECTIONAL_FIELD_NUMBER: _ClassVar[int]
+    METRIC_FIELD_NUMBER: _ClassVar[int]
     NODE_ID_FROM_FIELD_NUMBER: _ClassVar[int]
     NODE_ID_TO_FIELD_NUMBER: _ClassVar[int]
+    OVERLAY_FIELD_NUMBER: _ClassVar[int]
+    PARALLEL_WINDOW_FIELD_NUMBER: _ClassVar[int]
+    a_star: bool
+    bidirectional: bool
+    metric: Metric
     node_id_from: int
     node_id_to: int
-    def __init__(self, node_id_from: _Optional[int] = ..., node_id_to: _Optional[int] = ...) -> None: ...
+    overlay: bool
+    parallel_window: int
+    def __init__(self, node_id_from: _Optional[int] = ..., node_id_to: _Optional[int] = ..., a_star: _Optional[bool] = ..., bidirectional: _Optional[bool] = ..., metric: _Optional[Metric] = ..., overlay: _Optional[bool] = ..., parallel_window: _Optional[int] = ...) -> None: ...
 
 class QueryId(_message.Message):
     __slots__ = ["query_id"]
@@ -52,3 +200,50 @@ class QueryResults(_message.Message):
     query_id: int
     shortest_path_len: int
     def __init__(self, query_id: _Optional[int] = ..., shortest_path_len: _Optional[int] = ...) -> None: ...
+
+class ReachableNode(_message.Message):
+    __slots__ = ["lat", "lon", "node_id", "shortest_path_len", "worker_id"]
+    LAT_FIELD_NUMBER: _ClassVar[int]
+    LON_FIELD_NUMBER: _ClassVar[int]
+    NODE_ID_FIELD_NUMBER: _ClassVar[int]
+    SHORTEST_PATH_LEN_FIELD_NUMBER: _ClassVar[int]
+    WORKER_ID_FIELD_NUMBER: _ClassVar[int]
+    lat: float
+    lon: float
+    node_id: int
+    shortest_path_len: int
+    worker_id: int
+    def __init__(self, node_id: _Optional[int] = ..., worker_id: _Optional[int] = ..., shortest_path_len: _Optional[int] = ..., lat: _Optional[float] = ..., lon: _Optional[float] = ...) -> None: ...
+
+class ReachableQueryData(_message.Message):
+    __slots__ = ["budget", "hull", "metric", "node_id_from"]
+    BUDGET_FIELD_NUMBER: _ClassVar[int]
+    HULL_FIELD_NUMBER: _ClassVar[int]
+    METRIC_FIELD_NUMBER: _ClassVar[int]
+    NODE_ID_FROM_FIELD_NUMBER: _ClassVar[int]
+    budget: int
+    hull: bool
+    metric: Metric
+    node_id_from: int
+    def __init__(self, node_id_from: _Optional[int] = ..., budget: _Optional[int] = ..., metric: _Optional[Metric] = ..., hull: _Optional[bool] = ...) -> None: ...
+
+class ReachableResponse(_message.Message):
+    __slots__ = ["hull", "node"]
+    HULL_FIELD_NUMBER: _ClassVar[int]
+    NODE_FIELD_NUMBER: _ClassVar[int]
+    hull: Hull
+    node: ReachableNode
+    def __init__(self, node: _Optional[_Union[ReachableNode, _Mapping]] = ..., hull: _Optional[_Union[Hull, _Mapping]] = ...) -> None: ...
+
+class TargetResult(_message.Message):
+    __slots__ = ["node_id_to", "query_id", "shortest_path_len"]
+    NODE_ID_TO_FIELD_NUMBER: _ClassVar[int]
+    QUERY_ID_FIELD_NUMBER: _ClassVar[int]
+    SHORTEST_PATH_LEN_FIELD_NUMBER: _ClassVar[int]
+    node_id_to: int
+    query_id: int
+    shortest_path_len: int
+    def __init__(self, node_id_to: _Optional[int] = ..., query_id: _Optional[int] = ..., shortest_path_len: _Optional[int] = ...) -> None: ...
+
+class Metric(int, metaclass=_enum_type_wrapper.EnumTypeWrapper):
+    __slots__ = []
@@ -36,6 +36,36 @@ class ExecuterStub(object):
                 request_serializer=executer__pb2.Node.SerializeToString,
                 response_deserializer=executer__pb2.CoordinateResponse.FromString,
                 )
+        self.OneToManyQuery = channel.unary_unary(
+                '/executer.Executer/OneToManyQuery',
+                request_serializer=executer__pb2.OneToManyQueryData.SerializeToString,
+                response_deserializer=executer__pb2.OneToManyResults.FromString,
+                )
+        self.ReachableQuery = channel.unary_stream(
+                '/executer.Executer/ReachableQuery',
+                request_serializer=executer__pb2.ReachableQueryData.SerializeToString,
+                response_deserializer=executer__pb2.ReachableResponse.FromString,
+                )
+        self.NearestNodes = channel.unary_unary(
+                '/executer.Executer/NearestNodes',
+                request_serializer=executer__pb2.NearestNodesQuery.SerializeToString,
+                response_deserializer=executer__pb2.NearestNodesResults.FromString,
+                )
+        self.ShortestPathBetweenCoordinates = channel.unary_unary(
+                '/executer.Executer/ShortestPathBetweenCoordinates',
+                request_serializer=executer__pb2.CoordinatesQueryData.SerializeToString,
+                response_deserializer=executer__pb2.CoordinatesQueryResults.FromString,
+                )
+        self.KShortestPathsQuery = channel.unary_unary(
+                '/executer.Executer/KShortestPathsQuery',
+                request_serializer=executer__pb2.KShortestPathsQueryData.SerializeToString,
+                response_deserializer=executer__pb2.KShortestPathsResults.FromString,
+                )
+        self.UpdateEdgeWeights = channel.unary_unary(
+                '/executer.Executer/UpdateEdgeWeights',
+                request_serializer=executer__pb2.EdgeWeightUpdates.SerializeToString,
+                response_deserializer=executer__pb2.EdgeWeightUpdatesResult.FromString,
+                )
 
 
 class ExecuterServicer(object):
@@ -66,6 +96,50 @@ class ExecuterServicer(object):
         context.set_details('Method not implemented!')
         raise NotImplementedError('Method not implemented!')
 
+    def OneToManyQuery(self, request, context):
+        """Distances from one node to many. Each reached target gets its own query handle, usable with
+        `BacktrackPathForQuery` and `ForgetQuery`.
+        """
+        context.set_code(grpc.StatusCode.UNIMPLEMENTED)
+        context.set_details('Method not implemented!')
+        raise NotImplementedError('Method not implemented!')
+
+    def ReachableQuery(self, request, context):
+        """Nodes reachable from one node within the cost budget (an isochrone), optionally followed by
+        the outline of the reachable region.
+        """
+        context.set_code(grpc.StatusCode.UNIMPLEMENTED)
+        context.set_details('Method not implemented!')
+        raise NotImplementedError('Method not implemented!')
+
+    def NearestNodes(self, request, context):
+        """Nodes closest to the given coordinates, for the clients which know only the location."""
+        context.set_code(grpc.StatusCode.UNIMPLEMENTED)
+        context.set_details('Method not implemented!')
+        raise NotImplementedError('Method not implemented!')
+
+    def ShortestPathBetweenCoordinates(self, request, context):
+        """Like `ShortestPathQuery`, but the endpoints are given by coordinates and snapped to the
+        closest routable nodes.
+        """
+        context.set_code(grpc.StatusCode.UNIMPLEMENTED)
+        context.set_details('Method not implemented!')
+        raise NotImplementedError('Method not implemented!')
+
+    def KShortestPathsQuery(self, request, context):
+        """Up to `k` shortest loopless paths, each with its own query handle."""
+        context.set_code(grpc.StatusCode.UNIMPLEMENTED)
+        context.set_details('Method not implemented!')
+        raise NotImplementedError('Method not implemented!')
+
+    def UpdateEdgeWeights(self, request, context):
+        """Administrative: pushes traffic slowdowns and road closures to the workers owning the edges.
+        Queries started afterwards see the new weights.
+        """
+        context.set_code(grpc.StatusCode.UNIMPLEMENTED)
+        context.set_details('Method not implemented!')
+        raise NotImplementedError('Method not implemented!')
+
 
 def add_ExecuterServicer_to_server(servicer, server):
     rpc_method_handlers = {
@@ -89,6 +163,36 @@ def add_ExecuterServicer_to_server(servicer, server):
                     request_deserializer=executer__pb2.Node.FromString,
                     response_serializer=executer__pb2.CoordinateResponse.SerializeToString,
             ),
+            'OneToManyQuery': grpc.unary_unary_rpc_method_handler(
+                    servicer.OneToManyQuery,
+                    request_deserializer=executer__pb2.OneToManyQueryData.FromString,
+                    response_serializer=executer__pb2.OneToManyResults.SerializeToString,
+            ),
+            'ReachableQuery': grpc.unary_stream_rpc_method_handler(
+                    servicer.ReachableQuery,
+                    request_deserializer=executer__pb2.ReachableQueryData.FromString,
+                    response_serializer=executer__pb2.ReachableResponse.SerializeToString,
+            ),
+            'NearestNodes': grpc.unary_unary_rpc_method_handler(
+                    servicer.NearestNodes,
+                    request_deserializer=executer__pb2.NearestNodesQuery.FromString,
+                    response_serializer=executer__pb2.NearestNodesResults.SerializeToString,
+            ),
+            'ShortestPathBetweenCoordinates': grpc.unary_unary_rpc_method_handler(
+                    servicer.ShortestPathBetweenCoordinates,
+                    request_deserializer=executer__pb2.CoordinatesQueryData.FromString,
+                    response_serializer=executer__pb2.CoordinatesQueryResults.SerializeToString,
+            ),
+            'KShortestPathsQuery': grpc.unary_unary_rpc_method_handler(
+                    servicer.KShortestPathsQuery,
+                    request_deserializer=executer__pb2.KShortestPathsQueryData.FromString,
+                    response_serializer=executer__pb2.KShortestPathsResults.SerializeToString,
+            ),
+            'UpdateEdgeWeights': grpc.unary_unary_rpc_method_handler(
+                    servicer.UpdateEdgeWeights,
+                    request_deserializer=executer__pb2.EdgeWeightUpdates.FromString,
+                    response_serializer=executer__pb2.EdgeWeightUpdatesResult.SerializeToString,
+            ),
     }
     generic_handler = grpc.method_handlers_generic_handler(
             'executer.Executer', rpc_method_handlers)
@@ -167,3 +271,105 @@ class Executer(object):
             executer__pb2.CoordinateResponse.FromString,
             options, channel_credentials,
             insecure, call_credentials, compression, wait_for_ready, timeout, metadata)
+
+    @staticmethod
+    def OneToManyQuery(request,
+            target,
+            options=(),
+            channel_credentials=None,
+            call_credentials=None,
+            insecure=False,
+            compression=None,
+            wait_for_ready=None,
+            timeout=None,
+            metadata=None):
+        return grpc.experimental.unary_unary(request, target, '/executer.Executer/OneToManyQuery',
+            executer__pb2.OneToManyQueryData.SerializeToString,
+            executer__pb2.OneToManyResults.FromString,
+            options, channel_credentials,
+            insecure, call_credentials, compression, wait_for_ready, timeout, metadata)
+
+    @staticmethod
+    def ReachableQuery(request,
+            target,
+            options=(),
+            channel_credentials=None,
+            call_credentials=None,
+            insecure=False,
+            compression=None,
+            wait_for_ready=None,
+            timeout=None,
+            metadata=None):
+        return grpc.experimental.unary_stream(request, target, '/executer.Executer/ReachableQuery',
+            executer__pb2.ReachableQueryData.SerializeToString,
+            executer__pb2.ReachableResponse.FromString,
+            options, channel_credentials,
+            insecure, call_credentials, compression, wait_for_ready, timeout, metadata)
+
+    @staticmethod
+    def NearestNodes(request,
+            target,
+            options=(),
+            channel_credentials=None,
+            call_credentials=None,
+            insecure=False,
+            compression=None,
+            wait_for_ready=None,
+            timeout=None,
+            metadata=None):
+        return grpc.experimental.unary_unary(request, target, '/executer.Executer/NearestNodes',
+            executer__pb2.NearestNodesQuery.SerializeToString,
+            executer__pb2.NearestNodesResults.FromString,
+            options, channel_credentials,
+            insecure, call_credentials, compression, wait_for_ready, timeout, metadata)
+
+    @staticmethod
+    def ShortestPathBetweenCoordinates(request,
+            target,
+            options=(),
+            channel_credentials=None,
+            call_credentials=None,
+            insecure=False,
+            compression=None,
+            wait_for_ready=None,
+            timeout=None,
+            metadata=None):
+        return grpc.experimental.unary_unary(request, target, '/executer.Executer/ShortestPathBetweenCoordinates',
+            executer__pb2.CoordinatesQueryData.SerializeToString,
+            executer__pb2.CoordinatesQueryResults.FromString,
+            options, channel_credentials,
+            insecure, call_credentials, compression, wait_for_ready, timeout, metadata)
+
+    @staticmethod
+    def KShortestPathsQuery(request,
+            target,
+            options=(),
+            channel_credentials=None,
+            call_credentials=None,
+            insecure=False,
+            compression=None,
+            wait_for_ready=None,
+            timeout=None,
+            metadata=None):
+        return grpc.experimental.unary_unary(request, target, '/executer.Executer/KShortestPathsQuery',
+            executer__pb2.KShortestPathsQueryData.SerializeToString,
+            executer__pb2.KShortestPathsResults.FromString,
+            options, channel_credentials,
+            insecure, call_credentials, compression, wait_for_ready, timeout, metadata)
+
+    @staticmethod
+    def UpdateEdgeWeights(request,
+            target,
+            options=(),
+            channel_credentials=None,
+            call_credentials=None,
+            insecure=False,
+            compression=None,
+            wait_for_ready=None,
+            timeout=None,
+            metadata=None):
+        return grpc.experimental.unary_unary(request, target, '/executer.Executer/UpdateEdgeWeights',
+            executer__pb2.EdgeWeightUpdates.SerializeToString,
+            executer__pb2.EdgeWeightUpdatesResult.FromString,
+            options, channel_credentials,
+            insecure, call_credentials, compression, wait_for_ready, timeout, metadata)
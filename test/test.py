@@ -15,6 +15,10 @@ def main():
     parser.add_argument("--from-node", type=int, help="",required=True)
     parser.add_argument("--to-node", type=int, help="",required=True)
     parser.add_argument("--executer-addr", help="", required=True)
+    parser.add_argument("--a-star", action="store_true", help="Use goal-directed A* search")
+    parser.add_argument("--bidirectional", action="store_true", help="Search from both ends of the path")
+    parser.add_argument("--overlay", action="store_true", help="Search between workers on the overlay of their boundary nodes")
+    parser.add_argument("--metric", choices=["distance", "travel_time"], default="distance", help="Edge weights to minimise")
     args = parser.parse_args()
 
     with grpc.insecure_channel(args.executer_addr) as channel:
@@ -22,7 +26,7 @@ def main():
 
         print("Trying to get shortest path from {} to {} ...".format(args.from_node, args.to_node))
 
-        response = stub.ShortestPathQuery(executer_pb2.QueryData(node_id_from=args.from_node, node_id_to=args.to_node))
+        response = stub.ShortestPathQuery(executer_pb2.QueryData(node_id_from=args.from_node, node_id_to=args.to_node, a_star=args.a_star, bidirectional=args.bidirectional, overlay=args.overlay, metric=executer_pb2.Metric.Value(args.metric.upper())))
 
         if not response.HasField("shortest_path_len"):
             print("Path not found")
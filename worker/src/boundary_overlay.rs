@@ -0,0 +1,280 @@
+use std::mem;
+use std::sync::Arc;
+
+use tonic::Status;
+
+use generated::worker::{BoundaryOverlay, LocalPath, LocalPathRequest, OverlayEdge};
+use generated::worker::{LocalSearchRequest, LocalSearchResult, NodeDistance};
+
+use crate::globals::{self, Fragment};
+use crate::graph_store::{IdIdxMapper, Metric, NodeIdx, NodePointer, ShortestPathLen};
+use crate::graph_store::{SomeGraphMethods, METRICS_COUNT};
+use crate::query_processor::{Direction, QueueElement};
+use crate::search_pool::SearchBuffers;
+use crate::ErrorCollection;
+
+// Parent of the source of the local search
+const NO_PARENT: NodeIdx = NodeIdx::MAX;
+
+// Plain Dijkstra over the domestic edges of the fragment
+struct LocalSearch {
+    buffers: SearchBuffers,
+}
+
+impl LocalSearch {
+    // Stops once all reachable `targets` are settled; distances of other nodes may be too long.
+    fn run(
+        fragment: &Fragment,
+        source: NodeIdx,
+        metric: Metric,
+        direction: Direction,
+        targets: &[NodeIdx],
+    ) -> Self {
+        let mut buffers = globals::SEARCH_POOL.acquire(fragment.graph.ids.len());
+        let SearchBuffers { visits, queue } = &mut buffers;
+
+        let mut targets = targets.to_vec();
+        targets.sort_unstable();
+        targets.dedup();
+
+        let mut unsettled = targets.len();
+
+        visits.relax(source, NO_PARENT, 0);
+
+        if unsettled > 0 {
+            queue.push(QueueElement::new(source, 0, 0));
+        }
+
+        while let Some(node) = queue.pop() {
+            // The node was reached by a shorter path after this element was pushed
+            if visits.get(node.idx).unwrap().shortest < node.shortest {
+                continue;
+            }
+
+            if targets.binary_search(&node.idx).is_ok() {
+                unsettled -= 1;
+
+                if unsettled == 0 {
+                    break;
+                }
+            }
+
+            let edges = match direction {
+                Direction::Forward => fragment.edges(node.idx),
+                Direction::Backward => fragment.reverse_edges(node.idx),
+            };
+
+            for edge in edges {
+                if let NodePointer::Domestic(next) = edge.to {
+                    let shortest = node.shortest + edge.weight(metric);
+
+                    if visits.relax(next, node.idx, shortest) {
+                        queue.push(QueueElement::new(next, shortest, shortest));
+                    }
+                }
+            }
+        }
+
+        LocalSearch { buffers }
+    }
+
+    fn distance(&self, idx: NodeIdx) -> Option<ShortestPathLen> {
+        self.buffers.visits.get(idx).map(|visit| visit.shortest)
+    }
+
+    // From the source to `idx`, inclusive
+    fn path(&self, idx: NodeIdx) -> Option<Vec<NodeIdx>> {
+        let visits = &self.buffers.visits;
+        let mut path = vec![idx];
+        let mut parent = visits.get(idx)?.parent;
+
+        while parent != NO_PARENT {
+            path.push(parent);
+            parent = visits.get(parent).unwrap().parent;
+        }
+
+        path.reverse();
+
+        Some(path)
+    }
+}
+
+impl Drop for LocalSearch {
+    fn drop(&mut self) {
+        globals::SEARCH_POOL.release(mem::take(&mut self.buffers));
+    }
+}
+
+// Exit nodes for the forward direction, entry nodes for the backward one
+fn boundary_nodes(fragment: &Fragment, direction: Direction) -> Vec<NodeIdx> {
+    (0..fragment.graph.ids.len() as NodeIdx)
+        .filter(|idx| {
+            let mut edges = match direction {
+                Direction::Forward => fragment.edges(*idx),
+                Direction::Backward => fragment.reverse_edges(*idx),
+            };
+
+            edges.any(|edge| matches!(edge.to, NodePointer::Foreign(..)))
+        })
+        .collect()
+}
+
+fn compute_overlay(fragment: &Fragment, metric: Metric) -> BoundaryOverlay {
+    let graph = &fragment.graph;
+    let exits = boundary_nodes(fragment, Direction::Forward);
+    let entries = boundary_nodes(fragment, Direction::Backward);
+
+    let mut boundary_edges = Vec::new();
+
+    for &exit in exits.iter() {
+        for edge in fragment.edges(exit) {
+            if let NodePointer::Foreign(node_id, worker_id) = edge.to {
+                boundary_edges.push(OverlayEdge {
+                    node_from_id: graph.node_id(exit),
+                    node_to_id: node_id,
+                    weight: edge.weight(metric),
+                    worker_to_id: worker_id,
+                });
+            }
+        }
+    }
+
+    let mut clique_edges = Vec::new();
+
+    for &entry in entries.iter() {
+        let search = LocalSearch::run(fragment, entry, metric, Direction::Forward, &exits);
+
+        for &exit in exits.iter().filter(|exit| **exit != entry) {
+            if let Some(weight) = search.distance(exit) {
+                clique_edges.push(OverlayEdge {
+                    node_from_id: graph.node_id(entry),
+                    node_to_id: graph.node_id(exit),
+                    weight,
+                    worker_to_id: 0,
+                });
+            }
+        }
+    }
+
+    info!(
+        "computed {metric:?} overlay of fragment version {}: {} entries, {} exits, {} clique edges",
+        fragment.version,
+        entries.len(),
+        exits.len(),
+        clique_edges.len()
+    );
+
+    BoundaryOverlay {
+        graph_version: fragment.version,
+        clique_edges,
+        boundary_edges,
+    }
+}
+
+fn overlay(fragment: &Fragment, metric: Metric) -> BoundaryOverlay {
+    fragment.overlays[metric as usize]
+        .get_or_init(|| compute_overlay(fragment, metric))
+        .clone()
+}
+
+// Computes the overlays of the current fragment for all metrics, so that the first overlay query
+// does not wait for them.
+pub fn precompute_in_background() {
+    let fragment = globals::fragment();
+
+    tokio::task::spawn_blocking(move || {
+        for metric_idx in 0..METRICS_COUNT {
+            let metric = Metric::from_i32(metric_idx as i32).unwrap();
+            fragment.overlays[metric_idx].get_or_init(|| compute_overlay(&fragment, metric));
+        }
+    });
+}
+
+async fn run_blocking<T: Send + 'static>(
+    task: impl FnOnce(Arc<Fragment>) -> T + Send + 'static,
+) -> Result<T, Status> {
+    let fragment = globals::fragment();
+
+    tokio::task::spawn_blocking(move || task(fragment))
+        .await
+        .map_err(ErrorCollection::local_search_failed)
+}
+
+pub async fn get_overlay(metric: Metric) -> Result<BoundaryOverlay, Status> {
+    run_blocking(move |fragment| overlay(&fragment, metric)).await
+}
+
+pub async fn local_search(request: LocalSearchRequest) -> Result<LocalSearchResult, Status> {
+    run_blocking(move |fragment| {
+        let mapping = &fragment.mapping;
+        let source = mapping.get_mapping(request.node_id)?;
+
+        let direction = match request.backward {
+            true => Direction::Backward,
+            false => Direction::Forward,
+        };
+
+        let targets: Vec<NodeIdx> = boundary_nodes(&fragment, direction)
+            .into_iter()
+            .chain(request.targets.iter().filter_map(|id| mapping.find(*id)))
+            .collect();
+
+        let search = LocalSearch::run(&fragment, source, request.metric(), direction, &targets);
+
+        let distances = targets
+            .into_iter()
+            .filter_map(|idx| {
+                search.distance(idx).map(|distance| NodeDistance {
+                    node_id: fragment.graph.node_id(idx),
+                    distance,
+                })
+            })
+            .collect();
+
+        Ok(LocalSearchResult {
+            graph_version: fragment.version,
+            distances,
+        })
+    })
+    .await?
+}
+
+pub async fn local_path(request: LocalPathRequest) -> Result<LocalPath, Status> {
+    run_blocking(move |fragment| {
+        // The overlay path was found in another version of the fragment, which may have no such
+        // local path any more
+        if fragment.version != request.graph_version {
+            return Err(ErrorCollection::stale_local_path(
+                request.graph_version,
+                fragment.version,
+            ));
+        }
+
+        let from = fragment.mapping.get_mapping(request.node_from_id)?;
+        let to = fragment.mapping.get_mapping(request.node_to_id)?;
+
+        let search = LocalSearch::run(&fragment, from, request.metric(), Direction::Forward, &[to]);
+
+        let node_ids = search
+            .path(to)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|idx| fragment.graph.node_id(idx))
+            .collect();
+
+        Ok(LocalPath { node_ids })
+    })
+    .await?
+}
+
+impl ErrorCollection {
+    fn local_search_failed(error: tokio::task::JoinError) -> Status {
+        Status::internal(format!("local search task failed: {error}"))
+    }
+
+    fn stale_local_path(requested: u64, current: u64) -> Status {
+        Status::aborted(format!(
+            "local path requested in graph version {requested}, but the current one is {current}"
+        ))
+    }
+}
@@ -0,0 +1,538 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use tonic::Status;
+
+use crate::globals::{self, Fragment};
+use crate::graph_store::{Metric, NodeIdx, NodePointer, ShortestPathLen, METRICS_COUNT};
+use crate::ErrorCollection;
+
+// Rank of the uncontracted (boundary) nodes
+const CORE_RANK: u32 = u32::MAX;
+
+// `ChEdge::middle` of the original edges
+const NO_MIDDLE: NodeIdx = NodeIdx::MAX;
+
+// Witness searches stop after settling this many nodes. A shortcut added for lack of a witness is
+// still correct, it only makes the queries slower.
+const WITNESS_SEARCH_LIMIT: usize = 500;
+
+// Weight updates tend to come in streams; the hierarchies they invalidated are built again only
+// after no update came for this long.
+const REBUILD_DELAY: Duration = Duration::from_secs(5);
+
+// Time of the last weight update, while a rebuild is pending
+static PENDING_REBUILD: Mutex<Option<Instant>> = Mutex::new(None);
+
+#[derive(Debug, Clone, Copy)]
+pub struct ChEdge {
+    pub to: NodeIdx,
+    pub weight: ShortestPathLen,
+    // Node bypassed by the shortcut
+    middle: NodeIdx,
+}
+
+#[derive(Debug, Default)]
+struct EdgeLists {
+    offsets: Vec<usize>,
+    edges: Vec<ChEdge>,
+}
+
+impl EdgeLists {
+    fn new(lists: Vec<Vec<ChEdge>>) -> Self {
+        let mut offsets = Vec::with_capacity(lists.len() + 1);
+        offsets.push(0);
+
+        for list in lists.iter() {
+            offsets.push(offsets.last().unwrap() + list.len());
+        }
+
+        EdgeLists {
+            offsets,
+            edges: lists.into_iter().flatten().collect(),
+        }
+    }
+
+    fn get(&self, idx: NodeIdx) -> &[ChEdge] {
+        let idx = idx as usize;
+        &self.edges[self.offsets[idx]..self.offsets[idx + 1]]
+    }
+}
+
+// Contraction hierarchy of the domestic subgraph. Boundary nodes are never contracted and form the
+// core, in which the search runs as usual; everywhere else it goes only upwards.
+#[derive(Debug)]
+pub struct ContractionHierarchy {
+    rank: Vec<u32>,
+    // Edges to the nodes contracted later, and between the core nodes
+    up: EdgeLists,
+    // Reversed edges from the nodes contracted later; `to` is the source of the original edge
+    down: EdgeLists,
+}
+
+impl ContractionHierarchy {
+    pub fn is_core(&self, idx: NodeIdx) -> bool {
+        self.rank[idx as usize] == CORE_RANK
+    }
+
+    pub fn up(&self, idx: NodeIdx) -> &[ChEdge] {
+        self.up.get(idx)
+    }
+
+    pub fn down(&self, idx: NodeIdx) -> &[ChEdge] {
+        self.down.get(idx)
+    }
+
+    fn edge(&self, from: NodeIdx, to: NodeIdx) -> Result<&ChEdge, Status> {
+        let edge = match self.rank[from as usize] <= self.rank[to as usize] {
+            true => self.up(from).iter().find(|edge| edge.to == to),
+            false => self.down(to).iter().find(|edge| edge.to == from),
+        };
+
+        edge.ok_or_else(|| ErrorCollection::hierarchy_edge_not_found(from, to))
+    }
+
+    // Appends the nodes bypassed by the edge `from -> to`, in the path order.
+    pub fn unpack(
+        &self,
+        from: NodeIdx,
+        to: NodeIdx,
+        path: &mut Vec<NodeIdx>,
+    ) -> Result<(), Status> {
+        let middle = self.edge(from, to)?.middle;
+
+        if middle != NO_MIDDLE {
+            self.unpack(from, middle, path)?;
+            path.push(middle);
+            self.unpack(middle, to, path)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn build(fragment: &Fragment, metric: Metric) -> Self {
+        let nodes_count = fragment.graph.ids.len();
+        let mut contractor = Contractor {
+            outgoing: vec![Vec::new(); nodes_count],
+            incoming: vec![Vec::new(); nodes_count],
+        };
+
+        let mut rank = vec![CORE_RANK; nodes_count];
+        let mut queue = BinaryHeap::new();
+
+        for idx in 0..nodes_count as NodeIdx {
+            let mut boundary = fragment
+                .reverse_edges(idx)
+                .any(|edge| matches!(edge.to, NodePointer::Foreign(..)));
+
+            for edge in fragment.edges(idx) {
+                match edge.to {
+                    NodePointer::Foreign(..) => boundary = true,
+                    NodePointer::Domestic(to) if to != idx => {
+                        contractor.add_edge(idx, to, edge.weight(metric), NO_MIDDLE)
+                    }
+                    NodePointer::Domestic(_) => (), // Self loops are never on shortest paths
+                }
+            }
+
+            if !boundary {
+                rank[idx as usize] = 0;
+            }
+        }
+
+        for idx in 0..nodes_count as NodeIdx {
+            if rank[idx as usize] != CORE_RANK {
+                queue.push(Reverse((contractor.priority(idx), idx)));
+            }
+        }
+
+        let mut up = vec![Vec::new(); nodes_count];
+        let mut down = vec![Vec::new(); nodes_count];
+        let mut next_rank = 0;
+
+        while let Some(Reverse((_, idx))) = queue.pop() {
+            // Priorities change as the neighbours get contracted, so they are updated lazily
+            let priority = contractor.priority(idx);
+
+            if matches!(queue.peek(), Some(Reverse((next, _))) if *next < priority) {
+                queue.push(Reverse((priority, idx)));
+                continue;
+            }
+
+            let (outgoing, incoming) = contractor.contract(idx);
+            up[idx as usize] = outgoing;
+            down[idx as usize] = incoming;
+            rank[idx as usize] = next_rank;
+            next_rank += 1;
+        }
+
+        // What remains are the edges between the core nodes
+        for idx in 0..nodes_count {
+            if rank[idx] == CORE_RANK {
+                up[idx] = std::mem::take(&mut contractor.outgoing[idx]);
+            }
+        }
+
+        let hierarchy = ContractionHierarchy {
+            rank,
+            up: EdgeLists::new(up),
+            down: EdgeLists::new(down),
+        };
+
+        info!(
+            "built {metric:?} contraction hierarchy of fragment version {}: {} nodes contracted, \
+            {} edges",
+            fragment.version,
+            next_rank,
+            hierarchy.up.edges.len() + hierarchy.down.edges.len()
+        );
+
+        hierarchy
+    }
+}
+
+struct Contractor {
+    // Edges between the not yet contracted nodes
+    outgoing: Vec<Vec<ChEdge>>,
+    incoming: Vec<Vec<ChEdge>>, // `to` is the source of the edge
+}
+
+impl Contractor {
+    // Keeps the lighter of the parallel edges
+    fn add_edge(&mut self, from: NodeIdx, to: NodeIdx, weight: ShortestPathLen, middle: NodeIdx) {
+        let edge = ChEdge { to, weight, middle };
+        let reversed = ChEdge { to: from, ..edge };
+
+        match self.outgoing[from as usize].iter_mut().find(|e| e.to == to) {
+            Some(existing) if existing.weight <= weight => return,
+            Some(existing) => *existing = edge,
+            None => self.outgoing[from as usize].push(edge),
+        }
+
+        match self.incoming[to as usize].iter_mut().find(|e| e.to == from) {
+            Some(existing) => *existing = reversed,
+            None => self.incoming[to as usize].push(reversed),
+        }
+    }
+
+    // Edge difference: the number of shortcuts (at most) minus the number of removed edges
+    fn priority(&self, idx: NodeIdx) -> i64 {
+        let outgoing = self.outgoing[idx as usize].len() as i64;
+        let incoming = self.incoming[idx as usize].len() as i64;
+
+        outgoing * incoming - outgoing - incoming
+    }
+
+    // Distances from `source` not exceeding `limit`, over the nodes other than `avoided`
+    fn witness_search(
+        &self,
+        source: NodeIdx,
+        avoided: NodeIdx,
+        limit: ShortestPathLen,
+    ) -> HashMap<NodeIdx, ShortestPathLen> {
+        let mut distances = HashMap::from([(source, 0)]);
+        let mut queue = BinaryHeap::from([Reverse((0, source))]);
+        let mut settled = 0;
+
+        while let Some(Reverse((shortest, idx))) = queue.pop() {
+            if distances[&idx] < shortest {
+                continue;
+            }
+
+            settled += 1;
+
+            if settled > WITNESS_SEARCH_LIMIT {
+                break;
+            }
+
+            for edge in self.outgoing[idx as usize].iter() {
+                let new_shortest = shortest + edge.weight;
+
+                if edge.to == avoided || new_shortest > limit {
+                    continue;
+                }
+
+                if !matches!(distances.get(&edge.to), Some(d) if *d <= new_shortest) {
+                    distances.insert(edge.to, new_shortest);
+                    queue.push(Reverse((new_shortest, edge.to)));
+                }
+            }
+        }
+
+        distances
+    }
+
+    // Adds the shortcuts replacing the node and returns its edges
+    fn contract(&mut self, idx: NodeIdx) -> (Vec<ChEdge>, Vec<ChEdge>) {
+        let outgoing = std::mem::take(&mut self.outgoing[idx as usize]);
+        let incoming = std::mem::take(&mut self.incoming[idx as usize]);
+        let max_outgoing = outgoing.iter().map(|edge| edge.weight).max().unwrap_or(0);
+
+        for edge in outgoing.iter() {
+            self.incoming[edge.to as usize].retain(|e| e.to != idx);
+        }
+
+        for edge in incoming.iter() {
+            self.outgoing[edge.to as usize].retain(|e| e.to != idx);
+        }
+
+        for from in incoming.iter() {
+            let witnesses = self.witness_search(from.to, idx, from.weight + max_outgoing);
+
+            for to in outgoing.iter().filter(|to| to.to != from.to) {
+                let via = from.weight + to.weight;
+
+                if !matches!(witnesses.get(&to.to), Some(d) if *d <= via) {
+                    self.add_edge(from.to, to.to, via, idx);
+                }
+            }
+        }
+
+        (outgoing, incoming)
+    }
+}
+
+// Builds the hierarchies of the current fragment for all metrics. Queries started before they are
+// ready use the plain search.
+pub fn build_in_background() {
+    let fragment = globals::fragment();
+
+    tokio::task::spawn_blocking(move || {
+        for metric_idx in 0..METRICS_COUNT {
+            let metric = Metric::from_i32(metric_idx as i32).unwrap();
+            fragment.hierarchies[metric_idx]
+                .get_or_init(|| ContractionHierarchy::build(&fragment, metric));
+        }
+    });
+}
+
+// Builds the hierarchies invalidated by weight updates, once the updates calm down. Until then
+// the queries with the changed metrics use the plain search.
+pub fn rebuild_after_updates() {
+    if PENDING_REBUILD
+        .lock()
+        .unwrap()
+        .replace(Instant::now())
+        .is_some()
+    {
+        return;
+    }
+
+    info!(
+        "weight updates changed the graph, queries use the plain search until the contraction \
+        hierarchies are rebuilt"
+    );
+
+    tokio::spawn(async {
+        loop {
+            let wait = {
+                let mut pending = PENDING_REBUILD.lock().unwrap();
+                let quiet_since = pending.unwrap() + REBUILD_DELAY;
+                let wait = quiet_since.saturating_duration_since(Instant::now());
+
+                if wait.is_zero() {
+                    *pending = None;
+                }
+
+                wait
+            };
+
+            if wait.is_zero() {
+                break;
+            }
+
+            tokio::time::sleep(wait).await;
+        }
+
+        build_in_background();
+    });
+}
+
+impl ErrorCollection {
+    fn hierarchy_edge_not_found(from: NodeIdx, to: NodeIdx) -> Status {
+        Status::internal(format!(
+            "edge[idx: {from} -> idx: {to}] of the contraction hierarchy not found"
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph_store::{SPQGraphBuilder, TestRng};
+
+    // Grid of `side` x `side` nodes with pseudo-random weights, different in each direction. Nodes
+    // of the first row have edges to another worker, so that the hierarchy has a core.
+    fn grid_fragment(side: u64) -> Fragment {
+        let mut rng = TestRng::new();
+        let mut builder = SPQGraphBuilder::grid((0., 0.), side, 1., 0., &mut rng);
+        let mut weight = || {
+            let seed = rng.next();
+            [seed >> 59, (seed >> 54) & 0x1f]
+        };
+
+        for id in 0..side * side {
+            if id % side + 1 < side {
+                builder.add_edge(id, id + 1, None, weight());
+                builder.add_edge(id + 1, id, None, weight());
+            }
+
+            if id + side < side * side {
+                builder.add_edge(id, id + side, None, weight());
+                builder.add_edge(id + side, id, None, weight());
+            }
+
+            if id < side {
+                builder.add_edge(id, 1000 + id, Some(2), weight());
+            }
+        }
+
+        let (graph, mapping) = builder.build().unwrap();
+        Fragment::new(graph, mapping, 1)
+    }
+
+    // Distances and parents of the nodes reached from `source` by `edges`; nodes for which
+    // `expand` is false are reached, but not expanded.
+    fn search(
+        source: NodeIdx,
+        edges: impl Fn(NodeIdx) -> Vec<(NodeIdx, ShortestPathLen)>,
+        expand: impl Fn(NodeIdx) -> bool,
+    ) -> HashMap<NodeIdx, (ShortestPathLen, NodeIdx)> {
+        let mut visits = HashMap::from([(source, (0, source))]);
+        let mut queue = BinaryHeap::from([Reverse((0, source))]);
+
+        while let Some(Reverse((shortest, idx))) = queue.pop() {
+            if visits[&idx].0 < shortest || !expand(idx) {
+                continue;
+            }
+
+            for (to, weight) in edges(idx) {
+                let new_shortest = shortest + weight;
+
+                if !matches!(visits.get(&to), Some((d, _)) if *d <= new_shortest) {
+                    visits.insert(to, (new_shortest, idx));
+                    queue.push(Reverse((new_shortest, to)));
+                }
+            }
+        }
+
+        visits
+    }
+
+    // Path from the source of the forward search to `idx`, inclusive, unpacked to the original
+    // edges
+    fn unpacked_path(
+        hierarchy: &ContractionHierarchy,
+        visits: &HashMap<NodeIdx, (ShortestPathLen, NodeIdx)>,
+        mut idx: NodeIdx,
+    ) -> Vec<NodeIdx> {
+        let mut reversed = vec![idx];
+
+        while visits[&idx].1 != idx {
+            let parent = visits[&idx].1;
+            let mut bypassed = Vec::new();
+            hierarchy.unpack(parent, idx, &mut bypassed).unwrap();
+            reversed.extend(bypassed.into_iter().rev());
+            reversed.push(parent);
+            idx = parent;
+        }
+
+        reversed.reverse();
+        reversed
+    }
+
+    // Path from `idx` to the source of the backward search, inclusive, unpacked to the original
+    // edges
+    fn unpacked_backward_path(
+        hierarchy: &ContractionHierarchy,
+        visits: &HashMap<NodeIdx, (ShortestPathLen, NodeIdx)>,
+        mut idx: NodeIdx,
+    ) -> Vec<NodeIdx> {
+        let mut path = vec![idx];
+
+        while visits[&idx].1 != idx {
+            let next = visits[&idx].1;
+            hierarchy.unpack(idx, next, &mut path).unwrap();
+            path.push(next);
+            idx = next;
+        }
+
+        path
+    }
+
+    fn path_weight(fragment: &Fragment, metric: Metric, path: &[NodeIdx]) -> ShortestPathLen {
+        path.windows(2)
+            .map(|pair| {
+                fragment
+                    .edges(pair[0])
+                    .filter(|edge| matches!(edge.to, NodePointer::Domestic(to) if to == pair[1]))
+                    .map(|edge| edge.weight(metric))
+                    .min()
+                    .unwrap()
+            })
+            .sum()
+    }
+
+    #[test]
+    fn hierarchy_distances_match_dijkstra() {
+        let fragment = grid_fragment(7);
+        let nodes_count = fragment.graph.ids.len() as NodeIdx;
+
+        for metric in [Metric::Distance, Metric::TravelTime] {
+            let hierarchy = ContractionHierarchy::build(&fragment, metric);
+
+            let up = |idx| {
+                hierarchy
+                    .up(idx)
+                    .iter()
+                    .map(|edge| (edge.to, edge.weight))
+                    .collect()
+            };
+            let down = |idx| {
+                hierarchy
+                    .down(idx)
+                    .iter()
+                    .map(|edge| (edge.to, edge.weight))
+                    .collect()
+            };
+            let domestic = |idx| {
+                fragment
+                    .edges(idx)
+                    .filter_map(|edge| match edge.to {
+                        NodePointer::Domestic(to) => Some((to, edge.weight(metric))),
+                        NodePointer::Foreign(..) => None,
+                    })
+                    .collect()
+            };
+
+            for source in 0..nodes_count {
+                let expected = search(source, domestic, |_| true);
+                let forward = search(source, up, |_| true);
+
+                for target in 0..nodes_count {
+                    // The core is left to the forward search
+                    let backward = search(target, down, |idx| !hierarchy.is_core(idx));
+
+                    let meeting = forward
+                        .iter()
+                        .filter_map(|(idx, (to, _))| Some((to + backward.get(idx)?.0, *idx)))
+                        .min();
+
+                    let (distance, meeting) = meeting.unwrap();
+                    assert_eq!(distance, expected[&target].0, "{source} -> {target}");
+
+                    // Unpacked shortcuts make up a path of the same length
+                    let mut path = unpacked_path(&hierarchy, &forward, meeting);
+                    let to_target = unpacked_backward_path(&hierarchy, &backward, meeting);
+                    path.extend(to_target.into_iter().skip(1));
+
+                    assert_eq!(path.first(), Some(&source));
+                    assert_eq!(path.last(), Some(&target));
+                    assert_eq!(path_weight(&fragment, metric, &path), distance);
+                }
+            }
+        }
+    }
+}
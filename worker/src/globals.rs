@@ -1,21 +1,111 @@
+use std::sync::{Arc, RwLock};
+
 use once_cell::sync::OnceCell;
 
-use crate::graph_store::IdIdxMapping;
-use crate::graph_store::SPQGraph;
+use generated::worker::BoundaryOverlay;
+
+use crate::contraction::ContractionHierarchy;
+use crate::graph_store::{Edges, IdIdxMapping, NodeIdx, SPQGraph, SomeGraphMethods};
+use crate::graph_store::{WeightOverrides, METRICS_COUNT};
+use crate::search_pool::SearchPool;
+use crate::spatial_index::SpatialIndex;
 use crate::QueryProcessorHolder;
 
+// One version of the graph fragment. Queries keep the version they started with, so the old
+// version is dropped once its last query is forgotten. Versions made by weight updates share the
+// graph and the mapping.
+#[derive(Debug)]
+pub struct Fragment {
+    pub graph: Arc<SPQGraph>,
+    pub mapping: Arc<IdIdxMapping>,
+    pub overrides: WeightOverrides,
+    pub version: u64,
+    // Computed on demand, indexed by `Metric`
+    pub overlays: [OnceCell<BoundaryOverlay>; METRICS_COUNT],
+    // Built in the background if enabled. Shared with the versions made by weight updates which
+    // did not change the metric; the others are built again once the updates calm down.
+    pub hierarchies: [Arc<OnceCell<ContractionHierarchy>>; METRICS_COUNT],
+    // Shared by the versions with the same graph
+    pub spatial_index: Arc<OnceCell<SpatialIndex>>,
+}
+
+impl Fragment {
+    // Version without any weight overrides
+    pub fn new(graph: SPQGraph, mapping: IdIdxMapping, version: u64) -> Self {
+        Fragment {
+            graph: Arc::new(graph),
+            mapping: Arc::new(mapping),
+            overrides: WeightOverrides::default(),
+            version,
+            overlays: Default::default(),
+            hierarchies: Default::default(),
+            spatial_index: Default::default(),
+        }
+    }
+
+    // Outgoing edges with the current weights; closed edges are skipped.
+    pub fn edges(&self, node: NodeIdx) -> Edges<'_> {
+        self.graph
+            .edges(node)
+            .with_overrides(&self.overrides.forward)
+    }
+
+    pub fn reverse_edges(&self, node: NodeIdx) -> Edges<'_> {
+        self.graph
+            .reverse_edges(node)
+            .with_overrides(&self.overrides.reverse)
+    }
+}
+
 // TODO check simpler syntax
-pub static GRAPH: OnceCell<SPQGraph> = OnceCell::new();
-pub static MAPPING: OnceCell<IdIdxMapping> = OnceCell::new();
+pub static FRAGMENT: RwLock<Option<Arc<Fragment>>> = RwLock::new(None);
 pub static PROCESSOR_HOLDER: OnceCell<QueryProcessorHolder> = OnceCell::new();
+pub static SEARCH_POOL: SearchPool = SearchPool::new();
 
 // Not very pretty but I don't have better idea for that now, maybe macro?
-pub fn graph() -> &'static SPQGraph {
-    GRAPH.get().unwrap()
-}
-pub fn mapping() -> &'static IdIdxMapping {
-    MAPPING.get().unwrap()
+pub fn fragment() -> Arc<Fragment> {
+    FRAGMENT.read().unwrap().clone().unwrap()
 }
 pub fn processor_holder() -> &'static QueryProcessorHolder {
     PROCESSOR_HOLDER.get().unwrap()
 }
+
+// Replaces the current fragment (if any) and returns the version of the new one.
+pub fn publish_fragment(graph: SPQGraph, mapping: IdIdxMapping) -> u64 {
+    let mut current = FRAGMENT.write().unwrap();
+    let version = current.as_ref().map_or(1, |fragment| fragment.version + 1);
+
+    *current = Some(Arc::new(Fragment::new(graph, mapping, version)));
+
+    version
+}
+
+// Publishes a new version of the current fragment, with the weight overrides modified by `update`.
+// `update` also returns which metrics it changed (indexed by `Metric`).
+pub fn update_overrides<T>(
+    update: impl FnOnce(&Fragment, &mut WeightOverrides) -> (T, [bool; METRICS_COUNT]),
+) -> (u64, T) {
+    let mut current = FRAGMENT.write().unwrap();
+    let fragment = current.as_ref().unwrap();
+
+    let mut overrides = fragment.overrides.clone();
+    let (result, changed) = update(fragment, &mut overrides);
+    let version = fragment.version + 1;
+
+    let hierarchies = std::array::from_fn(|metric| match changed[metric] {
+        true => Default::default(),
+        false => fragment.hierarchies[metric].clone(),
+    });
+
+    *current = Some(Arc::new(Fragment {
+        graph: fragment.graph.clone(),
+        mapping: fragment.mapping.clone(),
+        overrides,
+        version,
+        overlays: Default::default(),
+        hierarchies,
+        spatial_index: fragment.spatial_index.clone(),
+    }));
+
+    (version, result)
+}
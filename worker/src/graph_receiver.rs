@@ -1,45 +1,220 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
 use tonic::transport::Channel;
 use tonic::{Request, Status};
 
 use generated::manager::manager_service_client::ManagerServiceClient;
-use generated::manager::{graph_piece, WorkerMetadata, WorkerProperties};
+use generated::manager::{graph_piece, Edge, WorkerMetadata, WorkerProperties};
 
 use crate::globals;
+use crate::graph_snapshot;
 use crate::graph_store;
+use crate::graph_validation;
+
+use graph_store::{EdgeWeights, IdIdxMapping, SPQGraph, SPQGraphBuilder};
+use graph_store::{Metric, WorkerId, METRICS_COUNT};
 
-use graph_store::{IdIdxMapper, IdIdxMapping, NodePointer, SPQGraph, SomeGraphMethods, WorkerId};
+#[derive(Debug, Clone)]
+pub struct SnapshotConfig {
+    pub path: PathBuf,
+    // Map the snapshot into memory instead of loading it onto the heap
+    pub mapped: bool,
+}
+
+// Interval between the attempts to register a worker started from its snapshot
+const REGISTER_RETRY_INTERVAL: Duration = Duration::from_secs(5);
 
 pub struct GraphReceiver {
     pub client: ManagerServiceClient<Channel>,
     pub worker_id: WorkerId,
+    // Unknown until the manager answers the registration of a worker started from its snapshot
+    pub workers_count: Option<u32>,
+    listening_address: String,
+    // Refuse the fragment if the validation finds any problem, instead of only logging them
+    pub strict_validation: bool,
+    pub snapshot: Option<SnapshotConfig>,
 }
 
 impl GraphReceiver {
+    // A restarted worker takes its ID from the snapshot and registers under it in the background,
+    // so it can start without the manager. Other workers wait for the manager to assign an ID.
     pub async fn new(
         mut client: ManagerServiceClient<Channel>,
         listening_address: String,
+        strict_validation: bool,
+        snapshot: Option<SnapshotConfig>,
     ) -> Result<Self, tonic::Status> {
+        let snapshot_worker_id = snapshot.as_ref().and_then(|snapshot| {
+            let path = snapshot.path.as_path();
+
+            match graph_snapshot::worker_id(path) {
+                Ok(worker_id) => Some(worker_id),
+                Err(e) => {
+                    debug!(
+                        "cannot read worker_id from snapshot '{}': {e}",
+                        path.display()
+                    );
+                    None
+                }
+            }
+        });
+
+        let (worker_id, workers_count) = match snapshot_worker_id {
+            Some(worker_id) => {
+                info!("starting as worker[id: {worker_id}] from the snapshot");
+
+                let client = client.clone();
+                let address = listening_address.clone();
+                tokio::spawn(Self::register_in_background(client, address, worker_id));
+
+                (worker_id, None)
+            }
+            None => {
+                let metadata = Self::register(&mut client, listening_address.clone(), None).await?;
+                (metadata.worker_id, Some(metadata.workers_count))
+            }
+        };
+
+        Ok(GraphReceiver {
+            client,
+            worker_id,
+            workers_count,
+            listening_address,
+            strict_validation,
+            snapshot,
+        })
+    }
+
+    async fn register(
+        client: &mut ManagerServiceClient<Channel>,
+        listening_address: String,
+        worker_id: Option<WorkerId>,
+    ) -> Result<WorkerMetadata, Status> {
         debug!("registering this worker in manager");
 
         let response = client
-            .register_worker(Request::new(WorkerProperties { listening_address }))
+            .register_worker(Request::new(WorkerProperties {
+                listening_address,
+                worker_id,
+            }))
             .await?;
-        let worker_id = response.get_ref().worker_id;
+        let metadata = response.into_inner();
 
-        debug!("worker_id has been assigned: {worker_id}");
+        debug!(
+            "worker_id has been assigned: {} (of {})",
+            metadata.worker_id, metadata.workers_count
+        );
 
-        Ok(GraphReceiver { client, worker_id })
+        Ok(metadata)
     }
 
+    // Executers learn about the worker from the manager, so it is asked until it answers
+    async fn register_in_background(
+        mut client: ManagerServiceClient<Channel>,
+        listening_address: String,
+        worker_id: WorkerId,
+    ) {
+        loop {
+            let address = listening_address.clone();
+
+            match Self::register(&mut client, address, Some(worker_id)).await {
+                Ok(_) => return,
+                Err(e) => warn!("cannot register in manager, retrying: {}", e.message()),
+            }
+
+            tokio::time::sleep(REGISTER_RETRY_INTERVAL).await;
+        }
+    }
+
+    // Loads (or maps) the graph from the snapshot if it is configured, present and valid.
+    // Otherwise requests the graph from the manager.
     pub async fn receive_graph(&mut self) -> Result<(), Status> {
+        if let Some(snapshot) = self.snapshot.as_ref() {
+            let open_snapshot = match snapshot.mapped {
+                true => graph_snapshot::map,
+                false => graph_snapshot::load,
+            };
+
+            let path = snapshot.path.as_path();
+
+            match open_snapshot(path, self.worker_id) {
+                Ok((graph, mapping)) => {
+                    info!("loaded graph from snapshot '{}'", path.display());
+                    Self::publish_graph(graph, mapping);
+                    return Ok(());
+                }
+                Err(e) => warn!("cannot load snapshot '{}': {e}", path.display()),
+            }
+        }
+
+        self.reload_graph().await?;
+
+        Ok(())
+    }
+
+    // Requests the graph from the manager, saves it to the snapshot (if configured) and swaps it
+    // in place of the current one. Returns the version of the new fragment.
+    pub async fn reload_graph(&mut self) -> Result<u64, Status> {
+        let (graph, mapping) = self.fetch_graph().await?;
+
+        let (graph, mapping) = match self.snapshot.as_ref() {
+            Some(snapshot) => Self::store_snapshot(snapshot, self.worker_id, graph, mapping),
+            None => (graph, mapping),
+        };
+
+        Ok(Self::publish_graph(graph, mapping))
+    }
+
+    // Returns the graph that should be published: the mapped snapshot or the received graph.
+    fn store_snapshot(
+        snapshot: &SnapshotConfig,
+        worker_id: WorkerId,
+        graph: SPQGraph,
+        mapping: IdIdxMapping,
+    ) -> (SPQGraph, IdIdxMapping) {
+        let path = snapshot.path.as_path();
+
+        match graph_snapshot::save(path, worker_id, &graph, &mapping) {
+            Ok(()) => info!("saved graph snapshot to '{}'", path.display()),
+            Err(e) => {
+                warn!("cannot save snapshot '{}': {e}", path.display());
+                return (graph, mapping);
+            }
+        }
+
+        // Drop the received graph in favour of the mapped one, which is shared with other
+        // processes using the same snapshot.
+        if snapshot.mapped {
+            match graph_snapshot::map(path, worker_id) {
+                Ok(mapped) => return mapped,
+                Err(e) => warn!("cannot map saved snapshot '{}': {e}", path.display()),
+            }
+        }
+
+        (graph, mapping)
+    }
+
+    async fn fetch_graph(&mut self) -> Result<(SPQGraph, IdIdxMapping), Status> {
+        // Needed by the validation
+        let workers_count = match self.workers_count {
+            Some(workers_count) => workers_count,
+            None => {
+                let address = self.listening_address.clone();
+                let metadata =
+                    Self::register(&mut self.client, address, Some(self.worker_id)).await?;
+                *self.workers_count.insert(metadata.workers_count)
+            }
+        };
+
         info!("requesting graph");
 
-        let mut graph = SPQGraph::new();
-        let mut mapping = IdIdxMapping::new();
+        let mut graph = SPQGraphBuilder::new();
         let mut stream = self
             .client
             .get_graph_fragment(Request::new(WorkerMetadata {
                 worker_id: self.worker_id,
+                ..Default::default()
             }))
             .await?
             .into_inner();
@@ -51,29 +226,42 @@ impl GraphReceiver {
                 Some(Nodes(node)) => {
                     let coords = (node.lat, node.lon);
                     let node_idx = graph.add_node(node.node_id, coords);
-                    mapping.insert(node.node_id, node_idx);
 
                     debug!(
                         "got node[id: {}, idx: {}, lat: {}, lon: {}]",
                         node.node_id, node_idx, node.lat, node.lon
                     )
                 }
+                Some(Edges(edge)) if edge.node_from_worker_id.is_some() => {
+                    // Edge from the node of some other worker; needed only by the backward search
+                    let worker_id = edge.node_from_worker_id.unwrap();
+                    let weights = Self::edge_weights(&edge)?;
+
+                    debug!(
+                        "got incoming edge[from_node_id: {}, from_worker_id: {}, to_node_id: {}, \
+                        weights: {:?}]",
+                        edge.node_from_id, worker_id, edge.node_to_id, weights
+                    );
+
+                    graph.add_incoming_edge(edge.node_from_id, worker_id, edge.node_to_id, weights);
+                }
                 Some(Edges(edge)) => {
-                    let node_from_idx = mapping.get_mapping(edge.node_from_id)?;
                     // If `worker_id` is present, then the edge points to foreign node that belongs
                     // to some other worker
-                    let pointer_to = match edge.node_to_worker_id {
-                        Some(worker_id) => NodePointer::Foreign(edge.node_to_id, worker_id),
-                        None => NodePointer::Domestic(mapping.get_mapping(edge.node_to_id)?),
-                    };
+                    let weights = Self::edge_weights(&edge)?;
 
                     debug!(
-                        "got edge[from_node_id: {}, to_node_id: {}, \
-                        weight: {}, from_idx: {}, pointer: {:?}]",
-                        edge.node_from_id, edge.node_to_id, edge.weight, node_from_idx, pointer_to
+                        "got edge[from_node_id: {}, to_node_id: {}, to_worker_id: {:?}, \
+                        weights: {:?}]",
+                        edge.node_from_id, edge.node_to_id, edge.node_to_worker_id, weights
                     );
 
-                    graph.add_edge(node_from_idx, pointer_to, edge.weight);
+                    graph.add_edge(
+                        edge.node_from_id,
+                        edge.node_to_id,
+                        edge.node_to_worker_id,
+                        weights,
+                    );
                 }
                 None => {
                     warn!("got empty GraphPiece with no node or edge!");
@@ -83,14 +271,71 @@ impl GraphReceiver {
 
         debug!("finished receiving graph");
 
-        globals::GRAPH
-            .set(graph)
-            .map_err(|_| Status::internal(format!("cannot set global graph")))?;
+        let (graph, mapping) = graph.build()?;
+        self.validate_graph(&graph, &mapping, workers_count)?;
 
-        globals::MAPPING
-            .set(mapping)
-            .map_err(|_| Status::internal(format!("cannot set global mapping")))?;
+        Ok((graph, mapping))
+    }
 
-        Ok(())
+    // Snapshots are not validated again, they are saved only after the validation.
+    fn validate_graph(
+        &self,
+        graph: &SPQGraph,
+        mapping: &IdIdxMapping,
+        workers_count: u32,
+    ) -> Result<(), Status> {
+        let diagnostics = graph_validation::validate(graph, mapping, self.worker_id, workers_count);
+
+        if diagnostics.is_empty() {
+            debug!("graph fragment passed validation");
+            return Ok(());
+        }
+
+        graph_validation::log_diagnostics(&diagnostics);
+
+        match self.strict_validation {
+            true => Err(Status::failed_precondition(format!(
+                "graph fragment failed validation with {} problems",
+                diagnostics.len()
+            ))),
+            false => Ok(()),
+        }
+    }
+
+    // Every metric must be given exactly once
+    fn edge_weights(edge: &Edge) -> Result<EdgeWeights, Status> {
+        let mut weights = [None; METRICS_COUNT];
+
+        for metric in edge.metrics.iter() {
+            let kind = Metric::from_i32(metric.metric).ok_or_else(|| {
+                Status::invalid_argument(format!("unknown metric {}", metric.metric))
+            })?;
+
+            if weights[kind as usize].replace(metric.value).is_some() {
+                return Err(Status::invalid_argument(format!(
+                    "duplicated metric {kind:?} of edge[from: {}, to: {}]",
+                    edge.node_from_id, edge.node_to_id
+                )));
+            }
+        }
+
+        let mut result = [0; METRICS_COUNT];
+
+        for (idx, weight) in weights.into_iter().enumerate() {
+            result[idx] = weight.ok_or_else(|| {
+                Status::invalid_argument(format!(
+                    "missing metric {idx} of edge[from: {}, to: {}]",
+                    edge.node_from_id, edge.node_to_id
+                ))
+            })?;
+        }
+
+        Ok(result)
+    }
+
+    fn publish_graph(graph: SPQGraph, mapping: IdIdxMapping) -> u64 {
+        let version = globals::publish_fragment(graph, mapping);
+        info!("published graph fragment version {version}");
+        version
     }
 }
@@ -0,0 +1,705 @@
+// Binary snapshot of the received graph fragment, so that restarted worker does not need to
+// stream the whole fragment from the manager again. The snapshot can be either loaded onto the
+// heap or memory-mapped and queried in place.
+//
+// Layout (all numbers little-endian, every section padded to 8 bytes):
+//
+//   magic: [u8; 8], version: u32, worker_id: u32, nodes_count: u64, edges_count: u64,
+//   reverse_edges_count: u64, foreign_count: u64, checksum: u64,
+//   ids: [u64; nodes_count], coords: [(f64, f64); nodes_count],
+//   offsets: [u32; nodes_count + 1], targets: [u32; edges_count],
+//   weights: [[u64; METRICS_COUNT]; edges_count],
+//   reverse_offsets: [u32; nodes_count + 1], reverse_targets: [u32; reverse_edges_count],
+//   reverse_weights: [[u64; METRICS_COUNT]; reverse_edges_count],
+//   foreign: [(u64, u32, u32 padding); foreign_count],
+//   index: [(u64 id, u32 idx, u32 padding); nodes_count] sorted by id
+//
+// Checksum is FNV-1a of everything that follows the header. The version changes together with
+// `METRICS_COUNT`, so snapshots with another set of metrics are rejected.
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::graph_store::{
+    EdgeWeights, ForeignTarget, IdIdxEntry, IdIdxMapping, SPQGraph, WorkerId, METRICS_COUNT,
+};
+use crate::storage::{MappedFile, Section};
+
+const MAGIC: &[u8; 8] = b"SPQSNAP\0";
+const VERSION: u32 = 4;
+const HEADER_LEN: u64 = 56;
+const CHECKSUM_OFFSET: u64 = HEADER_LEN - 8;
+
+// Numbers the temporary files of the saves made by this process
+static SAVE_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+struct Fnv1a(u64);
+
+impl Fnv1a {
+    fn new() -> Self {
+        Fnv1a(FNV_OFFSET_BASIS)
+    }
+
+    fn update(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 = (self.0 ^ *byte as u64).wrapping_mul(FNV_PRIME);
+        }
+    }
+}
+
+struct ChecksumWriter<W: Write> {
+    inner: W,
+    checksum: Fnv1a,
+    written: u64,
+}
+
+impl<W: Write> ChecksumWriter<W> {
+    fn put(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.checksum.update(bytes);
+        self.written += bytes.len() as u64;
+        self.inner.write_all(bytes)
+    }
+
+    fn pad(&mut self) -> io::Result<()> {
+        let padding = (8 - self.written % 8) % 8;
+        self.put(&[0; 8][..padding as usize])
+    }
+}
+
+struct ChecksumReader<R: Read> {
+    inner: R,
+    checksum: Fnv1a,
+    read: u64,
+}
+
+impl<R: Read> ChecksumReader<R> {
+    fn take<const N: usize>(&mut self) -> io::Result<[u8; N]> {
+        let mut bytes = [0; N];
+        self.inner.read_exact(&mut bytes)?;
+        self.checksum.update(&bytes);
+        self.read += N as u64;
+        Ok(bytes)
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        self.take().map(u32::from_le_bytes)
+    }
+
+    fn u64(&mut self) -> io::Result<u64> {
+        self.take().map(u64::from_le_bytes)
+    }
+
+    fn f64(&mut self) -> io::Result<f64> {
+        self.take().map(f64::from_le_bytes)
+    }
+
+    fn weights(&mut self) -> io::Result<EdgeWeights> {
+        let mut weights = [0; METRICS_COUNT];
+        for weight in weights.iter_mut() {
+            *weight = self.u64()?;
+        }
+        Ok(weights)
+    }
+
+    fn skip_padding(&mut self) -> io::Result<()> {
+        let padding = (8 - self.read % 8) % 8;
+
+        for _ in 0..padding {
+            self.take::<1>()?;
+        }
+
+        Ok(())
+    }
+}
+
+fn invalid_data(what: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, what.into())
+}
+
+// Size of `count` items of `item_size` bytes, padded to 8 bytes; `None` on overflow
+fn padded(count: usize, item_size: usize) -> Option<usize> {
+    let size = count.checked_mul(item_size)?.checked_add(7)?;
+    Some(size & !7)
+}
+
+struct Header {
+    nodes_count: usize,
+    edges_count: usize,
+    reverse_edges_count: usize,
+    foreign_count: usize,
+    checksum: u64,
+}
+
+impl Header {
+    // Returns the ID of the worker which made the snapshot. Fails if the header is not
+    // a snapshot header.
+    fn worker_id(header: &[u8]) -> io::Result<WorkerId> {
+        let field_u32 = |at: usize| u32::from_le_bytes(header[at..at + 4].try_into().unwrap());
+
+        if &header[0..8] != MAGIC {
+            return Err(invalid_data("not a graph snapshot"));
+        }
+
+        let version = field_u32(8);
+        if version != VERSION {
+            return Err(invalid_data(format!(
+                "unsupported snapshot version {version}, expected {VERSION}"
+            )));
+        }
+
+        Ok(field_u32(12))
+    }
+
+    // Fails if the header is not a snapshot header or was made by a worker with another
+    // `worker_id`.
+    fn parse(header: &[u8], worker_id: WorkerId) -> io::Result<Self> {
+        let field_u64 = |at: usize| u64::from_le_bytes(header[at..at + 8].try_into().unwrap());
+
+        let snapshot_worker_id = Self::worker_id(header)?;
+        if snapshot_worker_id != worker_id {
+            return Err(invalid_data(format!(
+                "snapshot belongs to worker[id: {snapshot_worker_id}], not worker[id: {worker_id}]"
+            )));
+        }
+
+        Ok(Header {
+            nodes_count: field_u64(16) as usize,
+            edges_count: field_u64(24) as usize,
+            reverse_edges_count: field_u64(32) as usize,
+            foreign_count: field_u64(40) as usize,
+            checksum: field_u64(CHECKSUM_OFFSET as usize),
+        })
+    }
+}
+
+// Fails unless the graph and its index can be queried without going out of bounds: see
+// `SPQGraph::check_layout`, and the index must be sorted by id and point to the nodes with
+// those ids.
+fn check_structure(graph: &SPQGraph, index: &[IdIdxEntry]) -> io::Result<()> {
+    graph.check_layout().map_err(invalid_data)?;
+
+    if index.windows(2).any(|pair| pair[0].id >= pair[1].id) {
+        return Err(invalid_data("snapshot index is not sorted"));
+    }
+
+    let misplaced = index
+        .iter()
+        .any(|entry| graph.ids.get(entry.idx as usize) != Some(&entry.id));
+
+    if misplaced {
+        return Err(invalid_data("snapshot index does not match the nodes"));
+    }
+
+    Ok(())
+}
+
+// Creates a new file next to `path`, named after this process and the number of the save. Names
+// taken by other saves (also of other processes sharing the directory) are skipped.
+fn create_temporary(path: &Path) -> io::Result<(PathBuf, File)> {
+    let mut file_name = path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(format!(".{}", std::process::id()));
+
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+
+    loop {
+        let save_number = SAVE_COUNTER.fetch_add(1, Ordering::Relaxed);
+        let mut tmp_name = file_name.clone();
+        tmp_name.push(format!(".{save_number}.tmp"));
+        let tmp_path = path.with_file_name(tmp_name);
+
+        match options.open(&tmp_path) {
+            Err(error) if error.kind() == io::ErrorKind::AlreadyExists => continue,
+            file => return Ok((tmp_path, file?)),
+        }
+    }
+}
+
+pub fn save(
+    path: &Path,
+    worker_id: WorkerId,
+    graph: &SPQGraph,
+    mapping: &IdIdxMapping,
+) -> io::Result<()> {
+    // Write to a temporary file of this save first, so that neither a crash in the middle nor
+    // another save running at the same time leaves a corrupted snapshot under `path`.
+    let (tmp_path, file) = create_temporary(path)?;
+
+    let written = write(file, worker_id, graph, mapping);
+
+    if let Err(error) = written.and_then(|()| std::fs::rename(&tmp_path, path)) {
+        // The snapshot under `path`, if any, is left intact
+        let _ = std::fs::remove_file(&tmp_path);
+        return Err(error);
+    }
+
+    Ok(())
+}
+
+// Writes the whole snapshot to `file` and syncs it to the disk.
+fn write(
+    file: File,
+    worker_id: WorkerId,
+    graph: &SPQGraph,
+    mapping: &IdIdxMapping,
+) -> io::Result<()> {
+    let mut file = BufWriter::new(file);
+
+    file.write_all(MAGIC)?;
+    file.write_all(&VERSION.to_le_bytes())?;
+    file.write_all(&worker_id.to_le_bytes())?;
+    file.write_all(&(graph.ids.len() as u64).to_le_bytes())?;
+    file.write_all(&(graph.targets.len() as u64).to_le_bytes())?;
+    file.write_all(&(graph.reverse_targets.len() as u64).to_le_bytes())?;
+    file.write_all(&(graph.foreign.len() as u64).to_le_bytes())?;
+    file.write_all(&0u64.to_le_bytes())?; // Checksum placeholder
+
+    let mut body = ChecksumWriter {
+        inner: &mut file,
+        checksum: Fnv1a::new(),
+        written: 0,
+    };
+
+    for id in graph.ids.iter() {
+        body.put(&id.to_le_bytes())?;
+    }
+    for [lat, lon] in graph.coords.iter() {
+        body.put(&lat.to_le_bytes())?;
+        body.put(&lon.to_le_bytes())?;
+    }
+    for offset in graph.offsets.iter() {
+        body.put(&offset.to_le_bytes())?;
+    }
+    body.pad()?;
+    for target in graph.targets.iter() {
+        body.put(&target.to_le_bytes())?;
+    }
+    body.pad()?;
+    for weight in graph.weights.iter().flatten() {
+        body.put(&weight.to_le_bytes())?;
+    }
+    for offset in graph.reverse_offsets.iter() {
+        body.put(&offset.to_le_bytes())?;
+    }
+    body.pad()?;
+    for target in graph.reverse_targets.iter() {
+        body.put(&target.to_le_bytes())?;
+    }
+    body.pad()?;
+    for weight in graph.reverse_weights.iter().flatten() {
+        body.put(&weight.to_le_bytes())?;
+    }
+    for foreign in graph.foreign.iter() {
+        body.put(&foreign.node_id.to_le_bytes())?;
+        body.put(&foreign.worker_id.to_le_bytes())?;
+        body.put(&0u32.to_le_bytes())?;
+    }
+
+    for entry in mapping.entries() {
+        body.put(&entry.id.to_le_bytes())?;
+        body.put(&entry.idx.to_le_bytes())?;
+        body.put(&0u32.to_le_bytes())?;
+    }
+
+    let checksum = body.checksum.0;
+
+    file.seek(SeekFrom::Start(CHECKSUM_OFFSET))?;
+    file.write_all(&checksum.to_le_bytes())?;
+    file.into_inner()?.sync_all()
+}
+
+// ID of the worker which made the snapshot, so that a restarted worker can start from its
+// snapshot before the manager is asked for anything.
+pub fn worker_id(path: &Path) -> io::Result<WorkerId> {
+    let mut header = [0; HEADER_LEN as usize];
+    File::open(path)?.read_exact(&mut header)?;
+
+    Header::worker_id(&header)
+}
+
+// Loads the snapshot onto the heap. Fails if the snapshot is corrupted or was made by a worker
+// with another `worker_id`.
+pub fn load(path: &Path, worker_id: WorkerId) -> io::Result<(SPQGraph, IdIdxMapping)> {
+    let mut file = BufReader::new(File::open(path)?);
+
+    let mut header = [0; HEADER_LEN as usize];
+    file.read_exact(&mut header)?;
+
+    let Header {
+        nodes_count,
+        edges_count,
+        reverse_edges_count,
+        foreign_count,
+        checksum,
+    } = Header::parse(&header, worker_id)?;
+
+    let mut body = ChecksumReader {
+        inner: file,
+        checksum: Fnv1a::new(),
+        read: 0,
+    };
+
+    let mut ids = Vec::new();
+    let mut coords = Vec::new();
+    let mut offsets = Vec::new();
+    let mut targets = Vec::new();
+    let mut weights = Vec::new();
+    let mut reverse_offsets = Vec::new();
+    let mut reverse_targets = Vec::new();
+    let mut reverse_weights = Vec::new();
+    let mut foreign = Vec::new();
+    let mut index = Vec::new();
+
+    for _ in 0..nodes_count {
+        ids.push(body.u64()?);
+    }
+    for _ in 0..nodes_count {
+        coords.push([body.f64()?, body.f64()?]);
+    }
+    for _ in 0..nodes_count + 1 {
+        offsets.push(body.u32()?);
+    }
+    body.skip_padding()?;
+    for _ in 0..edges_count {
+        targets.push(body.u32()?);
+    }
+    body.skip_padding()?;
+    for _ in 0..edges_count {
+        weights.push(body.weights()?);
+    }
+    for _ in 0..nodes_count + 1 {
+        reverse_offsets.push(body.u32()?);
+    }
+    body.skip_padding()?;
+    for _ in 0..reverse_edges_count {
+        reverse_targets.push(body.u32()?);
+    }
+    body.skip_padding()?;
+    for _ in 0..reverse_edges_count {
+        reverse_weights.push(body.weights()?);
+    }
+    for _ in 0..foreign_count {
+        let node_id = body.u64()?;
+        let worker_id = body.u32()?;
+        let padding = body.u32()?;
+
+        foreign.push(ForeignTarget {
+            node_id,
+            worker_id,
+            padding,
+        });
+    }
+    for _ in 0..nodes_count {
+        let id = body.u64()?;
+        let idx = body.u32()?;
+        let padding = body.u32()?;
+
+        index.push(IdIdxEntry { id, idx, padding });
+    }
+
+    if body.checksum.0 != checksum {
+        return Err(invalid_data("snapshot checksum mismatch"));
+    }
+
+    if body.inner.read(&mut [0])? != 0 {
+        return Err(invalid_data("trailing bytes after snapshot"));
+    }
+
+    let graph = SPQGraph {
+        ids: ids.into(),
+        coords: coords.into(),
+        offsets: offsets.into(),
+        targets: targets.into(),
+        weights: weights.into(),
+        reverse_offsets: reverse_offsets.into(),
+        reverse_targets: reverse_targets.into(),
+        reverse_weights: reverse_weights.into(),
+        foreign: foreign.into(),
+    };
+
+    check_structure(&graph, &index)?;
+
+    Ok((graph, IdIdxMapping::new(index.into())))
+}
+
+// Maps the snapshot into memory and queries it in place, so that processes on one host share the
+// page cache. The checksum is not verified, as it would require reading the whole file; the
+// structure is, so that a corrupted snapshot cannot make the lookups go out of bounds.
+pub fn map(path: &Path, worker_id: WorkerId) -> io::Result<(SPQGraph, IdIdxMapping)> {
+    if cfg!(target_endian = "big") {
+        return Err(invalid_data(
+            "snapshots cannot be mapped on big-endian hosts",
+        ));
+    }
+
+    let file = MappedFile::open(&File::open(path)?)?;
+
+    if file.len() < HEADER_LEN as usize {
+        return Err(invalid_data("snapshot is shorter than its header"));
+    }
+
+    let Header {
+        nodes_count,
+        edges_count,
+        reverse_edges_count,
+        foreign_count,
+        ..
+    } = Header::parse(&file.bytes()[..HEADER_LEN as usize], worker_id)?;
+
+    let mut offset = HEADER_LEN as usize;
+
+    let too_large = || invalid_data("snapshot header counts are too large");
+
+    // Counts come from the file, so the sizes are checked for overflow
+    let mut next = |count: usize, item_size: usize| {
+        let section_offset = offset;
+        offset = padded(count, item_size)
+            .and_then(|size| offset.checked_add(size))
+            .ok_or_else(too_large)?;
+        Ok::<_, io::Error>(section_offset)
+    };
+
+    let offsets_count = nodes_count.checked_add(1).ok_or_else(too_large)?;
+
+    let ids_offset = next(nodes_count, 8)?;
+    let coords_offset = next(nodes_count, 16)?;
+    let offsets_offset = next(offsets_count, 4)?;
+    let targets_offset = next(edges_count, 4)?;
+    let weights_offset = next(edges_count, 8 * METRICS_COUNT)?;
+    let reverse_offsets_offset = next(offsets_count, 4)?;
+    let reverse_targets_offset = next(reverse_edges_count, 4)?;
+    let reverse_weights_offset = next(reverse_edges_count, 8 * METRICS_COUNT)?;
+    let foreign_offset = next(foreign_count, 16)?;
+    let index_offset = next(nodes_count, 16)?;
+
+    if offset != file.len() {
+        return Err(invalid_data("snapshot size does not match its header"));
+    }
+
+    let graph = SPQGraph {
+        ids: Section::mapped(&file, ids_offset, nodes_count)?,
+        coords: Section::mapped(&file, coords_offset, nodes_count)?,
+        offsets: Section::mapped(&file, offsets_offset, offsets_count)?,
+        targets: Section::mapped(&file, targets_offset, edges_count)?,
+        weights: Section::mapped(&file, weights_offset, edges_count)?,
+        reverse_offsets: Section::mapped(&file, reverse_offsets_offset, offsets_count)?,
+        reverse_targets: Section::mapped(&file, reverse_targets_offset, reverse_edges_count)?,
+        reverse_weights: Section::mapped(&file, reverse_weights_offset, reverse_edges_count)?,
+        foreign: Section::mapped(&file, foreign_offset, foreign_count)?,
+    };
+
+    let index = Section::<IdIdxEntry>::mapped(&file, index_offset, nodes_count)?;
+    check_structure(&graph, &index)?;
+
+    Ok((graph, IdIdxMapping::new(index)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph_store::{IdIdxMapper, SPQGraphBuilder};
+
+    const WORKER_ID: WorkerId = 1;
+
+    fn sample_graph() -> (SPQGraph, IdIdxMapping) {
+        let mut builder = SPQGraphBuilder::new();
+
+        builder.add_node(30, (50.0, 19.0));
+        builder.add_node(10, (50.1, 19.1));
+        builder.add_node(20, (50.2, 19.2));
+        builder.add_edge(30, 10, None, [5, 50]);
+        builder.add_edge(10, 20, None, [7, 70]);
+        builder.add_edge(20, 99, Some(2), [9, 90]);
+        builder.add_incoming_edge(98, 2, 30, [3, 30]);
+
+        builder.build().unwrap()
+    }
+
+    fn snapshot_path(name: &str) -> PathBuf {
+        let file_name = format!("spq-snapshot-{}-{name}", std::process::id());
+        std::env::temp_dir().join(file_name)
+    }
+
+    fn assert_same_graph(expected: &(SPQGraph, IdIdxMapping), actual: &(SPQGraph, IdIdxMapping)) {
+        let ((expected, expected_mapping), (actual, actual_mapping)) = (expected, actual);
+        let foreign = |graph: &SPQGraph| -> Vec<_> {
+            graph
+                .foreign
+                .iter()
+                .map(|target| (target.node_id, target.worker_id))
+                .collect()
+        };
+        let entries = |mapping: &IdIdxMapping| -> Vec<_> {
+            mapping
+                .entries()
+                .iter()
+                .map(|entry| (entry.id, entry.idx))
+                .collect()
+        };
+
+        assert_eq!(&expected.ids[..], &actual.ids[..]);
+        assert_eq!(&expected.coords[..], &actual.coords[..]);
+        assert_eq!(&expected.offsets[..], &actual.offsets[..]);
+        assert_eq!(&expected.targets[..], &actual.targets[..]);
+        assert_eq!(&expected.weights[..], &actual.weights[..]);
+        assert_eq!(&expected.reverse_offsets[..], &actual.reverse_offsets[..]);
+        assert_eq!(&expected.reverse_targets[..], &actual.reverse_targets[..]);
+        assert_eq!(&expected.reverse_weights[..], &actual.reverse_weights[..]);
+        assert_eq!(foreign(expected), foreign(actual));
+        assert_eq!(entries(expected_mapping), entries(actual_mapping));
+    }
+
+    #[test]
+    fn load_returns_saved_graph() {
+        let path = snapshot_path("load");
+        let graph = sample_graph();
+
+        save(&path, WORKER_ID, &graph.0, &graph.1).unwrap();
+        let loaded = load(&path, WORKER_ID).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_same_graph(&graph, &loaded);
+        assert_eq!(loaded.1.find(20), Some(2));
+    }
+
+    #[test]
+    fn concurrent_saves_leave_whole_snapshot() {
+        let path = snapshot_path("concurrent");
+        let graph = sample_graph();
+
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| save(&path, WORKER_ID, &graph.0, &graph.1).unwrap());
+            }
+        });
+
+        let loaded = load(&path, WORKER_ID).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_same_graph(&graph, &loaded);
+
+        let prefix = path.file_name().unwrap().to_str().unwrap().to_owned() + ".";
+        let leftovers = std::fs::read_dir(path.parent().unwrap())
+            .unwrap()
+            .filter(|entry| {
+                let name = entry.as_ref().unwrap().file_name();
+                name.to_str().unwrap().starts_with(&prefix)
+            })
+            .count();
+
+        assert_eq!(leftovers, 0);
+    }
+
+    #[test]
+    fn load_rejects_corrupted_snapshot() {
+        let path = snapshot_path("corrupted");
+        let (graph, mapping) = sample_graph();
+
+        save(&path, WORKER_ID, &graph, &mapping).unwrap();
+        let mut bytes = std::fs::read(&path).unwrap();
+        bytes[HEADER_LEN as usize + 3] ^= 1;
+        std::fs::write(&path, bytes).unwrap();
+
+        let error = load(&path, WORKER_ID).unwrap_err();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(error.to_string().contains("checksum"));
+    }
+
+    #[test]
+    fn load_rejects_snapshot_of_another_worker() {
+        let path = snapshot_path("another-worker");
+        let (graph, mapping) = sample_graph();
+
+        save(&path, WORKER_ID, &graph, &mapping).unwrap();
+        let error = load(&path, WORKER_ID + 1).unwrap_err();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn worker_id_is_read_from_header() {
+        let path = snapshot_path("worker-id");
+        let (graph, mapping) = sample_graph();
+
+        save(&path, WORKER_ID, &graph, &mapping).unwrap();
+        let worker_id = worker_id(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(worker_id.unwrap(), WORKER_ID);
+    }
+
+    // Saves the sample graph and overwrites the bytes at `at`, counted from the end if negative
+    fn save_corrupted(name: &str, at: isize, bytes: &[u8]) -> PathBuf {
+        let path = snapshot_path(name);
+        let (graph, mapping) = sample_graph();
+
+        save(&path, WORKER_ID, &graph, &mapping).unwrap();
+        let mut snapshot = std::fs::read(&path).unwrap();
+        let at = match at < 0 {
+            true => snapshot.len() - at.unsigned_abs(),
+            false => at as usize,
+        };
+        snapshot[at..at + bytes.len()].copy_from_slice(bytes);
+        std::fs::write(&path, snapshot).unwrap();
+
+        path
+    }
+
+    fn map_error(path: &Path) -> io::Error {
+        let error = map(path, WORKER_ID).unwrap_err();
+        std::fs::remove_file(path).unwrap();
+        error
+    }
+
+    #[test]
+    fn map_returns_saved_graph() {
+        let path = snapshot_path("map");
+        let graph = sample_graph();
+
+        save(&path, WORKER_ID, &graph.0, &graph.1).unwrap();
+        let mapped = map(&path, WORKER_ID).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_same_graph(&graph, &mapped);
+        assert_eq!(mapped.1.find(30), Some(0));
+    }
+
+    #[test]
+    fn map_rejects_huge_counts() {
+        let path = save_corrupted("huge-counts", 16, &u64::MAX.to_le_bytes());
+
+        assert_eq!(map_error(&path).kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn map_rejects_unsorted_index() {
+        // The index is the last section, of three 16-byte entries
+        let path = save_corrupted("unsorted-index", -48, &u64::MAX.to_le_bytes());
+
+        assert!(map_error(&path).to_string().contains("not sorted"));
+    }
+
+    #[test]
+    fn map_rejects_target_out_of_bounds() {
+        // Targets follow the ids, coordinates and offsets of the three nodes
+        let targets_offset = HEADER_LEN as isize + 24 + 48 + 16;
+        let path = save_corrupted("target", targets_offset, &3u32.to_le_bytes());
+
+        assert!(map_error(&path).to_string().contains("out of the fragment"));
+    }
+
+    #[test]
+    fn map_rejects_decreasing_offsets() {
+        let offsets_offset = HEADER_LEN as isize + 24 + 48;
+        let path = save_corrupted("offsets", offsets_offset + 4, &3u32.to_le_bytes());
+
+        assert!(map_error(&path).to_string().contains("decreasing"));
+    }
+}
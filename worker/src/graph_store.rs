@@ -2,83 +2,641 @@ use std::collections::HashMap;
 
 use tonic::Status;
 
+use crate::storage::{Plain, Section};
+
+pub use generated::worker::Metric;
+
 pub type NodeId = u64;
 pub type NodeIdx = u32;
+pub type EdgeIdx = u32;
 pub type EdgeWeight = u64;
 pub type ShortestPathLen = u64;
 
 pub type WorkerId = u32;
 
-#[derive(Debug)]
+pub const METRICS_COUNT: usize = Metric::TravelTime as usize + 1;
+
+// Weight of the edge in each metric, indexed by `Metric`
+pub type EdgeWeights = [EdgeWeight; METRICS_COUNT];
+
+#[derive(Debug, Clone, Copy)]
 pub enum NodePointer {
     Domestic(NodeIdx),
     Foreign(NodeId, WorkerId),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub struct EdgePayload {
-    pub weight: EdgeWeight,
+    pub weights: EdgeWeights,
     pub to: NodePointer,
 }
 
-#[derive(Debug)]
-pub struct NodePayload {
+impl EdgePayload {
+    pub fn weight(&self, metric: Metric) -> EdgeWeight {
+        self.weights[metric as usize]
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct ForeignTarget {
+    pub node_id: NodeId,
+    pub worker_id: WorkerId,
+    pub padding: u32,
+}
+
+unsafe impl Plain for ForeignTarget {}
+
+// Edge targets with this bit set are indices into the `foreign` side table, otherwise they are
+// `NodeIdx` of the domestic node.
+const FOREIGN_TARGET: u32 = 1 << 31;
+
+// Graph fragment in the compressed sparse row layout. Outgoing edges of the node `idx` occupy
+// range `offsets[idx]..offsets[idx + 1]` of `targets` and `weights`. Incoming edges (used by the
+// backward search) are stored the same way in the `reverse_*` arrays; their targets are the
+// sources of the original edges.
+#[derive(Debug, Default)]
+pub struct SPQGraph {
+    pub ids: Section<NodeId>,
+    pub coords: Section<[f64; 2]>,
+    pub offsets: Section<EdgeIdx>,
+    pub targets: Section<u32>,
+    pub weights: Section<EdgeWeights>,
+    pub reverse_offsets: Section<EdgeIdx>,
+    pub reverse_targets: Section<u32>,
+    pub reverse_weights: Section<EdgeWeights>,
+    pub foreign: Section<ForeignTarget>,
+}
+
+impl SPQGraph {
+    // Checks the invariants which the lookups rely on: offsets are non-decreasing and end at the
+    // number of edges, targets are domestic nodes or entries of `foreign`. Runs in linear time;
+    // used for graphs which were not built by `SPQGraphBuilder`.
+    pub fn check_layout(&self) -> Result<(), String> {
+        let nodes_count = self.ids.len();
+
+        if self.coords.len() != nodes_count {
+            return Err("coordinates do not match the nodes".to_string());
+        }
+
+        let directions = [
+            ("edges", &self.offsets, &self.targets, &self.weights),
+            (
+                "reverse edges",
+                &self.reverse_offsets,
+                &self.reverse_targets,
+                &self.reverse_weights,
+            ),
+        ];
+
+        for (name, offsets, targets, weights) in directions {
+            if offsets.len() != nodes_count + 1 || weights.len() != targets.len() {
+                return Err(format!("{name} do not match the nodes"));
+            }
+
+            if offsets[0] != 0 || offsets[nodes_count] as usize != targets.len() {
+                return Err(format!("offsets of {name} do not span all of them"));
+            }
+
+            if offsets.windows(2).any(|pair| pair[0] > pair[1]) {
+                return Err(format!("offsets of {name} are decreasing"));
+            }
+
+            let out_of_bounds = targets.iter().any(|target| match target & FOREIGN_TARGET {
+                0 => *target as usize >= nodes_count,
+                _ => (target & !FOREIGN_TARGET) as usize >= self.foreign.len(),
+            });
+
+            if out_of_bounds {
+                return Err(format!("{name} point out of the fragment"));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct IdIdxEntry {
     pub id: NodeId,
-    pub coords: (f64, f64),
-    pub edges: Vec<EdgePayload>,
+    pub idx: NodeIdx,
+    pub padding: u32,
 }
 
-pub type SPQGraph = Vec<NodePayload>;
-pub type IdIdxMapping = HashMap<NodeId, NodeIdx>;
+unsafe impl Plain for IdIdxEntry {}
 
-pub trait IdIdxMapper {
-    fn get_mapping(&self, id: NodeId) -> Result<NodeIdx, Status>;
+// Id entries are split into buckets by the range of their ids; a lookup then searches only within
+// one bucket, which holds this many entries on average.
+const ENTRIES_PER_BUCKET: usize = 4;
+
+// Index from node ids to their indices. Entries are sorted by `id` and `buckets[b]` is the
+// position of the first entry with `(id - min_id) >> shift >= b`.
+#[derive(Debug, Default)]
+pub struct IdIdxMapping {
+    entries: Section<IdIdxEntry>,
+    buckets: Vec<u32>,
+    min_id: NodeId,
+    shift: u32,
 }
 
-impl IdIdxMapper for IdIdxMapping {
+impl IdIdxMapping {
+    // `entries` must be sorted by `id`. Buckets are computed by a single pass over the entries.
+    pub fn new(entries: Section<IdIdxEntry>) -> Self {
+        let min_id = entries.first().map_or(0, |entry| entry.id);
+        let range = entries.last().map_or(0, |entry| entry.id - min_id);
+
+        let target_count = (entries.len() / ENTRIES_PER_BUCKET).next_power_of_two();
+        let range_bits = u64::BITS - range.leading_zeros();
+        // Shifting by the whole width overflows; with few entries over a huge range one bucket
+        // holds the lower and one the upper half of the range.
+        let shift = range_bits
+            .saturating_sub(target_count.trailing_zeros())
+            .min(u64::BITS - 1);
+        let buckets_count = (range >> shift) as usize + 1;
+
+        let mut buckets = Vec::with_capacity(buckets_count + 1);
+
+        for (position, entry) in entries.iter().enumerate() {
+            let bucket = ((entry.id - min_id) >> shift) as usize;
+
+            while buckets.len() <= bucket {
+                buckets.push(position as u32);
+            }
+        }
+
+        while buckets.len() <= buckets_count {
+            buckets.push(entries.len() as u32);
+        }
+
+        IdIdxMapping {
+            entries,
+            buckets,
+            min_id,
+            shift,
+        }
+    }
+
+    // Index of the nodes with the given ids, `ids[idx]` being the id of node `idx`
+    pub fn from_ids(ids: &[NodeId]) -> Self {
+        let mut entries: Vec<_> = ids
+            .iter()
+            .enumerate()
+            .map(|(idx, id)| IdIdxEntry {
+                id: *id,
+                idx: idx as NodeIdx,
+                padding: 0,
+            })
+            .collect();
+
+        entries.sort_unstable_by_key(|entry| entry.id);
+
+        Self::new(entries.into())
+    }
+
+    pub fn entries(&self) -> &[IdIdxEntry] {
+        &self.entries
+    }
+}
+
+pub trait IdIdxMapper {
+    fn find(&self, id: NodeId) -> Option<NodeIdx>;
+
     fn get_mapping(&self, id: NodeId) -> Result<NodeIdx, Status> {
-        self.get(&id)
-            .copied()
+        self.find(id)
             .ok_or_else(|| Status::invalid_argument(format!("Cannot find node[id: {id}]")))
     }
 }
 
+impl IdIdxMapper for IdIdxMapping {
+    fn find(&self, id: NodeId) -> Option<NodeIdx> {
+        let bucket = (id.checked_sub(self.min_id)? >> self.shift) as usize;
+        let begin = *self.buckets.get(bucket)? as usize;
+        let end = *self.buckets.get(bucket + 1)? as usize;
+        let entries = &self.entries[begin..end];
+
+        entries
+            .binary_search_by_key(&id, |entry| entry.id)
+            .ok()
+            .map(|position| entries[position].idx)
+    }
+}
+
+// Weights changed at runtime, keyed by the position of the edge in `targets` (or in
+// `reverse_targets`).
+pub type OverrideMap = HashMap<EdgeIdx, EdgeOverride>;
+
+// A closed edge keeps its weights, so that they apply again once the edge is reopened
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EdgeOverride {
+    pub weights: EdgeWeights,
+    pub closed: bool,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct WeightOverrides {
+    pub forward: OverrideMap,
+    pub reverse: OverrideMap,
+}
+
 pub trait SomeGraphMethods {
-    fn get_node(&self, node: NodeIdx) -> &NodePayload;
-    fn get_node_mut(&mut self, node: NodeIdx) -> &mut NodePayload;
-    fn edges(&self, node: NodeIdx) -> std::slice::Iter<'_, EdgePayload>;
-    fn add_node(&mut self, node_id: NodeId, coords: (f64, f64)) -> NodeIdx;
-    fn add_edge(&mut self, from: NodeIdx, to: NodePointer, weight: EdgeWeight);
+    fn node_id(&self, node: NodeIdx) -> NodeId;
+    fn coords(&self, node: NodeIdx) -> (f64, f64);
+    fn edges(&self, node: NodeIdx) -> Edges<'_>;
+    fn reverse_edges(&self, node: NodeIdx) -> Edges<'_>;
 }
 
 impl SomeGraphMethods for SPQGraph {
-    fn get_node(&self, node: NodeIdx) -> &NodePayload {
-        &self[node as usize]
+    fn node_id(&self, node: NodeIdx) -> NodeId {
+        self.ids[node as usize]
     }
 
-    fn get_node_mut(&mut self, node: NodeIdx) -> &mut NodePayload {
-        &mut self[node as usize]
+    fn coords(&self, node: NodeIdx) -> (f64, f64) {
+        let [lat, lon] = self.coords[node as usize];
+        (lat, lon)
     }
 
-    fn edges(&self, node: NodeIdx) -> std::slice::Iter<'_, EdgePayload> {
-        self.get_node(node).edges.iter()
+    fn edges(&self, node: NodeIdx) -> Edges<'_> {
+        let begin = self.offsets[node as usize] as usize;
+        let end = self.offsets[node as usize + 1] as usize;
+
+        Edges {
+            targets: &self.targets[begin..end],
+            weights: &self.weights[begin..end],
+            foreign: &self.foreign,
+            position: begin as EdgeIdx,
+            overrides: None,
+        }
     }
 
-    fn add_node(&mut self, node_id: NodeId, coords: (f64, f64)) -> NodeIdx {
-        let node_idx = self.len() as NodeIdx;
+    fn reverse_edges(&self, node: NodeIdx) -> Edges<'_> {
+        let begin = self.reverse_offsets[node as usize] as usize;
+        let end = self.reverse_offsets[node as usize + 1] as usize;
 
-        self.push(NodePayload {
-            id: node_id,
-            coords,
-            edges: Vec::new(),
-        });
+        Edges {
+            targets: &self.reverse_targets[begin..end],
+            weights: &self.reverse_weights[begin..end],
+            foreign: &self.foreign,
+            position: begin as EdgeIdx,
+            overrides: None,
+        }
+    }
+}
+
+pub struct Edges<'a> {
+    targets: &'a [u32],
+    weights: &'a [EdgeWeights],
+    foreign: &'a [ForeignTarget],
+    // Position of the next edge in the whole `targets` array
+    position: EdgeIdx,
+    overrides: Option<&'a OverrideMap>,
+}
+
+impl<'a> Edges<'a> {
+    // Applies the overrides from `WeightOverrides::forward` or `WeightOverrides::reverse`
+    // (matching the direction of the edges); closed edges are skipped.
+    pub fn with_overrides(self, overrides: &'a OverrideMap) -> Self {
+        Edges {
+            // Skip the lookups entirely while nothing is overridden
+            overrides: match overrides.is_empty() {
+                true => None,
+                false => Some(overrides),
+            },
+            ..self
+        }
+    }
+
+    // Yields the edges together with their positions in the whole `targets` array
+    pub fn with_positions(mut self) -> impl Iterator<Item = (EdgeIdx, EdgePayload)> + 'a {
+        std::iter::from_fn(move || self.next_with_position())
+    }
+
+    fn next_with_position(&mut self) -> Option<(EdgeIdx, EdgePayload)> {
+        loop {
+            let (target, targets) = self.targets.split_first()?;
+            let (weights, rest) = self.weights.split_first()?;
+
+            self.targets = targets;
+            self.weights = rest;
+
+            let position = self.position;
+            self.position += 1;
+
+            let weights = match self
+                .overrides
+                .and_then(|overrides| overrides.get(&position))
+            {
+                None => weights,
+                Some(overridden) if overridden.closed => continue,
+                Some(overridden) => &overridden.weights,
+            };
+
+            let to = if target & FOREIGN_TARGET != 0 {
+                let foreign = self.foreign[(target & !FOREIGN_TARGET) as usize];
+                NodePointer::Foreign(foreign.node_id, foreign.worker_id)
+            } else {
+                NodePointer::Domestic(*target)
+            };
+
+            let edge = EdgePayload {
+                weights: *weights,
+                to,
+            };
+
+            return Some((position, edge));
+        }
+    }
+}
+
+impl Iterator for Edges<'_> {
+    type Item = EdgePayload;
+
+    fn next(&mut self) -> Option<EdgePayload> {
+        self.next_with_position().map(|(_, edge)| edge)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.targets.len()))
+    }
+}
+
+// Edge as received from the manager, before its domestic endpoint ids are resolved to indices.
+// `foreign_worker_id` is the owner of the other endpoint, if it is not domestic.
+#[derive(Debug)]
+struct ReceivedEdge {
+    domestic: NodeId,
+    other: NodeId,
+    foreign_worker_id: Option<WorkerId>,
+    weights: EdgeWeights,
+}
+
+// Nodes and edges may arrive in any order, so they are collected here and packed into `SPQGraph`
+// (together with its `IdIdxMapping`) once the whole fragment is received.
+#[derive(Debug, Default)]
+pub struct SPQGraphBuilder {
+    ids: Vec<NodeId>,
+    coords: Vec<[f64; 2]>,
+    edges: Vec<ReceivedEdge>,
+    // Incoming edges from the nodes of other workers; reverse edges of domestic edges are derived
+    // from `edges`.
+    incoming_foreign_edges: Vec<ReceivedEdge>,
+}
+
+struct PackedEdges {
+    offsets: Vec<EdgeIdx>,
+    targets: Vec<u32>,
+    weights: Vec<EdgeWeights>,
+}
+
+impl SPQGraphBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_node(&mut self, node_id: NodeId, coords: (f64, f64)) -> NodeIdx {
+        let node_idx = self.ids.len() as NodeIdx;
+        debug_assert!(node_idx & FOREIGN_TARGET == 0);
+
+        self.ids.push(node_id);
+        self.coords.push([coords.0, coords.1]);
 
         node_idx
     }
 
-    fn add_edge(&mut self, from: NodeIdx, to: NodePointer, weight: EdgeWeight) {
-        self.get_node_mut(from)
+    // Adds the edge `from` -> `to`; `to_worker_id` is present if `to` belongs to another worker.
+    pub fn add_edge(
+        &mut self,
+        from: NodeId,
+        to: NodeId,
+        to_worker_id: Option<WorkerId>,
+        weights: EdgeWeights,
+    ) {
+        self.edges.push(ReceivedEdge {
+            domestic: from,
+            other: to,
+            foreign_worker_id: to_worker_id,
+            weights,
+        });
+    }
+
+    // Adds the edge `from` -> `to`, where `from` belongs to another worker. Such edges are only
+    // traversed by the backward search.
+    pub fn add_incoming_edge(
+        &mut self,
+        from: NodeId,
+        from_worker_id: WorkerId,
+        to: NodeId,
+        weights: EdgeWeights,
+    ) {
+        self.incoming_foreign_edges.push(ReceivedEdge {
+            domestic: to,
+            other: from,
+            foreign_worker_id: Some(from_worker_id),
+            weights,
+        });
+    }
+
+    // Returns the domestic endpoint and the payload pointing to the other endpoint
+    fn resolve(
+        mapping: &IdIdxMapping,
+        edge: &ReceivedEdge,
+    ) -> Result<(NodeIdx, EdgePayload), Status> {
+        let to = match edge.foreign_worker_id {
+            Some(worker_id) => NodePointer::Foreign(edge.other, worker_id),
+            None => NodePointer::Domestic(mapping.get_mapping(edge.other)?),
+        };
+
+        let payload = EdgePayload {
+            weights: edge.weights,
+            to,
+        };
+
+        Ok((mapping.get_mapping(edge.domestic)?, payload))
+    }
+
+    // Counting sort of edges by their source node; keeps the order in which the edges of a single
+    // node were received.
+    fn pack(
+        nodes_count: usize,
+        edges: Vec<(NodeIdx, EdgePayload)>,
+        foreign: &mut Vec<ForeignTarget>,
+    ) -> PackedEdges {
+        let mut offsets = vec![0 as EdgeIdx; nodes_count + 1];
+
+        for (from, _) in edges.iter() {
+            offsets[*from as usize + 1] += 1;
+        }
+
+        for idx in 0..nodes_count {
+            offsets[idx + 1] += offsets[idx];
+        }
+
+        let mut next_free = offsets.clone();
+        let mut targets = vec![0; edges.len()];
+        let mut weights = vec![[0; METRICS_COUNT]; edges.len()];
+
+        for (from, edge) in edges.into_iter() {
+            let slot = &mut next_free[from as usize];
+            let position = *slot as usize;
+            *slot += 1;
+
+            targets[position] = match edge.to {
+                NodePointer::Domestic(idx) => idx,
+                NodePointer::Foreign(node_id, worker_id) => {
+                    foreign.push(ForeignTarget {
+                        node_id,
+                        worker_id,
+                        padding: 0,
+                    });
+                    (foreign.len() - 1) as u32 | FOREIGN_TARGET
+                }
+            };
+            weights[position] = edge.weights;
+        }
+
+        PackedEdges {
+            offsets,
+            targets,
+            weights,
+        }
+    }
+
+    // Fails if some edge refers to a domestic node which was not received.
+    pub fn build(self) -> Result<(SPQGraph, IdIdxMapping), Status> {
+        let nodes_count = self.ids.len();
+        let mapping = IdIdxMapping::from_ids(&self.ids);
+        let mut foreign = Vec::new();
+
+        let edges = self
             .edges
-            .push(EdgePayload { weight, to });
+            .iter()
+            .map(|edge| Self::resolve(&mapping, edge))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut reverse_edges = self
+            .incoming_foreign_edges
+            .iter()
+            .map(|edge| Self::resolve(&mapping, edge))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        for (from, edge) in edges.iter() {
+            if let NodePointer::Domestic(to) = edge.to {
+                let reverse_to = NodePointer::Domestic(*from);
+                reverse_edges.push((
+                    to,
+                    EdgePayload {
+                        to: reverse_to,
+                        ..*edge
+                    },
+                ));
+            }
+        }
+
+        let forward = Self::pack(nodes_count, edges, &mut foreign);
+        let reverse = Self::pack(nodes_count, reverse_edges, &mut foreign);
+
+        let graph = SPQGraph {
+            ids: self.ids.into(),
+            coords: self.coords.into(),
+            offsets: forward.offsets.into(),
+            targets: forward.targets.into(),
+            weights: forward.weights.into(),
+            reverse_offsets: reverse.offsets.into(),
+            reverse_targets: reverse.targets.into(),
+            reverse_weights: reverse.weights.into(),
+            foreign: foreign.into(),
+        };
+
+        Ok((graph, mapping))
+    }
+}
+
+// Pseudo-random numbers for the test graphs, the same in every run
+#[cfg(test)]
+pub struct TestRng(u64);
+
+#[cfg(test)]
+impl TestRng {
+    pub fn new() -> Self {
+        TestRng(12345)
+    }
+
+    pub fn next(&mut self) -> u64 {
+        self.0 = self
+            .0
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        self.0
+    }
+
+    // Uniform in [0, 1)
+    pub fn unit(&mut self) -> f64 {
+        (self.next() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+#[cfg(test)]
+impl SPQGraphBuilder {
+    // Nodes of a `side` x `side` grid starting at `origin`, `spacing` degrees apart and numbered
+    // row by row from 0. Both coordinates of each node are moved by up to `jitter` degrees.
+    pub fn grid(
+        origin: (f64, f64),
+        side: u64,
+        spacing: f64,
+        jitter: f64,
+        rng: &mut TestRng,
+    ) -> Self {
+        let mut builder = SPQGraphBuilder::new();
+
+        for id in 0..side * side {
+            let lat = origin.0 + (id / side) as f64 * spacing + rng.unit() * jitter;
+            let lon = origin.1 + (id % side) as f64 * spacing + rng.unit() * jitter;
+            builder.add_node(id, (lat, lon));
+        }
+
+        builder
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mapping_finds_all_ids() {
+        let ids: Vec<NodeId> = (0..1000).map(|i| i * i * 7 + 3).rev().collect();
+        let mapping = IdIdxMapping::from_ids(&ids);
+
+        for (idx, id) in ids.iter().enumerate() {
+            assert_eq!(mapping.find(*id), Some(idx as NodeIdx));
+        }
+    }
+
+    #[test]
+    fn mapping_misses_absent_ids() {
+        let mapping = IdIdxMapping::from_ids(&[10, 20, 30, 1_000_000]);
+
+        for id in [0, 9, 11, 25, 31, 999_999, 1_000_001, NodeId::MAX] {
+            assert_eq!(mapping.find(id), None);
+        }
+    }
+
+    #[test]
+    fn mapping_handles_whole_id_range() {
+        let ids = [NodeId::MAX, 0, 1 << 63];
+        let mapping = IdIdxMapping::from_ids(&ids);
+
+        for (idx, id) in ids.iter().enumerate() {
+            assert_eq!(mapping.find(*id), Some(idx as NodeIdx));
+        }
+        assert_eq!(mapping.find(1), None);
+    }
+
+    #[test]
+    fn empty_mapping_finds_nothing() {
+        let mapping = IdIdxMapping::from_ids(&[]);
+
+        assert_eq!(mapping.find(0), None);
+        assert_eq!(mapping.find(NodeId::MAX), None);
     }
 }
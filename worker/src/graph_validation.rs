@@ -0,0 +1,366 @@
+use std::fmt;
+
+use crate::graph_store::{EdgePayload, EdgeWeight, IdIdxMapping, Metric, NodeId, NodeIdx};
+use crate::graph_store::{NodePointer, SPQGraph, SomeGraphMethods, WorkerId, METRICS_COUNT};
+
+// Path lengths are sums of edge weights, so a single weight above this limit makes the sums prone
+// to overflow. Real edges are many orders of magnitude lighter in every metric.
+const MAX_EDGE_WEIGHT: EdgeWeight = 1 << 40;
+
+// Number of diagnostics logged one by one; the rest is only counted.
+const LOGGED_DIAGNOSTICS: usize = 20;
+
+#[derive(Debug)]
+pub enum Diagnostic {
+    DuplicatedNode(NodeId),
+    NanCoordinates(NodeId),
+    SelfLoop(NodeId),
+    // Boundary edge whose foreign end is claimed to belong to this worker
+    ForeignPointsToSelf {
+        from: NodeId,
+        to: NodeId,
+    },
+    UnknownWorker {
+        from: NodeId,
+        to: NodeId,
+        worker_id: WorkerId,
+    },
+    ZeroWeight {
+        from: NodeId,
+        to: NodeId,
+        metric: Metric,
+    },
+    HugeWeight {
+        from: NodeId,
+        to: NodeId,
+        metric: Metric,
+        weight: EdgeWeight,
+    },
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use Diagnostic::*;
+
+        match self {
+            DuplicatedNode(id) => write!(f, "node[id: {id}] received more than once"),
+            NanCoordinates(id) => write!(f, "node[id: {id}] has NaN coordinates"),
+            SelfLoop(id) => write!(f, "edge from node[id: {id}] to itself"),
+            ForeignPointsToSelf { from, to } => {
+                write!(f, "boundary edge[{from} -> {to}] points to this worker")
+            }
+            UnknownWorker {
+                from,
+                to,
+                worker_id,
+            } => write!(
+                f,
+                "edge[{from} -> {to}] points to unknown worker[id: {worker_id}]"
+            ),
+            ZeroWeight { from, to, metric } => {
+                write!(f, "edge[{from} -> {to}] has zero {metric:?} weight")
+            }
+            HugeWeight {
+                from,
+                to,
+                metric,
+                weight,
+            } => write!(
+                f,
+                "edge[{from} -> {to}] has {metric:?} weight {weight} above limit"
+            ),
+        }
+    }
+}
+
+struct Validator<'a> {
+    graph: &'a SPQGraph,
+    worker_id: WorkerId,
+    // Zero if unknown, then the worker IDs are not checked
+    workers_count: u32,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Validator<'_> {
+    fn check_nodes(&mut self, mapping: &IdIdxMapping) {
+        for pair in mapping.entries().windows(2) {
+            if pair[0].id == pair[1].id {
+                self.diagnostics
+                    .push(Diagnostic::DuplicatedNode(pair[0].id));
+            }
+        }
+
+        for (idx, [lat, lon]) in self.graph.coords.iter().enumerate() {
+            if lat.is_nan() || lon.is_nan() {
+                let id = self.graph.node_id(idx as NodeIdx);
+                self.diagnostics.push(Diagnostic::NanCoordinates(id));
+            }
+        }
+    }
+
+    // `domestic` is the node whose adjacency list contains the edge; `reversed` is set for edges
+    // from the reverse adjacency lists, which point from `edge.to` to `domestic`.
+    fn check_edge(&mut self, domestic: NodeIdx, edge: EdgePayload, reversed: bool) {
+        let domestic_id = self.graph.node_id(domestic);
+
+        let other_id = match edge.to {
+            NodePointer::Domestic(idx) => self.graph.node_id(idx),
+            NodePointer::Foreign(id, _) => id,
+        };
+
+        let (from, to) = match reversed {
+            false => (domestic_id, other_id),
+            true => (other_id, domestic_id),
+        };
+
+        match edge.to {
+            NodePointer::Domestic(idx) if idx == domestic => {
+                self.diagnostics.push(Diagnostic::SelfLoop(from));
+            }
+            NodePointer::Domestic(_) => (),
+            NodePointer::Foreign(_, worker_id) if worker_id == self.worker_id => {
+                self.diagnostics
+                    .push(Diagnostic::ForeignPointsToSelf { from, to });
+            }
+            NodePointer::Foreign(_, worker_id) => {
+                if self.workers_count != 0 && (worker_id == 0 || worker_id > self.workers_count) {
+                    self.diagnostics.push(Diagnostic::UnknownWorker {
+                        from,
+                        to,
+                        worker_id,
+                    });
+                }
+            }
+        }
+
+        for metric_idx in 0..METRICS_COUNT {
+            let metric = Metric::from_i32(metric_idx as i32).unwrap();
+            let weight = edge.weight(metric);
+
+            if weight == 0 {
+                self.diagnostics
+                    .push(Diagnostic::ZeroWeight { from, to, metric });
+            } else if weight > MAX_EDGE_WEIGHT {
+                self.diagnostics.push(Diagnostic::HugeWeight {
+                    from,
+                    to,
+                    metric,
+                    weight,
+                });
+            }
+        }
+    }
+
+    fn check_edges(&mut self) {
+        for idx in 0..self.graph.ids.len() as NodeIdx {
+            for edge in self.graph.edges(idx) {
+                self.check_edge(idx, edge, false);
+            }
+
+            // Other reverse edges mirror the outgoing ones, which were already checked
+            for edge in self.graph.reverse_edges(idx) {
+                if let NodePointer::Foreign(..) = edge.to {
+                    self.check_edge(idx, edge, true);
+                }
+            }
+        }
+    }
+}
+
+// Checks the received fragment for inconsistencies which the manager should never send.
+pub fn validate(
+    graph: &SPQGraph,
+    mapping: &IdIdxMapping,
+    worker_id: WorkerId,
+    workers_count: u32,
+) -> Vec<Diagnostic> {
+    let mut validator = Validator {
+        graph,
+        worker_id,
+        workers_count,
+        diagnostics: Vec::new(),
+    };
+
+    validator.check_nodes(mapping);
+    validator.check_edges();
+
+    validator.diagnostics
+}
+
+pub fn log_diagnostics(diagnostics: &[Diagnostic]) {
+    for diagnostic in diagnostics.iter().take(LOGGED_DIAGNOSTICS) {
+        warn!("invalid graph fragment: {diagnostic}");
+    }
+
+    if diagnostics.len() > LOGGED_DIAGNOSTICS {
+        warn!(
+            "invalid graph fragment: {} more problems omitted",
+            diagnostics.len() - LOGGED_DIAGNOSTICS
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph_store::SPQGraphBuilder;
+
+    const WORKER_ID: WorkerId = 2;
+    const WORKERS_COUNT: u32 = 3;
+
+    // Valid fragment: 10 -> 20 -> 30 inside, 30 -> 40 of worker 3 and 50 of worker 1 -> 10
+    fn sample_builder() -> SPQGraphBuilder {
+        let mut builder = SPQGraphBuilder::new();
+
+        builder.add_node(10, (50.0, 19.0));
+        builder.add_node(20, (50.1, 19.1));
+        builder.add_node(30, (50.2, 19.2));
+        builder.add_edge(10, 20, None, [5, 50]);
+        builder.add_edge(20, 30, None, [7, 70]);
+        builder.add_edge(30, 40, Some(3), [9, 90]);
+        builder.add_incoming_edge(50, 1, 10, [3, 30]);
+
+        builder
+    }
+
+    fn diagnostics(builder: SPQGraphBuilder) -> Vec<Diagnostic> {
+        let (graph, mapping) = builder.build().unwrap();
+        validate(&graph, &mapping, WORKER_ID, WORKERS_COUNT)
+    }
+
+    #[test]
+    fn valid_fragment_passes() {
+        assert!(diagnostics(sample_builder()).is_empty());
+    }
+
+    #[test]
+    fn dangling_edge_is_rejected_by_builder() {
+        let mut builder = sample_builder();
+        builder.add_edge(20, 60, None, [1, 10]);
+
+        assert!(builder.build().is_err());
+    }
+
+    #[test]
+    fn duplicated_node_is_reported() {
+        let mut builder = sample_builder();
+        builder.add_node(20, (50.3, 19.3));
+
+        let diagnostics = diagnostics(builder);
+        assert!(matches!(diagnostics[..], [Diagnostic::DuplicatedNode(20)]));
+    }
+
+    #[test]
+    fn nan_coordinates_are_reported() {
+        let mut builder = sample_builder();
+        builder.add_node(60, (f64::NAN, 19.3));
+
+        let diagnostics = diagnostics(builder);
+        assert!(matches!(diagnostics[..], [Diagnostic::NanCoordinates(60)]));
+    }
+
+    #[test]
+    fn self_loop_is_reported() {
+        let mut builder = sample_builder();
+        builder.add_edge(20, 20, None, [1, 10]);
+
+        // Both the edge and its reverse copy are domestic, only the edge is checked
+        let diagnostics = diagnostics(builder);
+        assert!(matches!(diagnostics[..], [Diagnostic::SelfLoop(20)]));
+    }
+
+    #[test]
+    fn foreign_node_of_this_worker_is_reported() {
+        let mut builder = sample_builder();
+        builder.add_edge(20, 60, Some(WORKER_ID), [1, 10]);
+
+        let diagnostics = diagnostics(builder);
+        assert!(matches!(
+            diagnostics[..],
+            [Diagnostic::ForeignPointsToSelf { from: 20, to: 60 }]
+        ));
+    }
+
+    #[test]
+    fn worker_out_of_range_is_reported() {
+        let mut builder = sample_builder();
+        builder.add_edge(20, 60, Some(WORKERS_COUNT + 1), [1, 10]);
+        builder.add_edge(30, 70, Some(0), [1, 10]);
+
+        let diagnostics = diagnostics(builder);
+        assert!(matches!(
+            diagnostics[..],
+            [
+                Diagnostic::UnknownWorker {
+                    from: 20,
+                    to: 60,
+                    worker_id: 4
+                },
+                Diagnostic::UnknownWorker {
+                    from: 30,
+                    to: 70,
+                    worker_id: 0
+                }
+            ]
+        ));
+    }
+
+    #[test]
+    fn incoming_edge_from_unknown_worker_is_reported() {
+        let mut builder = sample_builder();
+        builder.add_incoming_edge(60, WORKERS_COUNT + 1, 20, [1, 10]);
+
+        // The edge is reversed, so it is reported in its own direction
+        let diagnostics = diagnostics(builder);
+        assert!(matches!(
+            diagnostics[..],
+            [Diagnostic::UnknownWorker {
+                from: 60,
+                to: 20,
+                worker_id: 4
+            }]
+        ));
+    }
+
+    #[test]
+    fn workers_are_not_checked_if_count_is_unknown() {
+        let mut builder = sample_builder();
+        builder.add_edge(20, 60, Some(WORKERS_COUNT + 1), [1, 10]);
+        let (graph, mapping) = builder.build().unwrap();
+
+        assert!(validate(&graph, &mapping, WORKER_ID, 0).is_empty());
+    }
+
+    #[test]
+    fn zero_weight_is_reported() {
+        let mut builder = sample_builder();
+        builder.add_edge(20, 10, None, [1, 0]);
+
+        let diagnostics = diagnostics(builder);
+        assert!(matches!(
+            diagnostics[..],
+            [Diagnostic::ZeroWeight {
+                from: 20,
+                to: 10,
+                metric: Metric::TravelTime
+            }]
+        ));
+    }
+
+    #[test]
+    fn huge_weight_is_reported() {
+        let mut builder = sample_builder();
+        builder.add_edge(30, 60, Some(1), [MAX_EDGE_WEIGHT + 1, 10]);
+
+        let diagnostics = diagnostics(builder);
+        assert!(matches!(
+            diagnostics[..],
+            [Diagnostic::HugeWeight {
+                from: 30,
+                to: 60,
+                metric: Metric::Distance,
+                weight
+            }] if weight == MAX_EDGE_WEIGHT + 1
+        ));
+    }
+}
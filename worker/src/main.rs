@@ -2,27 +2,40 @@ extern crate pretty_env_logger;
 #[macro_use]
 extern crate log;
 
+mod boundary_overlay;
+mod contraction;
 mod globals;
 mod graph_receiver;
+mod graph_snapshot;
 mod graph_store;
+mod graph_validation;
 mod proto_helpers;
 mod query_processor;
 mod query_processor_holder;
 mod query_realizator;
+mod search_pool;
+mod spatial_index;
+mod storage;
+mod weight_updates;
 mod worker_service;
 
 use std::env;
 use std::net::ToSocketAddrs;
+use std::path::PathBuf;
+use std::time::Duration;
 
 use local_ip_address::local_ip;
-use tonic::transport::Server;
+use tonic::transport::{Endpoint, Server};
 
-use crate::graph_receiver::GraphReceiver;
-use crate::query_processor_holder::QueryProcessorHolder;
+use crate::graph_receiver::{GraphReceiver, SnapshotConfig};
+use crate::query_processor_holder::{AdmissionConfig, ExpiryConfig, QueryProcessorHolder};
 use crate::worker_service::WorkerService;
 
 pub struct ErrorCollection {}
 
+// Queries not touched for this long are evicted, unless `QUERY_TTL_SECS` says otherwise
+const DEFAULT_QUERY_TTL: Duration = Duration::from_secs(10 * 60);
+
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let num_cpus = num_cpus::get();
 
@@ -43,17 +56,43 @@ async fn async_main() -> Result<(), Box<dyn std::error::Error>> {
 
     let manager_addr = env::var("PARTITIONER_IP")?;
     let my_port = env::var("MY_PORT")?;
+    let snapshot_path = env::var("GRAPH_SNAPSHOT").ok().map(PathBuf::from);
+    let snapshot_mapped = matches!(env::var("GRAPH_SNAPSHOT_MMAP").as_deref(), Ok("1" | "true"));
+    let query_ttl = match env::var("QUERY_TTL_SECS") {
+        Ok(secs) => Duration::from_secs(secs.parse()?),
+        Err(_) => DEFAULT_QUERY_TTL,
+    };
+    let query_memory_limit = match env::var("QUERY_MEMORY_LIMIT_MB") {
+        Ok(megabytes) => Some(megabytes.parse::<usize>()? << 20),
+        Err(_) => None,
+    };
+    let max_live_queries = match env::var("MAX_LIVE_QUERIES") {
+        Ok(count) => Some(count.parse()?),
+        Err(_) => None,
+    };
+    let query_memory_budget = match env::var("QUERY_MEMORY_BUDGET_MB") {
+        Ok(megabytes) => Some(megabytes.parse::<usize>()? << 20),
+        Err(_) => None,
+    };
+    let strict_validation = matches!(
+        env::var("GRAPH_VALIDATION_STRICT").as_deref(),
+        Ok("1" | "true")
+    );
+    let build_hierarchies = matches!(
+        env::var("CONTRACTION_HIERARCHY").as_deref(),
+        Ok("1" | "true")
+    );
 
     info!(
         "got manager ip address in environment variable `PARTITIONER_IP`: {}",
         manager_addr
     );
 
-    info!("connecting to manager");
-
-    let client = ManagerServiceClient::connect(manager_addr)
-        .await
-        .map_err(|e| format!("Cannot connect to the manager: {:?}", e))?;
+    // Connected on the first request, so a worker with a snapshot can start without the manager
+    let channel = Endpoint::from_shared(manager_addr)
+        .map_err(|e| format!("Invalid manager address: {:?}", e))?
+        .connect_lazy();
+    let client = ManagerServiceClient::new(channel);
 
     let worker_port = my_port.parse::<u16>()?;
     let my_local_ip = local_ip().unwrap();
@@ -68,14 +107,39 @@ async fn async_main() -> Result<(), Box<dyn std::error::Error>> {
 
     let listening_addr_unparsed = format!("http://{}", listening_addr);
 
-    let mut receiver = GraphReceiver::new(client, listening_addr_unparsed).await?;
+    let snapshot = snapshot_path.map(|path| SnapshotConfig {
+        path,
+        mapped: snapshot_mapped,
+    });
+
+    let mut receiver =
+        GraphReceiver::new(client, listening_addr_unparsed, strict_validation, snapshot).await?;
+
     receiver.receive_graph().await?;
+    boundary_overlay::precompute_in_background();
+    spatial_index::build_in_background();
+
+    if build_hierarchies {
+        contraction::build_in_background();
+    }
+
+    let expiry = ExpiryConfig {
+        ttl: query_ttl,
+        memory_limit: query_memory_limit,
+    };
+
+    let admission = AdmissionConfig {
+        max_live_queries,
+        memory_budget: query_memory_budget,
+    };
 
     globals::PROCESSOR_HOLDER
-        .set(QueryProcessorHolder::new())
+        .set(QueryProcessorHolder::new(expiry, admission))
         .map_err(|_| "Cannot set global processor holder")?;
 
-    let service = WorkerService::new();
+    tokio::spawn(globals::processor_holder().run_sweeper());
+
+    let service = WorkerService::new(receiver, build_hierarchies);
     let server = WorkerServer::new(service);
 
     Server::builder()
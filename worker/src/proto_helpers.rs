@@ -1,8 +1,11 @@
 use generated::worker::{
     response_djikstra::{
+        MeetingPoint,
         MessageType::{
+            FinalNodeReached as FinalNodeReachedVariant, MeetingPoint as MeetingPointVariant,
             NewForeignNode as NewForeignNodeVariant,
             SmallestDomesticNode as SmallestDomesticNodeVariant, Success as SuccessVariant,
+            TargetReached as TargetReachedVariant,
         },
         NewForeignNode, SmallestDomesticNode, Success,
     },
@@ -16,12 +19,14 @@ pub fn new_foreign_node(
     worker_id: WorkerId,
     parent_node_id: NodeId,
     shortest_path_len: ShortestPathLen,
+    estimated_path_len: ShortestPathLen,
 ) -> ResponseDjikstra {
     ResponseDjikstra {
         message_type: Some(NewForeignNodeVariant(NewForeignNode {
             this_node: Some(NodePointer { worker_id, node_id }),
             parent_node_id,
             shortest_path_len,
+            estimated_path_len,
         })),
     }
 }
@@ -42,3 +47,27 @@ pub fn success(node_id: NodeId, shortest_path_len: ShortestPathLen) -> ResponseD
         })),
     }
 }
+
+pub fn target_reached(node_id: NodeId, shortest_path_len: ShortestPathLen) -> ResponseDjikstra {
+    ResponseDjikstra {
+        message_type: Some(TargetReachedVariant(Success {
+            node_id,
+            shortest_path_len,
+        })),
+    }
+}
+
+pub fn final_node_reached(node_id: NodeId, shortest_path_len: ShortestPathLen) -> ResponseDjikstra {
+    ResponseDjikstra {
+        message_type: Some(FinalNodeReachedVariant(Success {
+            node_id,
+            shortest_path_len,
+        })),
+    }
+}
+
+pub fn meeting_point(node_id: NodeId, path_len: ShortestPathLen) -> ResponseDjikstra {
+    ResponseDjikstra {
+        message_type: Some(MeetingPointVariant(MeetingPoint { node_id, path_len })),
+    }
+}
@@ -1,6 +1,8 @@
-use std::cmp::Ordering;
+use std::cmp::{Ordering, Reverse};
 use std::collections::hash_map::Entry;
-use std::collections::{BinaryHeap, HashMap};
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::mem;
+use std::sync::Arc;
 
 use tonic::Status;
 
@@ -8,10 +10,13 @@ use generated::worker::request_djikstra;
 use generated::worker::ResponseDjikstra;
 use request_djikstra::QueryData;
 
-use crate::globals;
-use crate::graph_store::{IdIdxMapper, NodeId, NodeIdx, ShortestPathLen};
-use crate::graph_store::{NodePointer, SomeGraphMethods, WorkerId};
+use crate::contraction::ContractionHierarchy;
+use crate::globals::{self, Fragment};
+use crate::graph_store::{IdIdxMapper, IdIdxMapping, NodeId, NodeIdx, ShortestPathLen};
+use crate::graph_store::{Metric, NodePointer, SPQGraph, SomeGraphMethods, WorkerId};
 use crate::proto_helpers;
+use crate::search_pool::{DenseVisit, SearchBuffers, VisitArray};
+use crate::ErrorCollection;
 
 pub type QueryId = u32;
 
@@ -22,14 +27,202 @@ pub enum NodeParent {
     Foreign(NodeId, WorkerId),
 }
 
-type ParentMap = HashMap<NodeId, NodeParent>;
+// Parents of domestic nodes are stored as `NodeIdx`, with these two values reserved
+const PARENT_ROOT: NodeIdx = NodeIdx::MAX;
+const PARENT_FOREIGN: NodeIdx = NodeIdx::MAX - 1;
 
-#[derive(Debug)]
-pub struct QueryProcessor {
-    parent_map: ParentMap,
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Forward,
+    Backward, // From the final node, over reversed edges
+}
+
+// Distance weights are computed by the partitioner as `1e7 * distance in degrees`. The heuristic
+// uses a slightly smaller factor, so that it stays admissible despite the rounding of the weights.
+const HEURISTIC_WEIGHT_PER_DEGREE: f64 = 0.99e7;
+
+// Great-circle distance between two (lat, lon) points, expressed in edge weight units.
+fn great_circle_heuristic(from: (f64, f64), to: (f64, f64)) -> ShortestPathLen {
+    let (lat_from, lon_from) = (from.0.to_radians(), from.1.to_radians());
+    let (lat_to, lon_to) = (to.0.to_radians(), to.1.to_radians());
+
+    let haversine = ((lat_to - lat_from) / 2.).sin().powi(2)
+        + lat_from.cos() * lat_to.cos() * ((lon_to - lon_from) / 2.).sin().powi(2);
+    let angle = 2. * haversine.sqrt().min(1.).asin();
+
+    (angle.to_degrees() * HEURISTIC_WEIGHT_PER_DEGREE) as ShortestPathLen
+}
+
+// Lower bound of the distance from the node to the final node; zero if not in A* mode.
+fn heuristic(graph: &SPQGraph, final_coords: Option<(f64, f64)>, idx: NodeIdx) -> ShortestPathLen {
+    match final_coords {
+        Some(final_coords) => great_circle_heuristic(graph.coords(idx), final_coords),
+        None => 0,
+    }
+}
+
+// Upward search from the final node over the reversed edges of the hierarchy. Maps the reached
+// nodes to their distances to the final node and the next nodes on the way there.
+fn target_tree(
+    hierarchy: &ContractionHierarchy,
+    final_idx: NodeIdx,
+) -> HashMap<NodeIdx, (ShortestPathLen, NodeIdx)> {
+    let mut tree = HashMap::from([(final_idx, (0, final_idx))]);
+    let mut queue = BinaryHeap::from([Reverse((0, final_idx))]);
+
+    while let Some(Reverse((shortest, idx))) = queue.pop() {
+        // The core is left to the forward search
+        if tree[&idx].0 < shortest || hierarchy.is_core(idx) {
+            continue;
+        }
+
+        for edge in hierarchy.down(idx) {
+            let new_shortest = shortest + edge.weight;
+
+            if !matches!(tree.get(&edge.to), Some((d, _)) if *d <= new_shortest) {
+                tree.insert(edge.to, (new_shortest, idx));
+                queue.push(Reverse((new_shortest, edge.to)));
+            }
+        }
+    }
+
+    tree
+}
+
+// Nodes and edges the path must avoid
+#[derive(Debug, Default)]
+struct Exclusions {
+    nodes: HashSet<NodeId>,
+    edges: HashSet<(NodeId, NodeId)>,
+}
+
+impl Exclusions {
+    fn new(data: &QueryData) -> Self {
+        Exclusions {
+            nodes: data.excluded_node_ids.iter().copied().collect(),
+            edges: data
+                .excluded_edges
+                .iter()
+                .map(|pair| (pair.node_from_id, pair.node_to_id))
+                .collect(),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.nodes.is_empty() && self.edges.is_empty()
+    }
+
+    // Whether the edge from `from` to `to` (in the direction of the search) may not be used
+    fn excludes(&self, direction: Direction, from: NodeId, to: NodeId) -> bool {
+        let edge = match direction {
+            Direction::Forward => (from, to),
+            Direction::Backward => (to, from),
+        };
+
+        self.nodes.contains(&to) || self.edges.contains(&edge)
+    }
+}
+
+#[derive(Debug, Default)]
+struct SearchState {
+    // Visits of domestic nodes
+    visits: VisitArray,
+    // Parents of the domestic nodes reached from other workers
+    foreign_parents: HashMap<NodeIdx, (NodeId, WorkerId)>,
+    // Foreign nodes reached over boundary edges, with their domestic parents and path lengths
+    foreign_visits: HashMap<NodeId, (NodeIdx, ShortestPathLen)>,
     queue: BinaryHeap<QueueElement>,
     smallest_foreign: Option<ShortestPathLen>,
+}
+
+impl SearchState {
+    fn new(nodes_count: usize) -> Self {
+        let SearchBuffers { visits, queue } = globals::SEARCH_POOL.acquire(nodes_count);
+
+        SearchState {
+            visits,
+            foreign_parents: HashMap::new(),
+            foreign_visits: HashMap::new(),
+            queue,
+            smallest_foreign: None,
+        }
+    }
+
+    fn get_parent(&self, mapping: &IdIdxMapping, id: NodeId) -> Option<NodeParent> {
+        let idx = match mapping.find(id) {
+            Some(idx) => idx,
+            None => {
+                return self
+                    .foreign_visits
+                    .get(&id)
+                    .map(|(parent, _)| NodeParent::Domestic(*parent))
+            }
+        };
+
+        let parent = match self.visits.get(idx)?.parent {
+            PARENT_ROOT => NodeParent::Root,
+            PARENT_FOREIGN => {
+                let (id, worker_id) = self.foreign_parents[&idx];
+                NodeParent::Foreign(id, worker_id)
+            }
+            parent => NodeParent::Domestic(parent),
+        };
+
+        Some(parent)
+    }
+
+    // Rough estimate; hash maps store one control byte per bucket.
+    fn memory_usage(&self) -> usize {
+        let foreign_parent_size = mem::size_of::<(NodeIdx, (NodeId, WorkerId))>() + 1;
+        let foreign_visit_size = mem::size_of::<(NodeId, (NodeIdx, ShortestPathLen))>() + 1;
+
+        self.visits.memory_usage()
+            + self.foreign_parents.capacity() * foreign_parent_size
+            + self.foreign_visits.capacity() * foreign_visit_size
+            + self.queue.capacity() * mem::size_of::<QueueElement>()
+    }
+}
+
+impl Drop for SearchState {
+    fn drop(&mut self) {
+        globals::SEARCH_POOL.release(SearchBuffers {
+            visits: mem::take(&mut self.visits),
+            queue: mem::take(&mut self.queue),
+        });
+    }
+}
+
+#[derive(Debug)]
+pub struct QueryProcessor {
+    // Version of the graph fragment current when the query started
+    fragment: Arc<Fragment>,
+    forward: SearchState,
+    backward: SearchState, // Used only in the bidirectional mode
+    bidirectional: bool,
+    metric: Metric,
+    // Set by each request
+    direction: Direction,
+    round_limit: Option<ShortestPathLen>,
+    // Best meeting point of both searches found since the last step
+    new_meeting: Option<(NodeId, ShortestPathLen)>,
     final_node: NodeId,
+    // Targets of a one-to-many query not reached yet; replaced by each request
+    targets: HashSet<NodeId>,
+    one_to_many: bool,
+    // Whether the query has no final node and only explores the nodes within `round_limit`
+    reachable_set: bool,
+    // Whether other workers run their rounds at the same time, see `QueryData::parallel`
+    parallel: bool,
+    exclusions: Exclusions,
+    // Present in A* mode
+    final_coords: Option<(f64, f64)>,
+    // Whether the search runs over the contraction hierarchy of the fragment
+    hierarchy: bool,
+    // Target tree of the final node, if it is domestic (hierarchy mode only)
+    target_tree: HashMap<NodeIdx, (ShortestPathLen, NodeIdx)>,
+    // Shortest path to the final node found so far and the node where it leaves the forward
+    // search (hierarchy mode only)
+    best: Option<(ShortestPathLen, NodeIdx)>,
     query_id: QueryId,
 }
 
@@ -43,27 +236,240 @@ impl QueryProcessor {
         self.query_id
     }
 
-    pub fn new(data: &QueryData) -> Self {
+    // Starts the query on the given version of the graph fragment
+    pub fn new(data: &QueryData, fragment: Arc<Fragment>) -> Self {
+        let nodes_count = fragment.graph.ids.len();
+
+        // The backward search is not run outside the bidirectional mode
+        let backward = match data.bidirectional {
+            true => SearchState::new(nodes_count),
+            false => SearchState::default(),
+        };
+
+        // Plain queries only; the hierarchy is not ready yet for the queries started right after
+        // the graph was received.
+        let hierarchy = match fragment.hierarchies[data.metric() as usize].get() {
+            Some(hierarchy)
+                if !data.bidirectional
+                    && data.final_node_coords.is_none()
+                    && data.target_node_ids.is_empty()
+                    && !data.reachable_set
+                    && data.excluded_node_ids.is_empty()
+                    && data.excluded_edges.is_empty()
+                    && !data.parallel =>
+            {
+                Some(hierarchy)
+            }
+            _ => None,
+        };
+
+        let target_tree = match (hierarchy, fragment.mapping.find(data.final_node_id)) {
+            (Some(hierarchy), Some(final_idx)) => target_tree(hierarchy, final_idx),
+            _ => HashMap::new(),
+        };
+
+        let uses_hierarchy = hierarchy.is_some();
+
         QueryProcessor {
-            parent_map: ParentMap::new(),
-            queue: BinaryHeap::new(),
-            smallest_foreign: None,
+            fragment,
+            forward: SearchState::new(nodes_count),
+            backward,
+            bidirectional: data.bidirectional,
+            metric: data.metric(),
+            direction: Direction::Forward,
+            round_limit: None,
+            new_meeting: None,
             query_id: data.query_id,
             final_node: data.final_node_id,
+            targets: data.target_node_ids.iter().copied().collect(),
+            one_to_many: !data.target_node_ids.is_empty(),
+            reachable_set: data.reachable_set,
+            parallel: data.parallel,
+            exclusions: Exclusions::new(data),
+            final_coords: data.final_node_coords.as_ref().map(|c| (c.lat, c.lon)),
+            hierarchy: uses_hierarchy,
+            target_tree,
+            best: None,
+        }
+    }
+
+    // Memory a new query takes before its first step: the visit arrays of its searches
+    pub fn initial_memory_usage(data: &QueryData, fragment: &Fragment) -> usize {
+        let searches = if data.bidirectional { 2 } else { 1 };
+
+        searches * fragment.graph.ids.len() * mem::size_of::<DenseVisit>()
+    }
+
+    // Heap memory held by the query, without the shared graph fragment
+    pub fn memory_usage(&self) -> usize {
+        let target_tree_size = mem::size_of::<(NodeIdx, (ShortestPathLen, NodeIdx))>() + 1;
+        let target_size = mem::size_of::<NodeId>() + 1;
+
+        self.forward.memory_usage()
+            + self.backward.memory_usage()
+            + self.target_tree.capacity() * target_tree_size
+            + self.targets.capacity() * target_size
+    }
+
+    pub fn fragment(&self) -> &Arc<Fragment> {
+        &self.fragment
+    }
+
+    fn search(&self, direction: Direction) -> &SearchState {
+        match direction {
+            Direction::Forward => &self.forward,
+            Direction::Backward => &self.backward,
+        }
+    }
+
+    // Whether the visits are relaxed by the shorter paths found later, so that the distances of the
+    // popped nodes are exact. Other searches stop at the first path found to the final node.
+    fn relaxes_visits(&self) -> bool {
+        self.hierarchy || self.parallel || self.one_to_many || self.reachable_set
+    }
+
+    fn get_parent(&self, id: NodeId, direction: Direction) -> Option<NodeParent> {
+        self.search(direction)
+            .get_parent(&self.fragment.mapping, id)
+    }
+
+    // Domestic nodes reached by the forward search within the budget
+    pub fn reachable(&self, budget: ShortestPathLen) -> Vec<(NodeIdx, ShortestPathLen)> {
+        self.forward
+            .visits
+            .iter()
+            .filter(|(_, visit)| visit.shortest <= budget)
+            .map(|(idx, visit)| (idx, visit.shortest))
+            .collect()
+    }
+
+    fn contraction_hierarchy(&self) -> Option<&ContractionHierarchy> {
+        match self.hierarchy {
+            true => self.fragment.hierarchies[self.metric as usize].get(),
+            false => None,
+        }
+    }
+
+    // Parents of the node, up to the root or the first foreign node
+    pub fn backtrack(&self, id: NodeId, direction: Direction) -> Result<Vec<NodeParent>, Status> {
+        let graph = &self.fragment.graph;
+        let mut parents = Vec::new();
+
+        let hierarchy = match self.contraction_hierarchy() {
+            Some(hierarchy) => hierarchy,
+            None => {
+                let mut current = id;
+
+                loop {
+                    let parent = self
+                        .get_parent(current, direction)
+                        .ok_or_else(|| ErrorCollection::cannot_find_parent(current))?;
+                    parents.push(parent);
+
+                    match parent {
+                        NodeParent::Domestic(idx) => current = graph.node_id(idx),
+                        _ => return Ok(parents),
+                    }
+                }
+            }
+        };
+
+        let search = &self.forward;
+
+        let mut current = match self.fragment.mapping.find(id) {
+            None => {
+                let (idx, _) = *search
+                    .foreign_visits
+                    .get(&id)
+                    .ok_or_else(|| ErrorCollection::cannot_find_parent(id))?;
+                parents.push(NodeParent::Domestic(idx));
+                idx
+            }
+            Some(final_idx) if id == self.final_node => {
+                let (_, meeting) = self
+                    .best
+                    .ok_or_else(|| ErrorCollection::cannot_find_parent(id))?;
+
+                // From the meeting node down to the final node, excluding it
+                let mut path = vec![meeting];
+                let mut idx = meeting;
+
+                while idx != final_idx {
+                    let next = self.target_tree[&idx].1;
+                    hierarchy.unpack(idx, next, &mut path)?;
+                    path.push(next);
+                    idx = next;
+                }
+
+                path.pop();
+                parents.extend(path.into_iter().rev().map(NodeParent::Domestic));
+                meeting
+            }
+            Some(idx) => idx,
+        };
+
+        // Parents in the search may be reached by shortcuts
+        loop {
+            let visit = search
+                .visits
+                .get(current)
+                .ok_or_else(|| ErrorCollection::cannot_find_parent(graph.node_id(current)))?;
+
+            match visit.parent {
+                PARENT_ROOT => {
+                    parents.push(NodeParent::Root);
+                    return Ok(parents);
+                }
+                PARENT_FOREIGN => {
+                    let (id, worker_id) = search.foreign_parents[&current];
+                    parents.push(NodeParent::Foreign(id, worker_id));
+                    return Ok(parents);
+                }
+                parent => {
+                    let mut path = Vec::new();
+                    hierarchy.unpack(parent, current, &mut path)?;
+                    parents.extend(path.into_iter().rev().map(NodeParent::Domestic));
+                    parents.push(NodeParent::Domestic(parent));
+                    current = parent;
+                }
+            }
         }
     }
 
-    pub fn get_parent(&self, id: NodeId) -> Option<NodeParent> {
-        self.parent_map.get(&id).copied()
+    fn offer_meeting(
+        new_meeting: &mut Option<(NodeId, ShortestPathLen)>,
+        id: NodeId,
+        path_len: ShortestPathLen,
+    ) {
+        if !matches!(new_meeting, Some((_, len)) if *len <= path_len) {
+            debug!(" -> new meeting point[id: {id}, len: {path_len}]");
+            *new_meeting = Some((id, path_len));
+        }
     }
 
-    pub fn update_smallest_foreign(&mut self, smallest_foreign: Option<ShortestPathLen>) {
+    // Prepares the processor for the request described by `data`
+    pub fn start_round(&mut self, data: &QueryData) {
+        self.direction = match data.backward {
+            true => Direction::Backward,
+            false => Direction::Forward,
+        };
+        self.round_limit = data.round_limit;
+
+        if self.one_to_many {
+            self.targets = data.target_node_ids.iter().copied().collect();
+        }
+
+        let search = match self.direction {
+            Direction::Forward => &mut self.forward,
+            Direction::Backward => &mut self.backward,
+        };
+
         debug!(
             " -> replacing old smallest_foreign {:?} with new {:?}",
-            self.smallest_foreign, smallest_foreign
+            search.smallest_foreign, data.smallest_foreign_node
         );
 
-        self.smallest_foreign = smallest_foreign;
+        search.smallest_foreign = data.smallest_foreign_node;
     }
 
     pub fn add_new_domestic_node(
@@ -72,18 +478,50 @@ impl QueryProcessor {
         shortest: ShortestPathLen,
         parent: NodeParent,
     ) -> Result<(), Status> {
-        let idx = globals::mapping().get_mapping(id)?;
-        let entry = self.parent_map.entry(id);
+        let idx = self.fragment.mapping.get_mapping(id)?;
+        let relax = self.relaxes_visits();
+
+        if self.exclusions.nodes.contains(&id) {
+            debug!(" -> node[id: {id}] is excluded");
+            return Ok(());
+        }
+
+        let (search, other) = match self.direction {
+            Direction::Forward => (&mut self.forward, &self.backward),
+            Direction::Backward => (&mut self.backward, &self.forward),
+        };
 
         debug!("new domestic node[id: {id}, idx: {idx}, len: {shortest}, parent: {parent:?}]");
 
-        match entry {
-            Entry::Occupied(_) => debug!(" -> node[id: {id}] was already visited"),
-            Entry::Vacant(entry) => {
-                debug!(" -> node[id: {id}] is not visited: pushing to queue");
-                entry.insert(parent);
-                self.queue.push(QueueElement { idx, shortest });
-            }
+        let parent_code = match parent {
+            NodeParent::Root => PARENT_ROOT,
+            NodeParent::Domestic(parent_idx) => parent_idx,
+            NodeParent::Foreign(..) => PARENT_FOREIGN,
+        };
+
+        // Nodes are settled only when popped, so later seeds may still improve them; in the
+        // parallel mode even the explored nodes may.
+        let visited = match relax {
+            true => !search.visits.relax(idx, parent_code, shortest),
+            false => !search.visits.insert(idx, parent_code, shortest),
+        };
+
+        if visited {
+            debug!(" -> node[id: {id}] was already visited");
+            return Ok(());
+        }
+
+        debug!(" -> node[id: {id}] is not visited: pushing to queue");
+
+        if let NodeParent::Foreign(parent_id, worker_id) = parent {
+            search.foreign_parents.insert(idx, (parent_id, worker_id));
+        }
+
+        let key = shortest + heuristic(&self.fragment.graph, self.final_coords, idx);
+        search.queue.push(QueueElement { idx, shortest, key });
+
+        if let Some(visit) = other.visits.get(idx) {
+            Self::offer_meeting(&mut self.new_meeting, id, shortest + visit.shortest);
         }
 
         Ok(())
@@ -91,23 +529,27 @@ impl QueryProcessor {
 
     // To be executed on the blocking thread
     pub fn djikstra_step(mut self) -> Result<(Self, StepResult), Status> {
+        if self.hierarchy {
+            return self.hierarchy_step();
+        }
+
         type RVec = Vec<ResponseDjikstra>;
         let mut responses = RVec::new();
 
         let append_response_foreign =
-            |responses: &mut RVec, node_id, worker_id, parent_id, shortest| {
+            |responses: &mut RVec, node_id, worker_id, parent_id, shortest, estimated| {
                 responses.push(proto_helpers::new_foreign_node(
-                    node_id, worker_id, parent_id, shortest,
+                    node_id, worker_id, parent_id, shortest, estimated,
                 ));
             };
 
-        let append_response_domestic = |responses: &mut RVec, shortest| {
-            debug!("pushing smallest domestic node[len: {shortest}] to response");
-            responses.push(proto_helpers::domestic_smallest_node(shortest));
+        let append_response_domestic = |responses: &mut RVec, key| {
+            debug!("pushing smallest domestic node[key: {key}] to response");
+            responses.push(proto_helpers::domestic_smallest_node(key));
         };
 
         let check_success = |node_id, shortest| {
-            if self.final_node == node_id {
+            if self.final_node == node_id && !self.reachable_set && !self.one_to_many {
                 debug!("success: node: {node_id}, length: {shortest}");
                 Some(StepResult::Finished(node_id, shortest))
             } else {
@@ -115,47 +557,144 @@ impl QueryProcessor {
             }
         };
 
+        let direction = self.direction;
+        let relax = self.relaxes_visits();
+        let fragment = &self.fragment;
+        let graph = &fragment.graph;
+        let (search, other) = match direction {
+            Direction::Forward => (&mut self.forward, &self.backward),
+            Direction::Backward => (&mut self.backward, &self.forward),
+        };
+
         // We consumed all nodes from our graph fragment? Time to stop the query.
-        while let Some(node) = self.queue.peek() {
+        while let Some(node) = search.queue.peek() {
+            // All targets of the one-to-many query reached? Time to stop the query.
+            if self.one_to_many && self.targets.is_empty() {
+                append_response_domestic(&mut responses, node.key);
+                break;
+            }
+
             // Smallest node does not belong to this worker? Time to stop the query.
-            if let Some(smf) = self.smallest_foreign {
-                if smf < node.shortest {
+            if let Some(smf) = search.smallest_foreign {
+                if smf < node.key {
                     debug!(
                         "smallest node does not belong to this worker, {} vs {}",
-                        node.shortest, smf
+                        node.key, smf
                     );
-                    append_response_domestic(&mut responses, node.shortest);
+                    append_response_domestic(&mut responses, node.key);
                     break;
                 }
             }
 
-            let node = self.queue.pop().unwrap();
+            // Frontier went past the limit set by the executer? Time to stop the query.
+            if let Some(limit) = self.round_limit {
+                if limit < node.key {
+                    debug!("round limit reached, {} vs {}", node.key, limit);
+                    append_response_domestic(&mut responses, node.key);
+                    break;
+                }
+            }
+
+            let node = search.queue.pop().unwrap();
+
+            // The node was reached by a shorter path after this element was pushed
+            if relax && search.visits.get(node.idx).unwrap().shortest < node.shortest {
+                continue;
+            }
+
+            // Distances to the targets are final only once they are popped
+            if self.one_to_many {
+                let node_id = graph.node_id(node.idx);
+
+                if self.targets.remove(&node_id) {
+                    debug!("target reached: node: {node_id}, length: {}", node.shortest);
+                    responses.push(proto_helpers::target_reached(node_id, node.shortest));
+                }
+            }
+
+            let node_heuristic = node.key - node.shortest;
+
+            let edges = match direction {
+                Direction::Forward => fragment.edges(node.idx),
+                Direction::Backward => fragment.reverse_edges(node.idx),
+            };
+
+            for edge in edges {
+                let parent_idx = node.idx;
+                let weight = edge.weight(self.metric);
+                let new_shortest = node.shortest + weight;
+
+                if !self.exclusions.is_empty() {
+                    let to = match edge.to {
+                        NodePointer::Foreign(node_id, _) => node_id,
+                        NodePointer::Domestic(idx) => graph.node_id(idx),
+                    };
+
+                    if self
+                        .exclusions
+                        .excludes(direction, graph.node_id(parent_idx), to)
+                    {
+                        continue;
+                    }
+                }
 
-            for edge in globals::graph().edges(node.idx) {
+                // Skip already visited nodes, unless the path is shorter and the visits are relaxed
                 let new_node_id = match edge.to {
-                    NodePointer::Foreign(node_id, _) => node_id,
-                    NodePointer::Domestic(new_node_idx) => {
-                        globals::graph().get_node(new_node_idx).id
+                    NodePointer::Foreign(node_id, _) => {
+                        match search.foreign_visits.entry(node_id) {
+                            Entry::Occupied(mut entry) if relax && entry.get().1 > new_shortest => {
+                                entry.insert((parent_idx, new_shortest));
+                            }
+                            Entry::Occupied(_) => continue,
+                            Entry::Vacant(entry) => {
+                                entry.insert((parent_idx, new_shortest));
+                            }
+                        };
+
+                        node_id
                     }
-                };
+                    NodePointer::Domestic(new_node_idx) => {
+                        let reached = match relax {
+                            true => search.visits.relax(new_node_idx, parent_idx, new_shortest),
+                            false => search.visits.insert(new_node_idx, parent_idx, new_shortest),
+                        };
 
-                let parent_idx = node.idx;
+                        if !reached {
+                            continue;
+                        }
 
-                match self.parent_map.entry(new_node_id) {
-                    Entry::Occupied(_) => continue, // Already visited.
-                    Entry::Vacant(entry) => entry.insert(NodeParent::Domestic(parent_idx)),
+                        graph.node_id(new_node_idx)
+                    }
                 };
 
-                let new_shortest = node.shortest + edge.weight;
-
-                // Maybe we found the final node?
-                if let Some(success) = check_success(new_node_id, new_shortest) {
+                if self.bidirectional {
+                    // Meeting points are reported only by the owners of the nodes
+                    if let NodePointer::Domestic(new_node_idx) = edge.to {
+                        if let Some(visit) = other.visits.get(new_node_idx) {
+                            let path_len = new_shortest + visit.shortest;
+                            Self::offer_meeting(&mut self.new_meeting, new_node_id, path_len);
+                        }
+                    }
+                } else if self.parallel {
+                    // Other workers may still find a shorter path
+                    if new_node_id == self.final_node {
+                        debug!("final node reached: length: {new_shortest}");
+                        responses
+                            .push(proto_helpers::final_node_reached(new_node_id, new_shortest));
+                    }
+                } else if let Some(success) = check_success(new_node_id, new_shortest) {
+                    // Maybe we found the final node?
                     return Ok((self, success));
                 }
 
                 match edge.to {
                     NodePointer::Foreign(_, worker_id) => {
-                        let parent_id = globals::graph().get_node(parent_idx).id;
+                        let parent_id = graph.node_id(parent_idx);
+
+                        // Coordinates of foreign nodes are unknown, but the heuristic is
+                        // consistent, so the parent's heuristic minus the edge weight is still
+                        // a lower bound.
+                        let estimated = new_shortest + node_heuristic.saturating_sub(weight);
 
                         append_response_foreign(
                             &mut responses,
@@ -163,42 +702,176 @@ impl QueryProcessor {
                             worker_id,
                             parent_id,
                             new_shortest,
+                            estimated,
                         );
 
-                        if let Some(smallest) = self.smallest_foreign.as_mut() {
-                            *smallest = std::cmp::min(*smallest, new_shortest);
+                        // In the parallel mode the round goes on up to its limit
+                        if self.parallel {
+                            continue;
+                        }
+
+                        if let Some(smallest) = search.smallest_foreign.as_mut() {
+                            *smallest = std::cmp::min(*smallest, estimated);
                         } else {
-                            self.smallest_foreign = Some(new_shortest);
+                            search.smallest_foreign = Some(estimated);
                         }
                     }
                     NodePointer::Domestic(new_node_idx) => {
-                        self.queue
-                            .push(QueueElement::new(new_node_idx, new_shortest));
+                        let key = new_shortest + heuristic(graph, self.final_coords, new_node_idx);
+
+                        search
+                            .queue
+                            .push(QueueElement::new(new_node_idx, new_shortest, key));
                     }
                 }
             }
         }
 
+        if let Some((node_id, path_len)) = self.new_meeting.take() {
+            responses.push(proto_helpers::meeting_point(node_id, path_len));
+        }
+
+        Ok((self, StepResult::Remaining(responses)))
+    }
+
+    // Like `djikstra_step`, but the search goes only upwards in the contraction hierarchy, except
+    // within the core, where the boundary nodes are. Distances to the core nodes are thus exact, and
+    // the final node is reached through its target tree.
+    fn hierarchy_step(mut self) -> Result<(Self, StepResult), Status> {
+        let mut responses = Vec::new();
+
+        let fragment = &self.fragment;
+        let graph = &fragment.graph;
+        let hierarchy = fragment.hierarchies[self.metric as usize]
+            .get()
+            .ok_or_else(ErrorCollection::hierarchy_not_built)?;
+        let search = &mut self.forward;
+
+        loop {
+            // The best path to the final node counts as a node of this worker
+            let top = search.queue.peek().map(|node| node.key);
+            let next = match (top, self.best) {
+                (Some(key), Some((best, _))) => std::cmp::min(key, best),
+                (Some(key), None) => key,
+                (None, Some((best, _))) => best,
+                (None, None) => break,
+            };
+
+            // Smallest node does not belong to this worker? Time to stop the query.
+            if let Some(smf) = search.smallest_foreign {
+                if smf < next {
+                    debug!("smallest node does not belong to this worker, {next} vs {smf}");
+                    responses.push(proto_helpers::domestic_smallest_node(next));
+                    break;
+                }
+            }
+
+            // Frontier went past the limit set by the executer? Time to stop the query.
+            if let Some(limit) = self.round_limit {
+                if limit < next {
+                    debug!("round limit reached, {next} vs {limit}");
+                    responses.push(proto_helpers::domestic_smallest_node(next));
+                    break;
+                }
+            }
+
+            if matches!(self.best, Some((best, _)) if best == next) {
+                let final_node = self.final_node;
+                debug!("success: node: {final_node}, length: {next}");
+                return Ok((self, StepResult::Finished(final_node, next)));
+            }
+
+            let node = search.queue.pop().unwrap();
+
+            // The node was reached by a shorter path after this element was pushed
+            if search.visits.get(node.idx).unwrap().shortest < node.shortest {
+                continue;
+            }
+
+            if let Some((to_final, _)) = self.target_tree.get(&node.idx) {
+                let path_len = node.shortest + to_final;
+
+                if !matches!(self.best, Some((best, _)) if best <= path_len) {
+                    self.best = Some((path_len, node.idx));
+                }
+            }
+
+            for edge in hierarchy.up(node.idx) {
+                let new_shortest = node.shortest + edge.weight;
+
+                if search.visits.relax(edge.to, node.idx, new_shortest) {
+                    search
+                        .queue
+                        .push(QueueElement::new(edge.to, new_shortest, new_shortest));
+                }
+            }
+
+            // Only the boundary nodes have foreign edges
+            if !hierarchy.is_core(node.idx) {
+                continue;
+            }
+
+            for edge in fragment.edges(node.idx) {
+                if let NodePointer::Foreign(node_id, worker_id) = edge.to {
+                    let new_shortest = node.shortest + edge.weight(self.metric);
+
+                    match search.foreign_visits.entry(node_id) {
+                        Entry::Occupied(_) => continue,
+                        Entry::Vacant(entry) => entry.insert((node.idx, new_shortest)),
+                    };
+
+                    if node_id == self.final_node {
+                        debug!("success: node: {node_id}, length: {new_shortest}");
+                        return Ok((self, StepResult::Finished(node_id, new_shortest)));
+                    }
+
+                    responses.push(proto_helpers::new_foreign_node(
+                        node_id,
+                        worker_id,
+                        graph.node_id(node.idx),
+                        new_shortest,
+                        new_shortest,
+                    ));
+
+                    search.smallest_foreign = Some(match search.smallest_foreign {
+                        Some(smallest) => std::cmp::min(smallest, new_shortest),
+                        None => new_shortest,
+                    });
+                }
+            }
+        }
+
         Ok((self, StepResult::Remaining(responses)))
     }
 }
 
+impl ErrorCollection {
+    fn cannot_find_parent(node_id: NodeId) -> Status {
+        Status::not_found(format!("cannot find parent of node[idx: {node_id}]"))
+    }
+
+    fn hierarchy_not_built() -> Status {
+        Status::internal("contraction hierarchy of the query is not built")
+    }
+}
+
 #[derive(Eq, PartialEq, Debug)]
-struct QueueElement {
-    idx: NodeIdx,
-    shortest: ShortestPathLen,
+pub struct QueueElement {
+    pub idx: NodeIdx,
+    pub shortest: ShortestPathLen,
+    key: ShortestPathLen, // `shortest` plus the heuristic
 }
 
 impl QueueElement {
-    fn new(idx: NodeIdx, shortest: ShortestPathLen) -> Self {
-        QueueElement { idx, shortest }
+    pub fn new(idx: NodeIdx, shortest: ShortestPathLen, key: ShortestPathLen) -> Self {
+        QueueElement { idx, shortest, key }
     }
 }
 
 impl Ord for QueueElement {
     fn cmp(&self, other: &Self) -> Ordering {
         // Note `reverse`: smallest element on top
-        self.shortest.cmp(&other.shortest).reverse()
+        self.key.cmp(&other.key).reverse()
     }
 }
 
@@ -207,3 +880,87 @@ impl PartialOrd for QueueElement {
         Some(self.cmp(other))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph_store::SPQGraphBuilder;
+
+    use generated::worker::response_djikstra::MessageType;
+
+    // 1 -> 3 directly is longer than the detour 1 -> 2 -> 3, which is found only later
+    fn detour_fragment() -> Arc<Fragment> {
+        let mut builder = SPQGraphBuilder::new();
+
+        for id in 1..=4 {
+            builder.add_node(id, (50.0, 19.0 + id as f64 / 100.0));
+        }
+
+        builder.add_edge(1, 3, None, [10, 10]);
+        builder.add_edge(1, 2, None, [1, 1]);
+        builder.add_edge(2, 3, None, [1, 1]);
+        builder.add_edge(3, 4, None, [1, 1]);
+
+        let (graph, mapping) = builder.build().unwrap();
+        Arc::new(Fragment::new(graph, mapping, 1))
+    }
+
+    // Runs the first round of the query from node 1 and returns its responses
+    fn first_round(data: &QueryData) -> (QueryProcessor, Vec<ResponseDjikstra>) {
+        let mut processor = QueryProcessor::new(data, detour_fragment());
+
+        processor.start_round(data);
+        processor
+            .add_new_domestic_node(1, 0, NodeParent::Root)
+            .unwrap();
+
+        match processor.djikstra_step().unwrap() {
+            (processor, StepResult::Remaining(responses)) => (processor, responses),
+            (_, StepResult::Finished(..)) => panic!("query without final node finished"),
+        }
+    }
+
+    #[test]
+    fn one_to_many_reports_shortest_distances() {
+        let data = QueryData {
+            target_node_ids: vec![3, 4],
+            ..Default::default()
+        };
+
+        let (_, responses) = first_round(&data);
+
+        let mut reached: Vec<_> = responses
+            .into_iter()
+            .filter_map(|response| match response.message_type {
+                Some(MessageType::TargetReached(target)) => {
+                    Some((target.node_id, target.shortest_path_len))
+                }
+                _ => None,
+            })
+            .collect();
+        reached.sort_unstable();
+
+        assert_eq!(reached, vec![(3, 2), (4, 3)]);
+    }
+
+    #[test]
+    fn reachable_set_has_shortest_distances() {
+        let data = QueryData {
+            reachable_set: true,
+            round_limit: Some(2),
+            ..Default::default()
+        };
+
+        let (processor, _) = first_round(&data);
+        let graph = &processor.fragment().graph;
+
+        let mut reachable: Vec<_> = processor
+            .reachable(2)
+            .into_iter()
+            .map(|(idx, shortest)| (graph.node_id(idx), shortest))
+            .collect();
+        reachable.sort_unstable();
+
+        assert_eq!(reachable, vec![(1, 0), (2, 1), (3, 2)]);
+    }
+}
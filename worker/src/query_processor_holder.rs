@@ -1,89 +1,224 @@
 use std::collections::HashMap;
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 use tonic::Status;
 
+use crate::globals::{self, Fragment};
 use crate::query_processor::{QueryId, QueryProcessor};
 use crate::ErrorCollection;
 
 use generated::worker::request_djikstra::QueryData;
 
+// Expired entries are kept this many TTLs, so that late requests get a meaningful error instead of
+// silently starting the query from scratch.
+const EXPIRED_ENTRY_LIFETIME_FACTOR: u32 = 10;
+
+const MIN_SWEEP_PERIOD: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone)]
+pub struct ExpiryConfig {
+    // Idle time after which the query is evicted
+    pub ttl: Duration,
+    // Limit of memory held by idle queries; above it the least recently used ones are evicted
+    pub memory_limit: Option<usize>,
+}
+
+// Limits checked before a new query is admitted
+#[derive(Debug, Clone)]
+pub struct AdmissionConfig {
+    pub max_live_queries: Option<usize>,
+    // Limit of memory held by all live queries, both idle and pending
+    pub memory_budget: Option<usize>,
+}
+
 #[derive(Debug)]
 pub struct QueryProcessorHolder {
     processors_map: Mutex<QueryProcessorMap>,
+    config: ExpiryConfig,
+    admission: AdmissionConfig,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum EvictionReason {
+    Idle,
+    MemoryPressure,
 }
 
 #[derive(Debug)]
 enum QueryProcessorEntry {
-    Busy, // The query is pending
-    Ready(QueryProcessor),
+    // The query is pending; `memory` is the usage known when the processor was taken out
+    Busy {
+        memory: usize,
+    },
+    Ready {
+        processor: Box<QueryProcessor>,
+        last_used: Instant,
+        memory: usize,
+    },
+    Expired {
+        since: Instant,
+        reason: EvictionReason,
+    },
+}
+
+#[derive(Debug, Default)]
+struct QueryProcessorMap {
+    entries: HashMap<QueryId, QueryProcessorEntry>,
+    // Sum of `memory` of the ready entries
+    ready_memory: usize,
+    // Sum of `memory` of the busy entries
+    busy_memory: usize,
+    // Number of the busy and ready entries
+    live: usize,
 }
 
-type QueryProcessorMap = HashMap<QueryId, QueryProcessorEntry>;
+impl QueryProcessorMap {
+    // Takes the processor out of the entry, leaving it busy
+    fn take(&mut self, query_id: QueryId) -> Result<Option<QueryProcessor>, Status> {
+        use std::collections::hash_map::Entry::{Occupied, Vacant};
+        use QueryProcessorEntry::{Busy, Expired, Ready};
+
+        let mut entry = match self.entries.entry(query_id) {
+            Vacant(_) => return Ok(None),
+            Occupied(entry) => entry,
+        };
+
+        match entry.get() {
+            // If it is busy, than there is an error in Executer; new request for this query came
+            // before previous was finished.
+            Busy { .. } => return Err(ErrorCollection::processor_busy(query_id)),
+            Expired { reason, .. } => {
+                return Err(ErrorCollection::query_expired(query_id, *reason))
+            }
+            Ready { .. } => (),
+        }
+
+        let memory = match entry.get() {
+            Ready { memory, .. } => *memory,
+            _ => unreachable!(),
+        };
+
+        match entry.insert(Busy { memory }) {
+            Ready { processor, .. } => {
+                self.ready_memory -= memory;
+                self.busy_memory += memory;
+                Ok(Some(*processor))
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    // `reserved` is the memory the new query is expected to take
+    fn admit(&self, admission: &AdmissionConfig, reserved: usize) -> Result<(), Status> {
+        if let Some(max_live_queries) = admission.max_live_queries {
+            if self.live >= max_live_queries {
+                return Err(ErrorCollection::too_many_queries(max_live_queries));
+            }
+        }
+
+        if let Some(budget) = admission.memory_budget {
+            let used = self.ready_memory + self.busy_memory;
+
+            if used + reserved > budget {
+                return Err(ErrorCollection::memory_budget_exceeded(used, budget));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn evict(&mut self, query_id: QueryId, now: Instant, reason: EvictionReason) {
+        let entry = self.entries.get_mut(&query_id).unwrap();
+        let expired = QueryProcessorEntry::Expired { since: now, reason };
+
+        if let QueryProcessorEntry::Ready { memory, .. } = std::mem::replace(entry, expired) {
+            self.ready_memory -= memory;
+            self.live -= 1;
+        }
+
+        info!("evicted query[id: {query_id}] ({reason:?})");
+    }
+
+    // Evicts the least recently used queries until the memory held by them fits in the limit
+    fn enforce_memory_limit(&mut self, limit: usize, now: Instant) {
+        if self.ready_memory <= limit {
+            return;
+        }
+
+        let mut ready: Vec<(Instant, QueryId)> = self
+            .entries
+            .iter()
+            .filter_map(|(query_id, entry)| match entry {
+                QueryProcessorEntry::Ready { last_used, .. } => Some((*last_used, *query_id)),
+                _ => None,
+            })
+            .collect();
+
+        ready.sort_unstable();
+
+        for (_, query_id) in ready {
+            if self.ready_memory <= limit {
+                break;
+            }
+
+            self.evict(query_id, now, EvictionReason::MemoryPressure);
+        }
+    }
+}
 
 impl QueryProcessorHolder {
-    pub fn new() -> Self {
+    pub fn new(config: ExpiryConfig, admission: AdmissionConfig) -> Self {
         QueryProcessorHolder {
-            processors_map: Mutex::new(QueryProcessorMap::new()),
+            processors_map: Mutex::new(QueryProcessorMap::default()),
+            config,
+            admission,
         }
     }
 
     pub fn get_existing(&self, query_id: QueryId) -> Result<Option<QueryProcessor>, Status> {
-        use std::collections::hash_map::Entry::{Occupied, Vacant};
-        use QueryProcessorEntry::{Busy, Ready};
-
         let mut queries = self
             .processors_map
             .lock()
             .map_err(ErrorCollection::locking_mutex)?;
 
-        let processor = match queries.entry(query_id) {
-            Vacant(_) => None,
-            Occupied(mut entry) => {
-                // QueryProcessor for this query_id was already created.
-                match entry.insert(Busy) {
-                    Ready(processor) => Some(processor),
-                    // If it is busy, than there is an error in Executer; new request for this
-                    // query came before previous was finished.
-                    Busy => return Err(ErrorCollection::processor_busy()),
-                }
-            }
-        };
-
-        Ok(processor)
+        queries.take(query_id)
     }
 
-    // Gets QueryProcessor for the query. If this is the first request for this query, creates new.
+    // Gets QueryProcessor for the query. If this is the first request for this query, creates new,
+    // unless the admission limits are hit.
     pub fn get_or_create(&self, query_data: &QueryData) -> Result<QueryProcessor, Status> {
-        use std::collections::hash_map::Entry::{Occupied, Vacant};
-        use QueryProcessorEntry::{Busy, Ready};
+        self.get_or_create_in(query_data, globals::fragment())
+    }
 
+    // New queries start on `fragment`
+    fn get_or_create_in(
+        &self,
+        query_data: &QueryData,
+        fragment: Arc<Fragment>,
+    ) -> Result<QueryProcessor, Status> {
         let mut queries = self
             .processors_map
             .lock()
             .map_err(ErrorCollection::locking_mutex)?;
 
-        let processor = match queries.entry(query_data.query_id) {
-            // No QueryProcessor for this query_id was created, but we will create one soon, so
-            // insert Busy into the holder.
-            Vacant(entry) => {
-                entry.insert(Busy);
-                None
-            }
-            Occupied(mut entry) => {
-                // QueryProcessor for this query_id was already created.
-                match entry.insert(Busy) {
-                    Ready(processor) => Some(processor),
-                    // If it is busy, than there is an error in Executer; new request for this
-                    // query came before previous was finished.
-                    Busy => return Err(ErrorCollection::processor_busy()),
-                }
-            }
-        };
+        let processor = queries.take(query_data.query_id)?;
 
         Ok(match processor {
             Some(processor) => processor,
-            None => QueryProcessor::new(query_data),
+            // No QueryProcessor for this query_id was created, but we will create one soon, so
+            // insert Busy into the holder.
+            None => {
+                let memory = QueryProcessor::initial_memory_usage(query_data, &fragment);
+
+                queries.admit(&self.admission, memory)?;
+                queries
+                    .entries
+                    .insert(query_data.query_id, QueryProcessorEntry::Busy { memory });
+                queries.busy_memory += memory;
+                queries.live += 1;
+                QueryProcessor::new(query_data, fragment)
+            }
         })
     }
 
@@ -96,15 +231,48 @@ impl QueryProcessorHolder {
             .map_err(ErrorCollection::locking_mutex)
             .unwrap();
 
-        let mut entry = match processor_map.entry(processor.query_id()) {
-            Occupied(entry) => entry,
-            Vacant(_) => unreachable!(),
+        use QueryProcessorEntry::{Busy, Ready};
+
+        let now = Instant::now();
+        let memory = processor.memory_usage();
+        let query_id = processor.query_id();
+
+        // The executer may forget the query while its request is still pending, eg. after another
+        // request of the query failed. Then the processor is no longer needed.
+        let mut entry = match processor_map.entries.entry(query_id) {
+            Occupied(entry) if matches!(entry.get(), Busy { .. }) => entry,
+            Occupied(_) | Vacant(_) => {
+                warn!("query[id: {query_id}] was forgotten while pending; dropping its processor");
+                return;
+            }
         };
 
-        use QueryProcessorEntry::{Busy, Ready};
+        let previous_value = entry.insert(Ready {
+            processor: Box::new(processor),
+            last_used: now,
+            memory,
+        });
 
-        let previous_value = entry.insert(Ready(processor));
-        debug_assert!(matches!(previous_value, Busy));
+        match previous_value {
+            Busy { memory } => processor_map.busy_memory -= memory,
+            _ => unreachable!(),
+        }
+
+        processor_map.ready_memory += memory;
+
+        if let Some(limit) = self.config.memory_limit {
+            processor_map.enforce_memory_limit(limit, now);
+        }
+    }
+
+    // Forgets the query when dropped, unless disarmed. Guards a request which takes the processor
+    // out, as its future is dropped when the executer disconnects before the processor is put back.
+    pub fn busy_guard(&self, query_id: QueryId) -> BusyGuard<'_> {
+        BusyGuard {
+            holder: self,
+            query_id,
+            armed: true,
+        }
     }
 
     // Drops the query processor and all information about this query
@@ -117,21 +285,371 @@ impl QueryProcessorHolder {
             .map_err(ErrorCollection::locking_mutex)
             .unwrap();
 
-        match processor_map.entry(query_id) {
-            Occupied(entry) => {
-                entry.remove();
-            }
+        match processor_map.entries.entry(query_id) {
+            Occupied(entry) => match entry.remove() {
+                QueryProcessorEntry::Ready { memory, .. } => {
+                    processor_map.ready_memory -= memory;
+                    processor_map.live -= 1;
+                }
+                QueryProcessorEntry::Busy { memory } => {
+                    processor_map.busy_memory -= memory;
+                    processor_map.live -= 1;
+                }
+                QueryProcessorEntry::Expired { .. } => (),
+            },
             Vacant(_) => warn!("Forgetting non-existent (or already forgotten) query"),
         };
     }
+
+    // Evicts queries idle for longer than the TTL and drops old expired entries
+    fn sweep(&self, now: Instant) {
+        let mut processor_map = self
+            .processors_map
+            .lock()
+            .map_err(ErrorCollection::locking_mutex)
+            .unwrap();
+
+        let ttl = self.config.ttl;
+        let expired_lifetime = ttl * EXPIRED_ENTRY_LIFETIME_FACTOR;
+
+        processor_map.entries.retain(|_, entry| match entry {
+            QueryProcessorEntry::Expired { since, .. } => {
+                now.duration_since(*since) < expired_lifetime
+            }
+            _ => true,
+        });
+
+        let idle: Vec<QueryId> = processor_map
+            .entries
+            .iter()
+            .filter_map(|(query_id, entry)| match entry {
+                QueryProcessorEntry::Ready { last_used, .. }
+                    if now.duration_since(*last_used) >= ttl =>
+                {
+                    Some(*query_id)
+                }
+                _ => None,
+            })
+            .collect();
+
+        for query_id in idle {
+            processor_map.evict(query_id, now, EvictionReason::Idle);
+        }
+
+        if let Some(limit) = self.config.memory_limit {
+            processor_map.enforce_memory_limit(limit, now);
+        }
+
+        debug!(
+            "swept query processors: {} entries, {} bytes held by idle queries",
+            processor_map.entries.len(),
+            processor_map.ready_memory
+        );
+    }
+
+    // Never returns; should be spawned as a separate task.
+    pub async fn run_sweeper(&self) {
+        let period = std::cmp::max(self.config.ttl / 4, MIN_SWEEP_PERIOD);
+        let mut interval = tokio::time::interval(period);
+
+        loop {
+            interval.tick().await;
+            self.sweep(Instant::now());
+        }
+    }
+}
+
+pub struct BusyGuard<'a> {
+    holder: &'a QueryProcessorHolder,
+    query_id: QueryId,
+    armed: bool,
+}
+
+impl BusyGuard<'_> {
+    pub fn disarm(mut self) {
+        self.armed = false;
+    }
+}
+
+impl Drop for BusyGuard<'_> {
+    fn drop(&mut self) {
+        if self.armed {
+            self.holder.forget_query(self.query_id);
+        }
+    }
 }
 
 impl ErrorCollection {
-    fn processor_busy() -> Status {
-        Status::invalid_argument("Cannot get busy QueryProcessor")
+    fn processor_busy(query_id: QueryId) -> Status {
+        Status::invalid_argument(format!(
+            "Cannot get busy QueryProcessor of query[id: {query_id}]; another request on this \
+            query is already pending"
+        ))
+    }
+
+    fn too_many_queries(max_live_queries: usize) -> Status {
+        Status::resource_exhausted(format!(
+            "worker already holds the maximum of {max_live_queries} live queries"
+        ))
+    }
+
+    fn memory_budget_exceeded(used: usize, budget: usize) -> Status {
+        Status::resource_exhausted(format!(
+            "live queries hold {used} bytes, which exhausts the budget of {budget} bytes"
+        ))
+    }
+
+    fn query_expired(query_id: QueryId, reason: EvictionReason) -> Status {
+        let reason = match reason {
+            EvictionReason::Idle => "it was idle for too long",
+            EvictionReason::MemoryPressure => "idle queries used too much memory",
+        };
+
+        Status::failed_precondition(format!(
+            "query[id: {query_id}] expired and was evicted, because {reason}"
+        ))
     }
 
     fn locking_mutex(e: impl std::error::Error) -> Status {
         Status::internal(format!("Internal error while locking mutex: {e}"))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph_store::{SPQGraphBuilder, TestRng};
+
+    use tonic::Code;
+
+    const TTL: Duration = Duration::from_secs(60);
+
+    fn fragment() -> Arc<Fragment> {
+        let builder = SPQGraphBuilder::grid((50., 19.), 10, 0.01, 0., &mut TestRng::new());
+        let (graph, mapping) = builder.build().unwrap();
+        Arc::new(Fragment::new(graph, mapping, 1))
+    }
+
+    fn holder(memory_limit: Option<usize>, admission: AdmissionConfig) -> QueryProcessorHolder {
+        let config = ExpiryConfig {
+            ttl: TTL,
+            memory_limit,
+        };
+
+        QueryProcessorHolder::new(config, admission)
+    }
+
+    fn unlimited() -> AdmissionConfig {
+        AdmissionConfig {
+            max_live_queries: None,
+            memory_budget: None,
+        }
+    }
+
+    fn query(query_id: QueryId) -> QueryData {
+        QueryData {
+            query_id,
+            final_node_id: 99,
+            ..Default::default()
+        }
+    }
+
+    // `ready_memory`, `busy_memory` and `live`
+    fn counters(holder: &QueryProcessorHolder) -> (usize, usize, usize) {
+        let map = holder.processors_map.lock().unwrap();
+        (map.ready_memory, map.busy_memory, map.live)
+    }
+
+    // Creates the query and puts it back; returns the memory it holds
+    fn start(holder: &QueryProcessorHolder, fragment: &Arc<Fragment>, query_id: QueryId) -> usize {
+        let processor = holder
+            .get_or_create_in(&query(query_id), fragment.clone())
+            .unwrap();
+        let memory = processor.memory_usage();
+
+        holder.put_back_query(processor);
+        memory
+    }
+
+    fn assert_expired(holder: &QueryProcessorHolder, query_id: QueryId) {
+        let error = holder.get_existing(query_id).unwrap_err();
+
+        assert_eq!(error.code(), Code::FailedPrecondition);
+        assert!(error.message().contains("expired"));
+    }
+
+    #[test]
+    fn idle_query_is_evicted_after_ttl() {
+        let (holder, fragment) = (holder(None, unlimited()), fragment());
+        let memory = start(&holder, &fragment, 1);
+
+        holder.sweep(Instant::now());
+        assert_eq!(counters(&holder), (memory, 0, 1));
+
+        holder.sweep(Instant::now() + TTL);
+        assert_eq!(counters(&holder), (0, 0, 0));
+        assert_expired(&holder, 1);
+
+        // Expired entries are dropped later, then the query is unknown
+        holder.sweep(Instant::now() + TTL * (EXPIRED_ENTRY_LIFETIME_FACTOR + 2));
+        assert!(holder.get_existing(1).unwrap().is_none());
+    }
+
+    #[test]
+    fn pending_query_is_not_evicted() {
+        let (holder, fragment) = (holder(None, unlimited()), fragment());
+        start(&holder, &fragment, 1);
+
+        let processor = holder.get_existing(1).unwrap().unwrap();
+        holder.sweep(Instant::now() + TTL);
+        holder.put_back_query(processor);
+
+        assert!(holder.get_existing(1).unwrap().is_some());
+    }
+
+    #[test]
+    fn least_recently_used_queries_are_evicted_over_memory_limit() {
+        let (mut holder, fragment) = (holder(None, unlimited()), fragment());
+
+        // Pooled search buffers may be larger than the fragment, so the memory is measured
+        let processors: Vec<QueryProcessor> = (1..=3)
+            .map(|query_id| {
+                holder
+                    .get_or_create_in(&query(query_id), fragment.clone())
+                    .unwrap()
+            })
+            .collect();
+        let memory: Vec<usize> = processors.iter().map(|p| p.memory_usage()).collect();
+
+        // Only the third query does not fit
+        holder.config.memory_limit = Some(memory.iter().sum::<usize>() - 1);
+
+        let mut processors = processors.into_iter();
+        holder.put_back_query(processors.next().unwrap());
+        std::thread::sleep(Duration::from_millis(1));
+        holder.put_back_query(processors.next().unwrap());
+        std::thread::sleep(Duration::from_millis(1));
+
+        // Using the first query makes the second one the least recently used
+        let processor = holder.get_existing(1).unwrap().unwrap();
+        holder.put_back_query(processor);
+        std::thread::sleep(Duration::from_millis(1));
+
+        holder.put_back_query(processors.next().unwrap());
+
+        assert_eq!(counters(&holder), (memory[0] + memory[2], 0, 2));
+        assert_expired(&holder, 2);
+
+        for query_id in [1, 3] {
+            let processor = holder.get_existing(query_id).unwrap().unwrap();
+            holder.put_back_query(processor);
+        }
+    }
+
+    #[test]
+    fn counters_return_to_zero_after_forget() {
+        let (holder, fragment) = (holder(None, unlimited()), fragment());
+        let first = start(&holder, &fragment, 1);
+        let second = start(&holder, &fragment, 2);
+
+        let busy = holder.get_existing(2).unwrap().unwrap();
+        assert_eq!(counters(&holder), (first, second, 2));
+
+        holder.forget_query(1);
+        assert_eq!(counters(&holder), (0, second, 1));
+
+        // The executer may forget the query while it is pending
+        holder.forget_query(2);
+        assert_eq!(counters(&holder), (0, 0, 0));
+        drop(busy);
+
+        // Expired queries are already not counted
+        start(&holder, &fragment, 3);
+        holder.sweep(Instant::now() + TTL);
+        holder.forget_query(3);
+        assert_eq!(counters(&holder), (0, 0, 0));
+        assert!(holder.processors_map.lock().unwrap().entries.is_empty());
+    }
+
+    #[test]
+    fn query_forgotten_while_pending_is_dropped() {
+        let (holder, fragment) = (holder(None, unlimited()), fragment());
+        let processor = holder.get_or_create_in(&query(1), fragment).unwrap();
+
+        holder.forget_query(1);
+        holder.put_back_query(processor);
+
+        assert_eq!(counters(&holder), (0, 0, 0));
+        assert!(holder.get_existing(1).unwrap().is_none());
+    }
+
+    #[test]
+    fn too_many_live_queries_are_rejected() {
+        let admission = AdmissionConfig {
+            max_live_queries: Some(2),
+            memory_budget: None,
+        };
+        let (holder, fragment) = (holder(None, admission), fragment());
+        let memory = start(&holder, &fragment, 1);
+        let pending = holder
+            .get_or_create_in(&query(2), fragment.clone())
+            .unwrap();
+
+        let error = holder
+            .get_or_create_in(&query(3), fragment.clone())
+            .unwrap_err();
+        assert_eq!(error.code(), Code::ResourceExhausted);
+
+        // The rejected query leaves no trace
+        let initial = QueryProcessor::initial_memory_usage(&query(2), &fragment);
+        assert_eq!(counters(&holder), (memory, initial, 2));
+        assert!(holder.get_existing(3).unwrap().is_none());
+
+        // Existing queries are not subject to the admission
+        holder.put_back_query(pending);
+        assert!(holder.get_or_create_in(&query(1), fragment.clone()).is_ok());
+
+        holder.forget_query(2);
+        assert!(holder.get_or_create_in(&query(3), fragment).is_ok());
+    }
+
+    #[test]
+    fn queries_over_memory_budget_are_rejected() {
+        let fragment = fragment();
+        let initial = QueryProcessor::initial_memory_usage(&query(1), &fragment);
+        let admission = AdmissionConfig {
+            max_live_queries: None,
+            memory_budget: Some(2 * initial),
+        };
+        let holder = holder(None, admission);
+
+        let first = holder
+            .get_or_create_in(&query(1), fragment.clone())
+            .unwrap();
+        let second = holder
+            .get_or_create_in(&query(2), fragment.clone())
+            .unwrap();
+
+        let error = holder
+            .get_or_create_in(&query(3), fragment.clone())
+            .unwrap_err();
+        assert_eq!(error.code(), Code::ResourceExhausted);
+        assert_eq!(counters(&holder), (0, 2 * initial, 2));
+
+        // Queries put back still count against the budget
+        let memory = first.memory_usage() + second.memory_usage();
+        holder.put_back_query(first);
+        holder.put_back_query(second);
+
+        let error = holder
+            .get_or_create_in(&query(3), fragment.clone())
+            .unwrap_err();
+        assert_eq!(error.code(), Code::ResourceExhausted);
+        assert_eq!(counters(&holder), (memory, 0, 2));
+
+        holder.forget_query(1);
+        holder.forget_query(2);
+        assert_eq!(counters(&holder), (0, 0, 0));
+        assert!(holder.get_or_create_in(&query(3), fragment).is_ok());
+    }
+}
@@ -2,7 +2,6 @@ use futures::Stream;
 use tonic::Status;
 
 use crate::globals;
-use crate::graph_store;
 use crate::proto_helpers;
 use crate::query_processor;
 use crate::worker_service;
@@ -17,7 +16,7 @@ async fn apply_update(
     processor: &mut query_processor::QueryProcessor,
     inbound: &mut worker_service::RequestDjikstraStream,
 ) -> Result<(), Status> {
-    processor.update_smallest_foreign(data.smallest_foreign_node);
+    processor.start_round(data);
 
     while let Some(message) = inbound.message().await? {
         use query_processor::NodeParent;
@@ -89,8 +88,14 @@ pub fn get_backtrack_stream(
     let worker::RequestBacktrack {
         query_id,
         from_node,
+        backward,
     } = request;
 
+    let direction = match backward {
+        true => query_processor::Direction::Backward,
+        false => query_processor::Direction::Forward,
+    };
+
     async_stream::try_stream! {
         use worker::ResponseBacktrack;
         use query_processor::NodeParent;
@@ -99,49 +104,63 @@ pub fn get_backtrack_stream(
             .get_existing(query_id)?
             .ok_or_else(|| ErrorCollection::query_not_found(query_id))?;
 
-        let mut next_node = Some(from_node);
-
-        let map_idx_to_id = |idx| -> graph_store::NodeId {
-            globals::graph().get_node(idx).id
-        };
+        let parents = processor.backtrack(from_node, direction);
+        let fragment = processor.fragment().clone();
 
-        while let Some(current_node) = next_node {
-            let parent = processor
-                .get_parent(current_node)
-                .ok_or_else(|| ErrorCollection::cannot_find_parent(current_node));
-
-            if let Err(_) = parent {
-                globals::processor_holder().put_back_query(processor);
-                warn!("cannot find parent of {current_node}");
-                parent?; // Due to the limitations of `try_stream!`
-                return;
-            }
+        globals::processor_holder().put_back_query(processor);
 
-            let parent = parent?;
+        let parents = parents.map_err(|status| {
+            warn!("cannot backtrack from {from_node}: {}", status.message());
+            status
+        })?;
 
-            next_node = match parent {
-                NodeParent::Root => None,
+        for parent in parents {
+            match parent {
+                NodeParent::Root => (),
                 NodeParent::Foreign(id, worker) => {
                     yield ResponseBacktrack { node_id: id, worker_id: Some(worker) };
-                    None
                 }
                 NodeParent::Domestic(idx) => {
-                    let id = map_idx_to_id(idx);
+                    let id = fragment.graph.node_id(idx);
                     yield ResponseBacktrack { node_id: id, worker_id: None };
-                    Some(id)
                 }
             }
         }
+    }
+}
+
+pub fn get_reachable_stream(
+    request: worker::RequestReachable,
+) -> impl Stream<Item = Result<worker::ReachableNode, Status>> + Send + 'static {
+    debug!("GetReachable request: {request:?}");
+
+    let worker::RequestReachable { query_id, budget } = request;
+
+    async_stream::try_stream! {
+        let processor = globals::processor_holder()
+            .get_existing(query_id)?
+            .ok_or_else(|| ErrorCollection::query_not_found(query_id))?;
+
+        let reachable = processor.reachable(budget);
+        let fragment = processor.fragment().clone();
 
         globals::processor_holder().put_back_query(processor);
+
+        debug!("{} nodes reachable within {budget}", reachable.len());
+
+        for (idx, shortest_path_len) in reachable {
+            let (lat, lon) = fragment.graph.coords(idx);
+
+            yield worker::ReachableNode {
+                node_id: fragment.graph.node_id(idx),
+                shortest_path_len,
+                coords: Some(worker::Coordinates { lat, lon }),
+            };
+        }
     }
 }
 
 impl ErrorCollection {
-    fn cannot_find_parent(node_id: graph_store::NodeId) -> Status {
-        Status::not_found(format!("cannot find parent of node[idx: {node_id}]"))
-    }
-
     fn duplicated_query_data() -> Status {
         Status::invalid_argument("duplicated QueryData in the middle of the stream")
     }
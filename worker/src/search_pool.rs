@@ -0,0 +1,204 @@
+use std::collections::BinaryHeap;
+use std::mem;
+use std::sync::Mutex;
+
+use crate::graph_store::{NodeIdx, ShortestPathLen};
+use crate::query_processor::QueueElement;
+
+// Buffers kept for reuse; more released buffers are freed
+const MAX_POOLED_BUFFERS: usize = 8;
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DenseVisit {
+    generation: u32,
+    pub parent: NodeIdx,
+    pub shortest: ShortestPathLen,
+}
+
+// Visits of a single search, indexed by `NodeIdx`. Visits stamped with another generation are
+// stale, so the array can be reused without clearing it.
+#[derive(Debug, Default)]
+pub struct VisitArray {
+    generation: u32,
+    visits: Vec<DenseVisit>,
+}
+
+impl VisitArray {
+    pub fn get(&self, idx: NodeIdx) -> Option<&DenseVisit> {
+        self.visits
+            .get(idx as usize)
+            .filter(|visit| visit.generation == self.generation)
+    }
+
+    // Returns false if the node was already visited.
+    pub fn insert(&mut self, idx: NodeIdx, parent: NodeIdx, shortest: ShortestPathLen) -> bool {
+        let visit = &mut self.visits[idx as usize];
+
+        if visit.generation == self.generation {
+            return false;
+        }
+
+        *visit = DenseVisit {
+            generation: self.generation,
+            parent,
+            shortest,
+        };
+
+        true
+    }
+
+    // Like `insert`, but also overwrites a visit with a longer path. Returns false if the visit
+    // was kept.
+    pub fn relax(&mut self, idx: NodeIdx, parent: NodeIdx, shortest: ShortestPathLen) -> bool {
+        let visit = &self.visits[idx as usize];
+
+        if visit.generation == self.generation && visit.shortest <= shortest {
+            return false;
+        }
+
+        self.visits[idx as usize] = DenseVisit {
+            generation: self.generation,
+            parent,
+            shortest,
+        };
+
+        true
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (NodeIdx, &DenseVisit)> + '_ {
+        self.visits
+            .iter()
+            .enumerate()
+            .filter(|(_, visit)| visit.generation == self.generation)
+            .map(|(idx, visit)| (idx as NodeIdx, visit))
+    }
+
+    pub fn memory_usage(&self) -> usize {
+        self.visits.capacity() * mem::size_of::<DenseVisit>()
+    }
+
+    // Invalidates all visits; the array may come from a previous version of the fragment.
+    fn reset(&mut self, nodes_count: usize) {
+        if self.generation == u32::MAX {
+            self.visits.clear();
+            self.generation = 0;
+        }
+
+        self.generation += 1;
+        self.visits.resize(nodes_count, DenseVisit::default());
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct SearchBuffers {
+    pub visits: VisitArray,
+    pub queue: BinaryHeap<QueueElement>,
+}
+
+// Per-worker pool of the search buffers, so that queries do not allocate them from scratch.
+#[derive(Debug)]
+pub struct SearchPool {
+    buffers: Mutex<Vec<SearchBuffers>>,
+}
+
+impl SearchPool {
+    pub const fn new() -> Self {
+        SearchPool {
+            buffers: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn acquire(&self, nodes_count: usize) -> SearchBuffers {
+        let mut buffers = self.buffers.lock().unwrap().pop().unwrap_or_default();
+
+        buffers.visits.reset(nodes_count);
+        buffers.queue.clear();
+
+        buffers
+    }
+
+    pub fn release(&self, buffers: SearchBuffers) {
+        // Unused searches hold no memory
+        if buffers.visits.visits.is_empty() {
+            return;
+        }
+
+        let mut pooled = self.buffers.lock().unwrap();
+
+        if pooled.len() < MAX_POOLED_BUFFERS {
+            pooled.push(buffers);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn visited(visits: &VisitArray) -> Vec<NodeIdx> {
+        visits.iter().map(|(idx, _)| idx).collect()
+    }
+
+    #[test]
+    fn reused_buffers_have_no_visits() {
+        let pool = SearchPool::new();
+
+        let mut buffers = pool.acquire(4);
+        assert!(buffers.visits.insert(1, 0, 10));
+        assert!(buffers.visits.insert(3, 1, 20));
+        pool.release(buffers);
+
+        let mut buffers = pool.acquire(4);
+        assert_eq!(buffers.visits.generation, 2);
+        assert!(visited(&buffers.visits).is_empty());
+        assert!(buffers.visits.get(1).is_none());
+
+        assert!(buffers.visits.insert(1, 2, 5));
+        assert!(!buffers.visits.insert(1, 3, 1));
+        assert_eq!(buffers.visits.get(1).unwrap().parent, 2);
+    }
+
+    #[test]
+    fn reused_buffers_fit_another_fragment() {
+        let pool = SearchPool::new();
+
+        let mut buffers = pool.acquire(8);
+        assert!(buffers.visits.insert(7, 0, 1));
+        pool.release(buffers);
+
+        let mut buffers = pool.acquire(3);
+        assert!(buffers.visits.get(7).is_none());
+        assert!(buffers.visits.insert(2, 0, 1));
+        pool.release(buffers);
+
+        let buffers = pool.acquire(16);
+        assert!(visited(&buffers.visits).is_empty());
+        assert!(buffers.visits.memory_usage() >= 16 * mem::size_of::<DenseVisit>());
+    }
+
+    #[test]
+    fn generation_wraparound_clears_visits() {
+        let mut visits = VisitArray::default();
+        visits.reset(4);
+        assert!(visits.insert(2, 0, 1));
+
+        // A visit stamped with the first generation after the wraparound would look fresh
+        visits.visits[3].generation = 1;
+        visits.generation = u32::MAX;
+        visits.reset(4);
+
+        assert_eq!(visits.generation, 1);
+        assert!(visited(&visits).is_empty());
+    }
+
+    #[test]
+    fn relax_keeps_shorter_visit() {
+        let mut visits = VisitArray::default();
+        visits.reset(2);
+
+        assert!(visits.relax(0, 1, 10));
+        assert!(!visits.relax(0, 1, 12));
+        assert!(visits.relax(0, 0, 7));
+        assert_eq!(visits.get(0).unwrap().shortest, 7);
+    }
+}
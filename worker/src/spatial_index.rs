@@ -0,0 +1,380 @@
+use std::collections::HashMap;
+
+use tonic::Status;
+
+use generated::worker::{Coordinates, NearNode, NearestNodesRequest, NearestNodesResult};
+
+use crate::globals::{self, Fragment};
+use crate::graph_store::{NodeIdx, SPQGraph, SomeGraphMethods};
+use crate::ErrorCollection;
+
+// Side of a grid cell, in degrees
+const CELL_DEGREES: f64 = 0.01;
+
+const EARTH_RADIUS_METERS: f64 = 6_371_000.;
+
+// Slightly less than a degree of latitude, so that the bounds derived from it stay lower bounds
+const METERS_PER_DEGREE: f64 = 111_000.;
+
+// Latitudes closer to the poles are clamped when bounding the width of the cells
+const MAX_BOUNDED_LATITUDE: f64 = 89.;
+
+type Cell = (i32, i32);
+
+fn cell(coords: (f64, f64)) -> Cell {
+    (
+        (coords.0 / CELL_DEGREES).floor() as i32,
+        (coords.1 / CELL_DEGREES).floor() as i32,
+    )
+}
+
+// Great-circle distance between two (lat, lon) points
+fn distance_meters(from: (f64, f64), to: (f64, f64)) -> f64 {
+    let (lat_from, lon_from) = (from.0.to_radians(), from.1.to_radians());
+    let (lat_to, lon_to) = (to.0.to_radians(), to.1.to_radians());
+
+    let haversine = ((lat_to - lat_from) / 2.).sin().powi(2)
+        + lat_from.cos() * lat_to.cos() * ((lon_to - lon_from) / 2.).sin().powi(2);
+
+    2. * EARTH_RADIUS_METERS * haversine.sqrt().min(1.).asin()
+}
+
+// Uniform grid over the coordinates of the domestic nodes
+#[derive(Debug)]
+pub struct SpatialIndex {
+    cells: HashMap<Cell, Vec<NodeIdx>>,
+    // Corners of the bounding box of the non-empty cells
+    min: Cell,
+    max: Cell,
+}
+
+impl SpatialIndex {
+    pub fn build(graph: &SPQGraph) -> Self {
+        let mut cells: HashMap<Cell, Vec<NodeIdx>> = HashMap::new();
+
+        for idx in 0..graph.ids.len() as NodeIdx {
+            cells.entry(cell(graph.coords(idx))).or_default().push(idx);
+        }
+
+        let min = cells.keys().fold((i32::MAX, i32::MAX), |min, cell| {
+            (min.0.min(cell.0), min.1.min(cell.1))
+        });
+        let max = cells.keys().fold((i32::MIN, i32::MIN), |max, cell| {
+            (max.0.max(cell.0), max.1.max(cell.1))
+        });
+
+        info!(
+            "built spatial index of {} nodes in {} cells",
+            graph.ids.len(),
+            cells.len()
+        );
+
+        SpatialIndex { cells, min, max }
+    }
+
+    // Up to `k` nodes closest to `coords` and not further than `max_radius`, closest first; only
+    // the nodes with open edges if `routable`. Searches the rings of cells around the cell of
+    // `coords`, until the nodes in the further rings cannot be closer than those found.
+    pub fn nearest(
+        &self,
+        fragment: &Fragment,
+        coords: (f64, f64),
+        k: usize,
+        max_radius: Option<f64>,
+        routable: bool,
+    ) -> Vec<(NodeIdx, f64)> {
+        let mut found: Vec<(NodeIdx, f64)> = Vec::new();
+
+        if k == 0 || self.cells.is_empty() {
+            return found;
+        }
+
+        let center = cell(coords);
+
+        let is_routable = |idx| {
+            fragment.edges(idx).next().is_some() || fragment.reverse_edges(idx).next().is_some()
+        };
+
+        let consider = |found: &mut Vec<(NodeIdx, f64)>, nodes: &[NodeIdx]| {
+            for &idx in nodes {
+                if routable && !is_routable(idx) {
+                    continue;
+                }
+
+                let distance = distance_meters(coords, fragment.graph.coords(idx));
+
+                if !matches!(max_radius, Some(max_radius) if distance > max_radius) {
+                    found.push((idx, distance));
+                }
+            }
+        };
+
+        // Rings further than this one hold no cells
+        let last_ring = [
+            center.0 - self.min.0,
+            self.max.0 - center.0,
+            center.1 - self.min.1,
+            self.max.1 - center.1,
+        ]
+        .into_iter()
+        .max()
+        .unwrap()
+        .max(0);
+
+        for ring in 0..=last_ring {
+            // Nodes in this and further rings are at least that far; cells narrow towards the
+            // poles.
+            let latitude = (coords.0.abs() + ring as f64 * CELL_DEGREES).min(MAX_BOUNDED_LATITUDE);
+            let bound = (ring - 1).max(0) as f64
+                * CELL_DEGREES
+                * METERS_PER_DEGREE
+                * latitude.to_radians().cos();
+
+            let enough = found.len() >= k && found[k - 1].1 <= bound;
+
+            if enough || matches!(max_radius, Some(max_radius) if bound > max_radius) {
+                break;
+            }
+
+            // Far from the fragment most cells in the ring are empty; then it is cheaper to go
+            // through all the remaining cells at once.
+            if 8 * ring as usize > self.cells.len() {
+                for (cell, nodes) in self.cells.iter() {
+                    let distance = (cell.0 - center.0).abs().max((cell.1 - center.1).abs());
+
+                    if distance >= ring {
+                        consider(&mut found, nodes);
+                    }
+                }
+
+                found.sort_by(|a, b| a.1.total_cmp(&b.1));
+                found.truncate(k);
+                break;
+            }
+
+            for dx in -ring..=ring {
+                // Only the cells on the edges of the ring
+                let step = match dx.abs() == ring {
+                    true => 1,
+                    false => (2 * ring).max(1) as usize,
+                };
+
+                for dy in (-ring..=ring).step_by(step) {
+                    if let Some(nodes) = self.cells.get(&(center.0 + dx, center.1 + dy)) {
+                        consider(&mut found, nodes);
+                    }
+                }
+            }
+
+            found.sort_by(|a, b| a.1.total_cmp(&b.1));
+            found.truncate(k);
+        }
+
+        found
+    }
+}
+
+// Far out of range coordinates would not even map to a cell
+fn check_coordinates(lat: f64, lon: f64) -> Result<(), Status> {
+    match lat.is_finite() && lon.is_finite() && lat.abs() <= 90. && lon.abs() <= 180. {
+        true => Ok(()),
+        false => Err(ErrorCollection::invalid_coordinates(lat, lon)),
+    }
+}
+
+fn index(fragment: &Fragment) -> &SpatialIndex {
+    fragment
+        .spatial_index
+        .get_or_init(|| SpatialIndex::build(&fragment.graph))
+}
+
+// Builds the index of the current fragment, so that the first lookup does not wait for it.
+pub fn build_in_background() {
+    let fragment = globals::fragment();
+
+    tokio::task::spawn_blocking(move || {
+        index(&fragment);
+    });
+}
+
+pub async fn nearest_nodes(request: NearestNodesRequest) -> Result<NearestNodesResult, Status> {
+    check_coordinates(request.lat, request.lon)?;
+
+    let fragment = globals::fragment();
+
+    tokio::task::spawn_blocking(move || {
+        let graph = &fragment.graph;
+        let nearest = index(&fragment).nearest(
+            &fragment,
+            (request.lat, request.lon),
+            request.k as usize,
+            request.max_radius,
+            request.routable,
+        );
+
+        let nodes = nearest
+            .into_iter()
+            .map(|(idx, distance)| {
+                let (lat, lon) = graph.coords(idx);
+
+                NearNode {
+                    node_id: graph.node_id(idx),
+                    distance,
+                    coords: Some(Coordinates { lat, lon }),
+                }
+            })
+            .collect();
+
+        Ok(NearestNodesResult { nodes })
+    })
+    .await
+    .expect("nearest nodes task panicked")
+}
+
+impl ErrorCollection {
+    fn invalid_coordinates(lat: f64, lon: f64) -> Status {
+        Status::invalid_argument(format!(
+            "Invalid coordinates ({lat}, {lon}); latitude must be within [-90, 90] and \
+            longitude within [-180, 180]"
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph_store::{SPQGraphBuilder, TestRng};
+
+    // Grid of `side` x `side` nodes with jittered coordinates, starting at `origin`. Every third
+    // node has no edges.
+    fn grid_fragment(origin: (f64, f64), side: u64, spacing: f64) -> Fragment {
+        let mut rng = TestRng::new();
+        let mut builder = SPQGraphBuilder::grid(origin, side, spacing, spacing, &mut rng);
+
+        for id in (0..side * side - 1).filter(|id| id % 3 != 0 && (id + 1) % 3 != 0) {
+            builder.add_edge(id, id + 1, None, [1, 1]);
+        }
+
+        let (graph, mapping) = builder.build().unwrap();
+        Fragment::new(graph, mapping, 1)
+    }
+
+    fn brute_force(
+        fragment: &Fragment,
+        coords: (f64, f64),
+        k: usize,
+        max_radius: Option<f64>,
+        routable: bool,
+    ) -> Vec<f64> {
+        let mut distances: Vec<f64> = (0..fragment.graph.ids.len() as NodeIdx)
+            .filter(|idx| {
+                !routable
+                    || fragment.edges(*idx).next().is_some()
+                    || fragment.reverse_edges(*idx).next().is_some()
+            })
+            .map(|idx| distance_meters(coords, fragment.graph.coords(idx)))
+            .filter(|distance| !matches!(max_radius, Some(max_radius) if *distance > max_radius))
+            .collect();
+
+        distances.sort_by(|a, b| a.total_cmp(b));
+        distances.truncate(k);
+        distances
+    }
+
+    fn assert_matches_brute_force(fragment: &Fragment, queries: &[(f64, f64)]) {
+        let index = SpatialIndex::build(&fragment.graph);
+
+        for &coords in queries {
+            for (k, max_radius, routable) in [
+                (1, None, false),
+                (5, None, false),
+                (5, None, true),
+                (20, Some(1_500.), false),
+                (3, Some(10.), true),
+            ] {
+                let found: Vec<f64> = index
+                    .nearest(fragment, coords, k, max_radius, routable)
+                    .into_iter()
+                    .map(|(_, distance)| distance)
+                    .collect();
+
+                assert_eq!(
+                    found,
+                    brute_force(fragment, coords, k, max_radius, routable),
+                    "query at {coords:?}, k {k}, max radius {max_radius:?}, routable {routable}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn nearest_matches_brute_force() {
+        let fragment = grid_fragment((50., 19.), 40, 0.004);
+        let queries = [
+            (50.05, 19.05),
+            (50.001, 19.1555),
+            (50.16, 19.16),
+            (49.99, 18.99),
+        ];
+
+        assert_matches_brute_force(&fragment, &queries);
+    }
+
+    // Cells narrow towards the poles, so the rings cover less distance along the longitude
+    #[test]
+    fn nearest_matches_brute_force_at_high_latitude() {
+        let fragment = grid_fragment((78., 15.), 40, 0.004);
+        let queries = [(78.05, 15.05), (78.08, 15.159), (78.0, 15.0)];
+
+        assert_matches_brute_force(&fragment, &queries);
+    }
+
+    // Most rings around a far query are empty, so the remaining cells are searched at once
+    #[test]
+    fn nearest_matches_brute_force_far_from_fragment() {
+        let fragment = grid_fragment((50., 19.), 10, 0.004);
+        let queries = [(50.5, 19.5), (48., 17.)];
+
+        assert_matches_brute_force(&fragment, &queries);
+    }
+
+    #[test]
+    fn nearest_of_empty_or_zero() {
+        let fragment = grid_fragment((50., 19.), 5, 0.004);
+        let index = SpatialIndex::build(&fragment.graph);
+
+        assert!(index
+            .nearest(&fragment, (50., 19.), 0, None, false)
+            .is_empty());
+        assert!(index
+            .nearest(&fragment, (50., 19.), 3, Some(0.), false)
+            .is_empty());
+        assert_eq!(
+            index.nearest(&fragment, (50., 19.), 100, None, false).len(),
+            25
+        );
+    }
+
+    #[test]
+    fn invalid_coordinates_are_rejected() {
+        for (lat, lon) in [(-90., -180.), (90., 180.), (50., 19.)] {
+            assert!(check_coordinates(lat, lon).is_ok());
+        }
+
+        let invalid = [
+            (f64::NAN, 19.),
+            (50., f64::NAN),
+            (f64::INFINITY, 19.),
+            (50., f64::NEG_INFINITY),
+            (90.5, 19.),
+            (-91., 19.),
+            (50., 180.5),
+            (50., -1e9),
+        ];
+
+        for (lat, lon) in invalid {
+            let error = check_coordinates(lat, lon).unwrap_err();
+            assert_eq!(error.code(), tonic::Code::InvalidArgument);
+        }
+    }
+}
@@ -0,0 +1,116 @@
+use std::fs::File;
+use std::io;
+use std::marker::PhantomData;
+use std::ops::Deref;
+use std::sync::Arc;
+
+use memmap2::Mmap;
+
+/// Types which can be reinterpreted directly from the little-endian bytes of the fragment file.
+///
+/// # Safety
+///
+/// The type must have no invalid bit patterns and alignment of at most 8 (sections in the
+/// fragment file are aligned to 8 bytes).
+pub unsafe trait Plain: Copy + 'static {}
+
+unsafe impl Plain for u32 {}
+unsafe impl Plain for u64 {}
+unsafe impl<const N: usize> Plain for [u64; N] {}
+unsafe impl Plain for [f64; 2] {}
+
+// Read-only mapping of the whole fragment file, shared by all of its sections.
+#[derive(Debug, Clone)]
+pub struct MappedFile(Arc<Mmap>);
+
+impl MappedFile {
+    pub fn open(file: &File) -> io::Result<Self> {
+        // Safety: the fragment files are never modified in place; new snapshots are written to
+        // a temporary file and renamed over the old one, which leaves our mapping intact.
+        let map = unsafe { Mmap::map(file)? };
+        Ok(MappedFile(Arc::new(map)))
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+// Array of `T` that lives either on the heap or in the mapped fragment file.
+#[derive(Debug)]
+pub enum Section<T: Plain> {
+    Owned(Vec<T>),
+    Mapped {
+        file: MappedFile,
+        offset: usize,
+        len: usize,
+        _type: PhantomData<T>,
+    },
+}
+
+impl<T: Plain> Section<T> {
+    // Fails if the section does not fit in the file or is not properly aligned.
+    pub fn mapped(file: &MappedFile, offset: usize, len: usize) -> io::Result<Self> {
+        let size = len.checked_mul(std::mem::size_of::<T>());
+        let end = size.and_then(|size| size.checked_add(offset));
+
+        if !matches!(end, Some(end) if end <= file.len()) {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "section exceeds the fragment file",
+            ));
+        }
+
+        let start = file.bytes()[offset..].as_ptr();
+
+        if start.align_offset(std::mem::align_of::<T>()) != 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "misaligned section in the fragment file",
+            ));
+        }
+
+        Ok(Section::Mapped {
+            file: file.clone(),
+            offset,
+            len,
+            _type: PhantomData,
+        })
+    }
+}
+
+impl<T: Plain> Default for Section<T> {
+    fn default() -> Self {
+        Section::Owned(Vec::new())
+    }
+}
+
+impl<T: Plain> From<Vec<T>> for Section<T> {
+    fn from(vec: Vec<T>) -> Self {
+        Section::Owned(vec)
+    }
+}
+
+impl<T: Plain> Deref for Section<T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        match self {
+            Section::Owned(vec) => vec,
+            Section::Mapped {
+                file, offset, len, ..
+            } => {
+                // Safety: bounds and alignment were checked in `Section::mapped`, `T` is `Plain`
+                // and the mapping is kept alive by `file`.
+                unsafe {
+                    let ptr = file.bytes().as_ptr().add(*offset) as *const T;
+                    std::slice::from_raw_parts(ptr, *len)
+                }
+            }
+        }
+    }
+}
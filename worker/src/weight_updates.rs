@@ -0,0 +1,235 @@
+use tonic::Status;
+
+use generated::worker::edge_weight_update::Change;
+use generated::worker::{EdgeWeightUpdate, NodePair};
+
+use crate::globals::{self, Fragment};
+use crate::graph_store::{EdgeIdx, EdgeOverride, EdgeWeights, IdIdxMapper, Metric, NodeId};
+use crate::graph_store::{
+    NodePointer, OverrideMap, SomeGraphMethods, WeightOverrides, METRICS_COUNT,
+};
+use crate::ErrorCollection;
+
+// Parsed `EdgeWeightUpdate`
+struct Update {
+    from: NodeId,
+    to: NodeId,
+    metric: Metric,
+    change: Change,
+}
+
+fn parse(update: &EdgeWeightUpdate) -> Result<Update, Status> {
+    let metric = Metric::from_i32(update.metric)
+        .ok_or_else(|| ErrorCollection::unknown_metric(update.metric))?;
+
+    let change = update
+        .change
+        .clone()
+        .ok_or_else(ErrorCollection::missing_change)?;
+
+    Ok(Update {
+        from: update.node_from_id,
+        to: update.node_to_id,
+        metric,
+        change,
+    })
+}
+
+fn change_weights(
+    overrides: &mut OverrideMap,
+    position: EdgeIdx,
+    base: EdgeWeights,
+    update: &Update,
+) {
+    let mut edge = overrides.get(&position).copied().unwrap_or(EdgeOverride {
+        weights: base,
+        closed: false,
+    });
+
+    match update.change {
+        Change::Closed(closed) => edge.closed = closed,
+        // A closed edge stays closed; the new weight applies once it is reopened
+        Change::NewWeight(weight) => edge.weights[update.metric as usize] = weight,
+    }
+
+    match edge.closed || edge.weights != base {
+        true => overrides.insert(position, edge),
+        false => overrides.remove(&position),
+    };
+}
+
+// Updates outgoing edges of `from` and incoming edges of `to`, whichever of them is domestic.
+// Edges are looked up without the overrides, so that closed edges can be reopened.
+fn apply_one(fragment: &Fragment, overrides: &mut WeightOverrides, update: &Update) -> bool {
+    let graph = &fragment.graph;
+    let mut matched = false;
+
+    let node_id = |pointer| match pointer {
+        NodePointer::Domestic(idx) => graph.node_id(idx),
+        NodePointer::Foreign(id, _) => id,
+    };
+
+    if let Some(from_idx) = fragment.mapping.find(update.from) {
+        for (position, edge) in graph.edges(from_idx).with_positions() {
+            if node_id(edge.to) == update.to {
+                change_weights(&mut overrides.forward, position, edge.weights, update);
+                matched = true;
+            }
+        }
+    }
+
+    if let Some(to_idx) = fragment.mapping.find(update.to) {
+        for (position, edge) in graph.reverse_edges(to_idx).with_positions() {
+            if node_id(edge.to) == update.from {
+                change_weights(&mut overrides.reverse, position, edge.weights, update);
+                matched = true;
+            }
+        }
+    }
+
+    matched
+}
+
+// Applies the whole batch as a single new version of the fragment. Returns the new version and
+// positions of the updates which matched some edge.
+pub fn apply(updates: &[EdgeWeightUpdate]) -> Result<(u64, Vec<u32>), Status> {
+    let updates = updates.iter().map(parse).collect::<Result<Vec<_>, _>>()?;
+
+    let (version, matched) = globals::update_overrides(|fragment, overrides| {
+        let mut changed = [false; METRICS_COUNT];
+
+        let matched = updates
+            .iter()
+            .enumerate()
+            .filter(|(_, update)| apply_one(fragment, overrides, update))
+            .map(|(position, update)| {
+                match update.change {
+                    // Closed edges are skipped in all metrics
+                    Change::Closed(_) => changed = [true; METRICS_COUNT],
+                    Change::NewWeight(_) => changed[update.metric as usize] = true,
+                }
+
+                position as u32
+            })
+            .collect::<Vec<_>>();
+
+        (matched, changed)
+    });
+
+    info!(
+        "applied {} of {} weight updates, graph version is {version}",
+        matched.len(),
+        updates.len()
+    );
+
+    Ok((version, matched))
+}
+
+// Current weights of the lightest open edges between the pairs of nodes
+pub fn lookup(metric: Metric, edges: &[NodePair]) -> Result<Vec<u64>, Status> {
+    let fragment = globals::fragment();
+    let graph = &fragment.graph;
+
+    edges
+        .iter()
+        .map(|pair| {
+            let from_idx = fragment.mapping.get_mapping(pair.node_from_id)?;
+
+            fragment
+                .edges(from_idx)
+                .filter(|edge| match edge.to {
+                    NodePointer::Domestic(idx) => graph.node_id(idx) == pair.node_to_id,
+                    NodePointer::Foreign(id, _) => id == pair.node_to_id,
+                })
+                .map(|edge| edge.weight(metric))
+                .min()
+                .ok_or_else(|| ErrorCollection::edge_not_found(pair))
+        })
+        .collect()
+}
+
+impl ErrorCollection {
+    fn edge_not_found(pair: &NodePair) -> Status {
+        Status::not_found(format!(
+            "no open edge[{} -> {}]",
+            pair.node_from_id, pair.node_to_id
+        ))
+    }
+
+    fn unknown_metric(metric: i32) -> Status {
+        Status::invalid_argument(format!("unknown metric {metric}"))
+    }
+
+    fn missing_change() -> Status {
+        Status::invalid_argument("EdgeWeightUpdate must set `new_weight` or `closed`")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn update(metric: Metric, change: Change) -> Update {
+        Update {
+            from: 1,
+            to: 2,
+            metric,
+            change,
+        }
+    }
+
+    #[test]
+    fn new_weight_keeps_closed_edge_closed() {
+        let base = [10, 20];
+        let mut overrides = OverrideMap::new();
+
+        change_weights(
+            &mut overrides,
+            7,
+            base,
+            &update(Metric::Distance, Change::Closed(true)),
+        );
+        change_weights(
+            &mut overrides,
+            7,
+            base,
+            &update(Metric::TravelTime, Change::NewWeight(5)),
+        );
+
+        let edge = overrides[&7];
+        assert!(edge.closed);
+        assert_eq!(edge.weights, [10, 5]);
+
+        change_weights(
+            &mut overrides,
+            7,
+            base,
+            &update(Metric::Distance, Change::Closed(false)),
+        );
+
+        let edge = overrides[&7];
+        assert!(!edge.closed);
+        assert_eq!(edge.weights, [10, 5]);
+    }
+
+    #[test]
+    fn reopened_edge_with_base_weights_is_not_overridden() {
+        let base = [10, 20];
+        let mut overrides = OverrideMap::new();
+
+        change_weights(
+            &mut overrides,
+            7,
+            base,
+            &update(Metric::Distance, Change::Closed(true)),
+        );
+        change_weights(
+            &mut overrides,
+            7,
+            base,
+            &update(Metric::Distance, Change::Closed(false)),
+        );
+
+        assert!(overrides.is_empty());
+    }
+}
@@ -6,20 +6,39 @@ use tonic::{Request, Response, Status};
 use generated::worker::request_djikstra;
 use generated::worker::worker_server::Worker;
 use generated::worker::{
-    ArePresent, Coordinates, ForgetQueryMessage, NodeIds, RequestBacktrack, RequestCoordinates,
-    RequestDjikstra, ResponseBacktrack, ResponseDjikstra,
+    ArePresent, BoundaryOverlay, BoundaryOverlayRequest, Coordinates, EdgeWeightUpdates,
+    EdgeWeightUpdatesResult, EdgeWeightsRequest, EdgeWeightsResult, ForgetQueryMessage,
+    GraphVersion, LocalPath, LocalPathRequest, LocalSearchRequest, LocalSearchResult,
+    NearestNodesRequest, NearestNodesResult, NodeIdList, NodeIdRange, NodeIdRanges, NodeIds,
+    ReachableNode, RequestBacktrack, RequestCoordinates, RequestDjikstra, RequestReachable,
+    ResponseBacktrack, ResponseDjikstra,
 };
 
+use crate::boundary_overlay;
+use crate::contraction;
 use crate::globals;
+use crate::graph_receiver::GraphReceiver;
 use crate::graph_store::{IdIdxMapper, SomeGraphMethods};
 use crate::query_realizator;
+use crate::spatial_index;
+use crate::weight_updates;
 use crate::ErrorCollection;
 
-pub struct WorkerService {}
+// Batch size of `ListNodeIds`, keeping the messages well below the size limit of gRPC
+const NODE_ID_RANGES_PER_MESSAGE: usize = 16_384;
+
+pub struct WorkerService {
+    receiver: tokio::sync::Mutex<GraphReceiver>,
+    // Whether contraction hierarchies are built for each received fragment
+    build_hierarchies: bool,
+}
 
 impl WorkerService {
-    pub fn new() -> Self {
-        WorkerService {}
+    pub fn new(receiver: GraphReceiver, build_hierarchies: bool) -> Self {
+        WorkerService {
+            receiver: tokio::sync::Mutex::new(receiver),
+            build_hierarchies,
+        }
     }
 }
 
@@ -28,6 +47,10 @@ pub type ResponseDjikstraStream =
     Pin<Box<dyn Stream<Item = Result<ResponseDjikstra, Status>> + Send + 'static>>;
 pub type ResponseBacktrackStream =
     Pin<Box<dyn Stream<Item = Result<ResponseBacktrack, Status>> + Send + 'static>>;
+pub type ReachableNodeStream =
+    Pin<Box<dyn Stream<Item = Result<ReachableNode, Status>> + Send + 'static>>;
+pub type NodeIdRangesStream =
+    Pin<Box<dyn Stream<Item = Result<NodeIdRanges, Status>> + Send + 'static>>;
 
 #[tonic::async_trait]
 impl Worker for WorkerService {
@@ -40,8 +63,9 @@ impl Worker for WorkerService {
             node_to_id,
         } = request.into_inner();
 
-        let node_from_present = globals::mapping().contains_key(&node_from_id);
-        let node_to_present = globals::mapping().contains_key(&node_to_id);
+        let fragment = globals::fragment();
+        let node_from_present = fragment.mapping.find(node_from_id).is_some();
+        let node_to_present = fragment.mapping.find(node_to_id).is_some();
 
         Ok(Response::new(ArePresent {
             node_from_present,
@@ -72,6 +96,17 @@ impl Worker for WorkerService {
         Ok(Response::new(Box::pin(stream)))
     }
 
+    type GetReachableStream = ReachableNodeStream;
+
+    async fn get_reachable(
+        &self,
+        request: Request<RequestReachable>,
+    ) -> Result<Response<ReachableNodeStream>, Status> {
+        let stream = query_realizator::get_reachable_stream(request.into_inner());
+
+        Ok(Response::new(Box::pin(stream)))
+    }
+
     type UpdateDjikstraStream = ResponseDjikstraStream;
 
     async fn update_djikstra(
@@ -93,19 +128,15 @@ impl Worker for WorkerService {
 
         debug!("got `update_djikstra` request: {query_data:?}");
 
-        let processor = globals::processor_holder()
-            .get_or_create(&query_data)
-            .map_err(ErrorCollection::duplicated_request)?;
+        let holder = globals::processor_holder();
+        let processor = holder.get_or_create(&query_data)?;
 
-        let response = query_realizator::update_djikstra(&query_data, processor, inbound).await;
+        // The query is forgotten on an error, and when this future is dropped halfway
+        let guard = holder.busy_guard(query_data.query_id);
+        let response = query_realizator::update_djikstra(&query_data, processor, inbound).await?;
+        guard.disarm();
 
-        match response {
-            Ok(response) => Ok(Response::new(response)),
-            Err(error) => {
-                globals::processor_holder().forget_query(query_data.query_id);
-                Err(error)
-            }
-        }
+        Ok(Response::new(response))
     }
 
     async fn get_node_coordinates(
@@ -114,22 +145,163 @@ impl Worker for WorkerService {
     ) -> Result<Response<Coordinates>, Status> {
         let request = request.into_inner();
 
-        let node_idx = globals::mapping().get_mapping(request.node_id)?;
-        let (lat, lon) = globals::graph().get_node(node_idx).coords;
+        let fragment = globals::fragment();
+        let node_idx = fragment.mapping.get_mapping(request.node_id)?;
+        let (lat, lon) = fragment.graph.coords(node_idx);
 
         Ok(Response::new(Coordinates { lat, lon }))
     }
+
+    async fn reload_graph(&self, _: Request<()>) -> Result<Response<GraphVersion>, Status> {
+        // Concurrent reloads wait for each other; queries are served meanwhile.
+        let mut receiver = self.receiver.lock().await;
+
+        info!("reloading graph fragment");
+        let version = receiver.reload_graph().await?;
+        boundary_overlay::precompute_in_background();
+        spatial_index::build_in_background();
+
+        if self.build_hierarchies {
+            contraction::build_in_background();
+        }
+
+        Ok(Response::new(GraphVersion { version }))
+    }
+
+    async fn ping(&self, _: Request<()>) -> Result<Response<GraphVersion>, Status> {
+        let version = globals::fragment().version;
+
+        Ok(Response::new(GraphVersion { version }))
+    }
+
+    async fn get_boundary_overlay(
+        &self,
+        request: Request<BoundaryOverlayRequest>,
+    ) -> Result<Response<BoundaryOverlay>, Status> {
+        let metric = request.into_inner().metric();
+        let overlay = boundary_overlay::get_overlay(metric).await?;
+
+        Ok(Response::new(overlay))
+    }
+
+    async fn local_search(
+        &self,
+        request: Request<LocalSearchRequest>,
+    ) -> Result<Response<LocalSearchResult>, Status> {
+        let result = boundary_overlay::local_search(request.into_inner()).await?;
+
+        Ok(Response::new(result))
+    }
+
+    async fn get_local_path(
+        &self,
+        request: Request<LocalPathRequest>,
+    ) -> Result<Response<LocalPath>, Status> {
+        let path = boundary_overlay::local_path(request.into_inner()).await?;
+
+        Ok(Response::new(path))
+    }
+
+    type ListNodeIdsStream = NodeIdRangesStream;
+
+    async fn list_node_ids(
+        &self,
+        _request: Request<()>,
+    ) -> Result<Response<NodeIdRangesStream>, Status> {
+        let fragment = globals::fragment();
+
+        let stream = async_stream::try_stream! {
+            let mut ranges: Vec<NodeIdRange> = Vec::new();
+
+            // Entries of the mapping are sorted by id
+            for entry in fragment.mapping.entries() {
+                match ranges.last_mut() {
+                    Some(range) if entry.id - range.last == 1 => range.last = entry.id,
+                    _ => {
+                        if ranges.len() == NODE_ID_RANGES_PER_MESSAGE {
+                            yield NodeIdRanges {
+                                ranges: std::mem::take(&mut ranges),
+                            };
+                        }
+
+                        ranges.push(NodeIdRange {
+                            first: entry.id,
+                            last: entry.id,
+                        });
+                    }
+                }
+            }
+
+            yield NodeIdRanges { ranges };
+        };
+
+        Ok(Response::new(Box::pin(stream)))
+    }
+
+    async fn filter_owned_nodes(
+        &self,
+        request: Request<NodeIdList>,
+    ) -> Result<Response<NodeIdList>, Status> {
+        let fragment = globals::fragment();
+
+        let node_ids = request
+            .into_inner()
+            .node_ids
+            .into_iter()
+            .filter(|node_id| fragment.mapping.find(*node_id).is_some())
+            .collect();
+
+        Ok(Response::new(NodeIdList { node_ids }))
+    }
+
+    async fn nearest_nodes(
+        &self,
+        request: Request<NearestNodesRequest>,
+    ) -> Result<Response<NearestNodesResult>, Status> {
+        let request = request.into_inner();
+
+        debug!("got nearest nodes request: {request:?}");
+
+        let result = spatial_index::nearest_nodes(request).await?;
+
+        Ok(Response::new(result))
+    }
+
+    async fn get_edge_weights(
+        &self,
+        request: Request<EdgeWeightsRequest>,
+    ) -> Result<Response<EdgeWeightsResult>, Status> {
+        let request = request.into_inner();
+        let weights = weight_updates::lookup(request.metric(), &request.edges)?;
+
+        Ok(Response::new(EdgeWeightsResult { weights }))
+    }
+
+    async fn update_edge_weights(
+        &self,
+        request: Request<EdgeWeightUpdates>,
+    ) -> Result<Response<EdgeWeightUpdatesResult>, Status> {
+        let EdgeWeightUpdates { updates } = request.into_inner();
+
+        debug!("got {} edge weight updates", updates.len());
+
+        // Overlays of the new version are computed by the first `GetBoundaryOverlay`, so that
+        // a stream of updates does not keep the blocking threads busy with overlays nobody asks for
+        let (graph_version, matched) = weight_updates::apply(&updates)?;
+
+        if self.build_hierarchies && !matched.is_empty() {
+            contraction::rebuild_after_updates();
+        }
+
+        Ok(Response::new(EdgeWeightUpdatesResult {
+            graph_version,
+            matched,
+        }))
+    }
 }
 
 impl ErrorCollection {
     fn wrong_first_message() -> Status {
         Status::invalid_argument("first message in UpdateDjikstra stream must be query_id")
     }
-
-    fn duplicated_request(e: impl std::error::Error) -> Status {
-        Status::invalid_argument(format!(
-            "executer requested UpdateDjikstra, while another UpdateDjikstra on this \
-            query was already pending: {e}"
-        ))
-    }
 }